@@ -0,0 +1,13 @@
+--- Migrate UP Entry
+
+CREATE TABLE Entry
+	( EntryID INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY
+	, ProjectID INTEGER NOT NULL REFERENCES Project(ProjectID) ON DELETE CASCADE
+	----- Autor des Eintrags; bleibt als NULL erhalten, wenn die Person gelöscht wird
+	, PersonID INTEGER REFERENCES Person(PersonID) ON DELETE SET NULL
+	, EntryTimestamp TIMESTAMPTZ NOT NULL DEFAULT now()
+	, EntryTitle TEXT NOT NULL
+	, EntryBody TEXT NOT NULL
+);
+
+CREATE INDEX Entry_ProjectID ON Entry (ProjectID);
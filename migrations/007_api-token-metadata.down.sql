@@ -0,0 +1,8 @@
+DROP INDEX ApiToken_PersonID;
+ALTER TABLE ApiToken
+	DROP CONSTRAINT ApiToken_TokenHash_unique
+	, DROP COLUMN LastUsedAt
+	, DROP COLUMN ExpiresAt
+	, DROP COLUMN CreatedAt
+	, DROP COLUMN TokenLabel
+	, ALTER COLUMN PersonID DROP NOT NULL;
@@ -0,0 +1,14 @@
+--- Migrate UP ApiToken metadata
+
+ALTER TABLE ApiToken
+	ALTER COLUMN PersonID SET NOT NULL
+	----- vom Nutzer vergebener Name, um Tokens auseinanderzuhalten
+	, ADD COLUMN TokenLabel TEXT NOT NULL DEFAULT ''
+	, ADD COLUMN CreatedAt TIMESTAMPTZ NOT NULL DEFAULT now()
+	----- NULL: das Token läuft nie ab
+	, ADD COLUMN ExpiresAt TIMESTAMPTZ
+	----- NULL: das Token wurde noch nie benutzt
+	, ADD COLUMN LastUsedAt TIMESTAMPTZ
+	, ADD CONSTRAINT ApiToken_TokenHash_unique UNIQUE (TokenHash);
+
+CREATE INDEX ApiToken_PersonID ON ApiToken (PersonID);
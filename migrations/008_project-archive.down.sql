@@ -0,0 +1,2 @@
+ALTER TABLE Project
+	DROP COLUMN IsArchived;
@@ -0,0 +1,5 @@
+--- Migrate UP Project archive
+
+ALTER TABLE Project
+	----- archivierte Projekte sind schreibgeschützt und werden standardmäßig nicht angezeigt
+	ADD COLUMN IsArchived BOOL NOT NULL DEFAULT FALSE;
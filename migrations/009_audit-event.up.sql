@@ -0,0 +1,23 @@
+--- Migrate UP AuditEvent
+
+----- Die Historie soll Personen und Projekte überdauern. Daher werden keine Fremdschlüssel
+----- verwendet, sondern Namen zum Zeitpunkt der Aktion mitgeschrieben.
+CREATE TABLE AuditEvent
+	( AuditEventID INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY
+	, CreatedAt TIMESTAMPTZ NOT NULL DEFAULT now()
+	----- Wer die Aktion ausgeführt hat
+	, ActorName TEXT NOT NULL
+	----- z.B. add_member, rename_project
+	, ActionKind TEXT NOT NULL
+	----- NULL: Die Aktion betrifft kein Projekt (z.B. API-Tokens)
+	, ProjectID INTEGER
+	, ProjectName TEXT
+	----- NULL: Die Aktion betrifft keine andere Person
+	, TargetName TEXT
+	, OldValue TEXT
+	, NewValue TEXT
+);
+
+CREATE INDEX AuditEvent_ProjectID ON AuditEvent (ProjectID);
+CREATE INDEX AuditEvent_ActorName ON AuditEvent (ActorName);
+CREATE INDEX AuditEvent_CreatedAt ON AuditEvent (CreatedAt);
@@ -0,0 +1,6 @@
+ALTER TABLE Project
+	DROP COLUMN Description,
+	DROP COLUMN Status,
+	DROP COLUMN StartDate,
+	DROP COLUMN EndDate,
+	DROP COLUMN ProjectNumber;
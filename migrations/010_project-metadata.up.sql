@@ -0,0 +1,11 @@
+--- Migrate UP Project metadata
+
+ALTER TABLE Project
+	----- Beschreibung des Projekts in Markdown
+	ADD COLUMN Description TEXT NOT NULL DEFAULT '',
+	----- planned, active, paused oder done
+	ADD COLUMN Status TEXT NOT NULL DEFAULT 'active' CHECK (Status IN ('planned', 'active', 'paused', 'done')),
+	ADD COLUMN StartDate DATE,
+	ADD COLUMN EndDate DATE,
+	----- Projektnummer oder Kostenstelle
+	ADD COLUMN ProjectNumber TEXT;
@@ -0,0 +1,4 @@
+DROP INDEX Entry_EntryBody_Trgm;
+DROP INDEX Entry_EntryTitle_Trgm;
+DROP INDEX Project_Description_Trgm;
+DROP INDEX Project_ProjectName_Trgm;
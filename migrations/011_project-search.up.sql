@@ -0,0 +1,7 @@
+--- Migrate UP Project search
+
+----- Trigramm-Indizes für die Suche auf der Startseite
+CREATE INDEX Project_ProjectName_Trgm ON Project USING GIN (ProjectName gin_trgm_ops);
+CREATE INDEX Project_Description_Trgm ON Project USING GIN (Description gin_trgm_ops);
+CREATE INDEX Entry_EntryTitle_Trgm ON Entry USING GIN (EntryTitle gin_trgm_ops);
+CREATE INDEX Entry_EntryBody_Trgm ON Entry USING GIN (EntryBody gin_trgm_ops);
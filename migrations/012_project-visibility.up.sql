@@ -0,0 +1,5 @@
+--- Migrate UP Project visibility
+
+ALTER TABLE Project
+	----- public: alle sehen alles, internal: alle sehen den Namen, secret: nur Mitglieder sehen das Projekt
+	ADD COLUMN Visibility TEXT NOT NULL DEFAULT 'internal' CHECK (Visibility IN ('public', 'internal', 'secret'));
@@ -0,0 +1,5 @@
+ALTER TABLE PersonProjectMap
+	ADD COLUMN IsProjectAdmin BOOL NOT NULL DEFAULT FALSE;
+UPDATE PersonProjectMap SET IsProjectAdmin = TRUE WHERE ProjectRole IN ('maintainer', 'owner');
+ALTER TABLE PersonProjectMap
+	DROP COLUMN ProjectRole;
@@ -0,0 +1,9 @@
+--- Migrate UP project roles
+
+ALTER TABLE PersonProjectMap
+	----- viewer, member, maintainer oder owner
+	ADD COLUMN ProjectRole TEXT NOT NULL DEFAULT 'member' CHECK (ProjectRole IN ('viewer', 'member', 'maintainer', 'owner'));
+----- bisherige Projekt-Admins dürfen alles außer das Projekt zu löschen
+UPDATE PersonProjectMap SET ProjectRole = 'maintainer' WHERE IsProjectAdmin;
+ALTER TABLE PersonProjectMap
+	DROP COLUMN IsProjectAdmin;
@@ -0,0 +1,3 @@
+ALTER TABLE PersonProjectMap
+	DROP COLUMN IsManual;
+DROP TABLE ProjectLdapGroup;
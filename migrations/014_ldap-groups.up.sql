@@ -0,0 +1,12 @@
+--- Migrate UP LDAP groups
+
+CREATE TABLE ProjectLdapGroup (
+	ProjectID INTEGER NOT NULL references Project(ProjectID) ON DELETE CASCADE,
+	----- DN der LDAP-Gruppe, deren Mitglieder in das Projekt übernommen werden
+	GroupDN TEXT NOT NULL,
+	PRIMARY KEY (ProjectID, GroupDN)
+);
+
+ALTER TABLE PersonProjectMap
+	----- true: von Hand hinzugefügt, false: über eine LDAP-Gruppe. Nur letztere entfernt der Abgleich wieder.
+	ADD COLUMN IsManual BOOL NOT NULL DEFAULT TRUE;
@@ -0,0 +1,3 @@
+ALTER TABLE Person
+	DROP COLUMN PersonDisplayName,
+	DROP COLUMN PersonEmail;
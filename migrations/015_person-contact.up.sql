@@ -0,0 +1,7 @@
+--- Migrate UP Person contact details
+
+ALTER TABLE Person
+	----- aus dem in der Konfiguration gewählten LDAP-Attribut
+	ADD COLUMN PersonEmail TEXT,
+	----- wird statt Vor- und Nachname angezeigt, falls gesetzt
+	ADD COLUMN PersonDisplayName TEXT;
@@ -0,0 +1,13 @@
+--- Migrate UP LocalUser
+
+CREATE TABLE LocalUser (
+	LocalUserID INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	LocalUserName TEXT NOT NULL UNIQUE,
+	----- argon2id im PHC-Format
+	PasswordHash TEXT NOT NULL,
+	LocalUserFirstname TEXT,
+	LocalUserSurname TEXT,
+	LocalUserEmail TEXT,
+	LocalUserDisplayName TEXT,
+	IsAdmin BOOL NOT NULL DEFAULT FALSE
+);
@@ -5,22 +5,38 @@
 //! necessary information from the user-supplied data and may then prepare the correct response
 //! themselves.
 
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
 
+use time::OffsetDateTime;
 use tracing::{debug, info};
 
 use crate::{
+    auth::AuthError,
     config::Config,
     db::{
-        add_project, get_person, get_project, remove_members_prepare, try_acquire_connection, update_member_permission, update_project_members_prepare, DBError
+        add_api_token, add_audit_event, add_project_ldap_group, add_project_prepare,
+        archive_project_prepare, delete_project_prepare, get_entry, get_person, get_project,
+        provision_person, remove_members_prepare, remove_project_ldap_group,
+        set_project_visibility as set_project_visibility_in_db, try_acquire_connection,
+        update_member_permission, update_project_members_prepare,
+        update_project_metadata as update_project_metadata_in_db, DBError,
     },
     matrix::MatrixClientError,
-    types::{DbNoMatrix, FullId, NoId, Person, Project, UserPermission},
+    permissions::{
+        may_administer_instance, may_change_entry, may_change_role, require_access, AccessDenied,
+        ProjectAccess,
+    },
+    types::{
+        hash_api_token_secret, ApiToken, AuditAction, AuditEvent, DbNoMatrix, Entry, FullId, NoId,
+        Person, Project, ProjectMetadata, ProjectRole, ProjectVisibility,
+    },
 };
 
 #[derive(Debug)]
 pub(super) enum AddMemberError {
     ProjectDoesNotExist,
+    /// Name of the Project, which is archived and cannot be changed anymore
+    ArchivedProject(String),
     /// Name of the Project the requester wanted to add to
     /// (the caller does not know how that project is called yet)
     RequesterHasNoPermission(String),
@@ -34,6 +50,9 @@ impl core::fmt::Display for AddMemberError {
             Self::ProjectDoesNotExist => {
                 write!(f, "The project does not exist.")
             }
+            Self::ArchivedProject(x) => {
+                write!(f, "The project {x} is archived.")
+            }
             Self::PersonDoesNotExist => {
                 write!(f, "The person does not exist.")
             }
@@ -63,6 +82,7 @@ impl From<MatrixClientError> for AddMemberError {
 /// Return:
 /// - the person that was aded to the project
 /// - the project the person was added to
+///
 /// Or the appropriate error
 pub(super) async fn add_member_to_project(
     config: Arc<Config>,
@@ -88,13 +108,15 @@ pub(super) async fn add_member_to_project(
         }
     };
 
-    let user_may_add_member_to_this_group = match project.local_permission_for_user(&requester) {
-        Some(UserPermission::Admin) => true,
-        Some(UserPermission::User) => requester.is_global_admin(),
-        None => requester.is_global_admin(),
+    match require_access(requester, &project, ProjectAccess::Maintainer) {
+        Ok(_) => (),
+        Err(AccessDenied::Hidden) => return Err(AddMemberError::ProjectDoesNotExist),
+        Err(AccessDenied::Insufficient) => {
+            return Err(AddMemberError::RequesterHasNoPermission(project.name));
+        }
     };
-    if !user_may_add_member_to_this_group {
-        return Err(AddMemberError::RequesterHasNoPermission(project.name));
+    if project.is_archived {
+        return Err(AddMemberError::ArchivedProject(project.name));
     };
 
     // The user is allowed to add members to project.
@@ -110,10 +132,17 @@ pub(super) async fn add_member_to_project(
     };
 
     // Everything okay. Add the new member.
-    project.add_member(new_member.clone(), UserPermission::User);
+    project.add_member(new_member.clone(), ProjectRole::Member);
 
     match update_project_members_prepare(config.pg_pool.clone(), &project).await {
-        Ok(tx) => {
+        Ok(mut tx) => {
+            let event = AuditEvent::new(requester, AuditAction::AddMember)
+                .with_project(&project)
+                .with_target(&new_member)
+                .with_values(None, Some(ProjectRole::Member.to_string()));
+            add_audit_event(&mut tx, &event)
+                .await
+                .map_err(AddMemberError::DB)?;
             debug!(
                 "Prepared a transaction to add {} to {}. Now trying to add to Matrix...",
                 new_member.name, project.name
@@ -141,6 +170,8 @@ pub(super) async fn add_member_to_project(
 #[derive(Debug)]
 pub(super) enum RemoveMemberError {
     ProjectDoesNotExist,
+    /// Name of the Project, which is archived and cannot be changed anymore
+    ArchivedProject(String),
     /// Name of the Project the requester wanted to add to
     /// (the caller does not know how that project is called yet)
     RequesterHasNoPermission(String),
@@ -154,6 +185,9 @@ impl core::fmt::Display for RemoveMemberError {
             Self::ProjectDoesNotExist => {
                 write!(f, "The project does not exist.")
             }
+            Self::ArchivedProject(x) => {
+                write!(f, "The project {x} is archived.")
+            }
             Self::PersonDoesNotExist => {
                 write!(f, "The person does not exist.")
             }
@@ -183,6 +217,7 @@ impl From<MatrixClientError> for RemoveMemberError {
 /// Return:
 /// - the person that was removed from the project
 /// - the project the person was removed from
+///
 /// Or the appropriate error
 pub(super) async fn remove_member_from_project(
     config: Arc<Config>,
@@ -208,14 +243,15 @@ pub(super) async fn remove_member_from_project(
         }
     };
 
-    let user_may_remove_member_from_this_group = match project.local_permission_for_user(&requester)
-    {
-        Some(UserPermission::Admin) => true,
-        Some(UserPermission::User) => requester.is_global_admin(),
-        None => requester.is_global_admin(),
+    let access = match require_access(requester, &project, ProjectAccess::Maintainer) {
+        Ok(x) => x,
+        Err(AccessDenied::Hidden) => return Err(RemoveMemberError::ProjectDoesNotExist),
+        Err(AccessDenied::Insufficient) => {
+            return Err(RemoveMemberError::RequesterHasNoPermission(project.name));
+        }
     };
-    if !user_may_remove_member_from_this_group {
-        return Err(RemoveMemberError::RequesterHasNoPermission(project.name));
+    if project.is_archived {
+        return Err(RemoveMemberError::ArchivedProject(project.name));
     };
 
     // The user is allowed to remove members to project.
@@ -229,15 +265,30 @@ pub(super) async fn remove_member_from_project(
             return Err(RemoveMemberError::DB(e));
         }
     };
+    let old_role = project.local_role_for_user(&remove_member);
+    if !may_change_role(access, old_role, None) {
+        return Err(RemoveMemberError::RequesterHasNoPermission(project.name));
+    };
 
     match remove_members_prepare(config.pg_pool.clone(), project.db_id(), &[&remove_member]).await {
-        Ok((_num_deleted, tx)) => {
+        Ok((_num_deleted, mut tx)) => {
+            let event = AuditEvent::new(requester, AuditAction::RemoveMember)
+                .with_project(&project)
+                .with_target(&remove_member)
+                .with_values(old_role.map(|x| x.to_string()), None);
+            add_audit_event(&mut tx, &event)
+                .await
+                .map_err(RemoveMemberError::DB)?;
             debug!(
                 "Prepared a transaction to remove {} from {}. Now trying to remove from Matrix...",
                 remove_member.name, project.name
             );
             // now try to make the deletion from Matrix
+            // the power level would otherwise come back if they are added again
             let mut our_client = config.matrix_client.clone();
+            our_client
+                .reset_power_level(&remove_member, &project)
+                .await?;
             our_client
                 .ensure_user_not_in_room(&remove_member, &project)
                 .await?;
@@ -260,11 +311,14 @@ pub(super) async fn remove_member_from_project(
 #[derive(Debug)]
 pub(super) enum SetPermissionError {
     ProjectDoesNotExist,
+    /// Name of the Project, which is archived and cannot be changed anymore
+    ArchivedProject(String),
     /// Name of the Project the requester wanted to add to
     /// (the caller does not know how that project is called yet)
     RequesterHasNoPermission(String),
     PersonDoesNotExist,
     DB(DBError),
+    Matrix(MatrixClientError),
 }
 impl core::fmt::Display for SetPermissionError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -272,6 +326,9 @@ impl core::fmt::Display for SetPermissionError {
             Self::ProjectDoesNotExist => {
                 write!(f, "The project does not exist.")
             }
+            Self::ArchivedProject(x) => {
+                write!(f, "The project {x} is archived.")
+            }
             Self::PersonDoesNotExist => {
                 write!(f, "The person does not exist.")
             }
@@ -281,17 +338,25 @@ impl core::fmt::Display for SetPermissionError {
             Self::DB(e) => {
                 write!(f, "The DB returned this error: {e}.")
             }
+            Self::Matrix(e) => {
+                write!(f, "Error communicating with matrix server: {e}")
+            }
         }
     }
 }
 impl std::error::Error for SetPermissionError {}
+impl From<MatrixClientError> for SetPermissionError {
+    fn from(value: MatrixClientError) -> Self {
+        Self::Matrix(value)
+    }
+}
 
 pub async fn set_member_permission(
     config: Arc<Config>,
     requester: &Person<DbNoMatrix>,
     change_member_name: &str,
     project_id: i32,
-    new_permission: UserPermission,
+    new_role: ProjectRole,
 ) -> Result<(Person<DbNoMatrix>, Project<FullId>), SetPermissionError> {
     // the permission to do this depends on the project, so we need to get that before checking
     // permission
@@ -311,20 +376,22 @@ pub async fn set_member_permission(
         }
     };
 
-    let user_may_set_member_permissions = match project.local_permission_for_user(&requester) {
-        Some(UserPermission::Admin) => true,
-        Some(UserPermission::User) => requester.is_global_admin(),
-        None => requester.is_global_admin(),
+    let access = match require_access(requester, &project, ProjectAccess::Maintainer) {
+        Ok(x) => x,
+        Err(AccessDenied::Hidden) => return Err(SetPermissionError::ProjectDoesNotExist),
+        Err(AccessDenied::Insufficient) => {
+            return Err(SetPermissionError::RequesterHasNoPermission(
+                project.name.clone(),
+            ));
+        }
     };
-    if !user_may_set_member_permissions {
-        return Err(SetPermissionError::RequesterHasNoPermission(
-            project.name.clone(),
-        ));
+    if project.is_archived {
+        return Err(SetPermissionError::ArchivedProject(project.name));
     };
 
     // The user is allowed to set member permissions on this project.
     // Now we need to make sure the requested member is actually a known user.
-    let change_member = match get_person(config.pg_pool.clone(), &change_member_name).await {
+    let change_member = match get_person(config.pg_pool.clone(), change_member_name).await {
         Ok(Some(x)) => x,
         Ok(None) => {
             return Err(SetPermissionError::PersonDoesNotExist);
@@ -333,24 +400,52 @@ pub async fn set_member_permission(
             return Err(SetPermissionError::DB(e));
         }
     };
+    let old_role = project.local_role_for_user(&change_member);
+    if old_role.is_none() {
+        // only members have a role in this project
+        return Err(SetPermissionError::PersonDoesNotExist);
+    };
+    if !may_change_role(access, old_role, Some(new_role)) {
+        return Err(SetPermissionError::RequesterHasNoPermission(
+            project.name.clone(),
+        ));
+    };
 
-    match update_member_permission(
-        config.pg_pool.clone(),
-        project_id,
-        change_member.db_id(),
-        new_permission,
-    )
-    .await
-    {
-        Ok(()) => {
-            info!(
-                "Updated permission for {} in {}; is now {}; request made by {}.",
-                change_member.name, project.name, new_permission, requester.name
-            );
-            Ok((change_member, project))
-        }
-        Err(e) => Err(SetPermissionError::DB(e)),
-    }
+    let mut tx = config
+        .pg_pool
+        .begin()
+        .await
+        .map_err(|e| SetPermissionError::DB(DBError::CannotStartTransaction(e)))?;
+    update_member_permission(&mut tx, project_id, change_member.db_id(), new_role)
+        .await
+        .map_err(SetPermissionError::DB)?;
+    let event = AuditEvent::new(requester, AuditAction::SetMemberPermission)
+        .with_project(&project)
+        .with_target(&change_member)
+        .with_values(old_role.map(|x| x.to_string()), Some(new_role.to_string()));
+    add_audit_event(&mut tx, &event)
+        .await
+        .map_err(SetPermissionError::DB)?;
+    debug!(
+        "Prepared a transaction to make {} {} in {}. Now trying to set their power level in Matrix...",
+        change_member.name, new_role.as_str(), project.name
+    );
+    let mut our_client = config.matrix_client.clone();
+    our_client
+        .set_power_level(&change_member, &project, new_role)
+        .await?;
+    debug!("Successfully set the power level of {} in {} in Matrix. Now trying to commit the held DB transaction...", change_member.name, project.name);
+    tx.commit()
+        .await
+        .map_err(|e| SetPermissionError::DB(DBError::CannotCommitTransaction(e)))?;
+    info!(
+        "Updated permission for {} in {}; is now {}; request made by {}.",
+        change_member.name,
+        project.name,
+        new_role.as_str(),
+        requester.name
+    );
+    Ok((change_member, project))
 }
 
 /// The errors that can occur while trying to create a project.
@@ -384,7 +479,7 @@ pub async fn create_project(
     requester: &Person<DbNoMatrix>,
     new_project_name: String,
 ) -> Result<Project<FullId>, CreateProjectError> {
-    if requester.global_permission != UserPermission::Admin {
+    if !may_administer_instance(requester) {
         return Err(CreateProjectError::RequesterHasNoPermission);
     };
 
@@ -409,9 +504,18 @@ pub async fn create_project(
     );
 
     // create it in the db
-    let idd_project = add_project(config.pg_pool.clone(), midd_project)
+    let (mut tx, idd_project) = add_project_prepare(config.pg_pool.clone(), midd_project)
+        .await
+        .map_err(CreateProjectError::DB)?;
+    let event = AuditEvent::new(requester, AuditAction::CreateProject)
+        .with_project(&idd_project)
+        .with_values(None, Some(idd_project.name.clone()));
+    add_audit_event(&mut tx, &event)
         .await
         .map_err(CreateProjectError::DB)?;
+    tx.commit()
+        .await
+        .map_err(|e| CreateProjectError::DB(DBError::CannotCommitTransaction(e)))?;
     info!(
         "Created Project {}; request made by {}.",
         idd_project.name, requester.name
@@ -427,6 +531,8 @@ pub(super) enum RenameProjectError {
     /// (the caller does not know how that project is called yet)
     RequesterHasNoPermission(String),
     ProjectDoesNotExist,
+    /// Name of the Project, which is archived and cannot be changed anymore
+    ArchivedProject(String),
     DB(DBError),
     Matrix(MatrixClientError),
 }
@@ -439,6 +545,9 @@ impl core::fmt::Display for RenameProjectError {
             Self::ProjectDoesNotExist => {
                 write!(f, "The project does not exist.")
             }
+            Self::ArchivedProject(x) => {
+                write!(f, "The project {x} is archived.")
+            }
             Self::DB(e) => {
                 write!(f, "The DB returned this error: {e}.")
             }
@@ -476,23 +585,1181 @@ pub async fn rename_project(
         .await?
         .ok_or(RenameProjectError::ProjectDoesNotExist)?;
 
-    // user needs either global or local admin permissions
-    if !requester.is_global_admin()
-        && project.local_permission_for_user(requester) == Some(UserPermission::User)
-    {
-        return Err(RenameProjectError::RequesterHasNoPermission(project.name));
-    }
+    match require_access(requester, &project, ProjectAccess::Maintainer) {
+        Ok(_) => (),
+        Err(AccessDenied::Hidden) => return Err(RenameProjectError::ProjectDoesNotExist),
+        Err(AccessDenied::Insufficient) => {
+            return Err(RenameProjectError::RequesterHasNoPermission(project.name));
+        }
+    };
+    if project.is_archived {
+        return Err(RenameProjectError::ArchivedProject(project.name));
+    };
 
     // the project exists and the requester has permission to change the name. do it!
     // change name in db and hold transcation
     crate::db::rename_project_in_tx(&mut tx, project_id, &new_project_name).await?;
+    let event = AuditEvent::new(requester, AuditAction::RenameProject)
+        .with_project(&project)
+        .with_values(Some(project.name.clone()), Some(new_project_name.clone()));
+    add_audit_event(&mut tx, &event).await?;
     debug!("Prepared transaction to rename project {} to {} in db. Now trying to rename in matrix...", project.name, &new_project_name);
     // change name in matrix
     let mut our_client = config.matrix_client.clone();
     our_client.set_project_name(&project, new_project_name.clone()).await?;
+    if config.mirror_description_to_topic {
+        our_client
+            .set_project_topic(&project, &project.metadata.description)
+            .await?;
+    };
     debug!("Renamed room {} to {} in matrix. Now trying to commit held transaction...", project.name, &new_project_name);
     // commit transaction
     tx.commit().await.map_err(DBError::CannotCommitTransaction)?;
     info!("Renamed project {} to {}. Request made by {}.", project.name, &new_project_name, requester.name);
     Ok(project)
 }
+
+/// The errors that can occur while trying to change the metadata of a project.
+#[derive(Debug)]
+pub(super) enum UpdateProjectMetadataError {
+    /// Name of the Project the requester wanted to change
+    RequesterHasNoPermission(String),
+    ProjectDoesNotExist,
+    /// Name of the Project, which is archived and cannot be changed anymore
+    ArchivedProject(String),
+    /// The start date is after the end date
+    DatesOutOfOrder,
+    DB(DBError),
+    Matrix(MatrixClientError),
+}
+impl core::fmt::Display for UpdateProjectMetadataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RequesterHasNoPermission(x) => {
+                write!(
+                    f,
+                    "The requester does not have the necessary permissions in group {x}."
+                )
+            }
+            Self::ProjectDoesNotExist => {
+                write!(f, "The project does not exist.")
+            }
+            Self::ArchivedProject(x) => {
+                write!(f, "The project {x} is archived.")
+            }
+            Self::DatesOutOfOrder => {
+                write!(f, "The start date is after the end date.")
+            }
+            Self::DB(e) => {
+                write!(f, "The DB returned this error: {e}.")
+            }
+            Self::Matrix(e) => {
+                write!(f, "Error communicating with matrix server: {e}")
+            }
+        }
+    }
+}
+impl std::error::Error for UpdateProjectMetadataError {}
+impl From<DBError> for UpdateProjectMetadataError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+impl From<MatrixClientError> for UpdateProjectMetadataError {
+    fn from(value: MatrixClientError) -> Self {
+        Self::Matrix(value)
+    }
+}
+
+/// Change the description, status, dates and project number of a project.
+///
+/// Returns the project with the new metadata.
+pub async fn update_project_metadata(
+    config: Arc<Config>,
+    requester: &Person<DbNoMatrix>,
+    project_id: i32,
+    metadata: ProjectMetadata,
+) -> Result<Project<FullId>, UpdateProjectMetadataError> {
+    if !metadata.dates_are_ordered() {
+        return Err(UpdateProjectMetadataError::DatesOutOfOrder);
+    };
+    let mut tx = config
+        .pg_pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let mut project = get_project(&mut tx, project_id)
+        .await?
+        .ok_or(UpdateProjectMetadataError::ProjectDoesNotExist)?;
+
+    match require_access(requester, &project, ProjectAccess::Maintainer) {
+        Ok(_) => (),
+        Err(AccessDenied::Hidden) => return Err(UpdateProjectMetadataError::ProjectDoesNotExist),
+        Err(AccessDenied::Insufficient) => {
+            return Err(UpdateProjectMetadataError::RequesterHasNoPermission(
+                project.name,
+            ));
+        }
+    };
+    if project.is_archived {
+        return Err(UpdateProjectMetadataError::ArchivedProject(project.name));
+    };
+
+    update_project_metadata_in_db(&mut tx, project_id, &metadata).await?;
+    let event = AuditEvent::new(requester, AuditAction::EditProjectMetadata)
+        .with_project(&project)
+        .with_values(Some(project.metadata.summary()), Some(metadata.summary()));
+    add_audit_event(&mut tx, &event).await?;
+    if config.mirror_description_to_topic && project.metadata.description != metadata.description {
+        debug!("Prepared transaction to change metadata of {} in db. Now trying to set the topic in matrix...", project.name);
+        let mut our_client = config.matrix_client.clone();
+        our_client
+            .set_project_topic(&project, &metadata.description)
+            .await?;
+    };
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+    info!(
+        "Changed the metadata of project {}. Request made by {}.",
+        project.name, requester.name
+    );
+    project.metadata = metadata;
+    Ok(project)
+}
+
+/// The errors that can occur while trying to change the visibility of a project.
+#[derive(Debug)]
+pub(super) enum SetVisibilityError {
+    /// Name of the Project the requester wanted to change
+    RequesterHasNoPermission(String),
+    ProjectDoesNotExist,
+    /// Name of the Project, which is archived and cannot be changed anymore
+    ArchivedProject(String),
+    DB(DBError),
+}
+impl core::fmt::Display for SetVisibilityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RequesterHasNoPermission(x) => {
+                write!(
+                    f,
+                    "The requester does not have the necessary permissions in group {x}."
+                )
+            }
+            Self::ProjectDoesNotExist => {
+                write!(f, "The project does not exist.")
+            }
+            Self::ArchivedProject(x) => {
+                write!(f, "The project {x} is archived.")
+            }
+            Self::DB(e) => {
+                write!(f, "The DB returned this error: {e}.")
+            }
+        }
+    }
+}
+impl std::error::Error for SetVisibilityError {}
+impl From<DBError> for SetVisibilityError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+
+/// Change who can see a project.
+///
+/// Returns the project with the new visibility.
+pub async fn set_project_visibility(
+    config: Arc<Config>,
+    requester: &Person<DbNoMatrix>,
+    project_id: i32,
+    visibility: ProjectVisibility,
+) -> Result<Project<FullId>, SetVisibilityError> {
+    let mut tx = config
+        .pg_pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let mut project = get_project(&mut tx, project_id)
+        .await?
+        .ok_or(SetVisibilityError::ProjectDoesNotExist)?;
+    match require_access(requester, &project, ProjectAccess::Maintainer) {
+        Ok(_) => (),
+        Err(AccessDenied::Hidden) => return Err(SetVisibilityError::ProjectDoesNotExist),
+        Err(AccessDenied::Insufficient) => {
+            return Err(SetVisibilityError::RequesterHasNoPermission(project.name));
+        }
+    };
+    if project.is_archived {
+        return Err(SetVisibilityError::ArchivedProject(project.name));
+    };
+
+    set_project_visibility_in_db(&mut tx, project_id, visibility).await?;
+    let event = AuditEvent::new(requester, AuditAction::SetProjectVisibility)
+        .with_project(&project)
+        .with_values(
+            Some(project.visibility.to_string()),
+            Some(visibility.to_string()),
+        );
+    add_audit_event(&mut tx, &event).await?;
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+    info!(
+        "Set the visibility of project {} to {}. Request made by {}.",
+        project.name,
+        visibility.as_str(),
+        requester.name
+    );
+    project.visibility = visibility;
+    Ok(project)
+}
+
+/// The errors that can occur while trying to link or unlink an LDAP group.
+#[derive(Debug)]
+pub(super) enum LdapGroupError {
+    /// Name of the Project the requester wanted to change
+    RequesterHasNoPermission(String),
+    ProjectDoesNotExist,
+    /// Name of the Project, which is archived and cannot be changed anymore
+    ArchivedProject(String),
+    EmptyGroupDn,
+    DB(DBError),
+}
+impl core::fmt::Display for LdapGroupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RequesterHasNoPermission(x) => {
+                write!(
+                    f,
+                    "The requester does not have the necessary permissions in group {x}."
+                )
+            }
+            Self::ProjectDoesNotExist => {
+                write!(f, "The project does not exist.")
+            }
+            Self::ArchivedProject(x) => {
+                write!(f, "The project {x} is archived.")
+            }
+            Self::EmptyGroupDn => {
+                write!(f, "The DN of the LDAP group is empty.")
+            }
+            Self::DB(e) => {
+                write!(f, "The DB returned this error: {e}.")
+            }
+        }
+    }
+}
+impl std::error::Error for LdapGroupError {}
+impl From<DBError> for LdapGroupError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+
+/// Link or unlink an LDAP group.
+///
+/// The members themselves are only changed by the next LDAP sync.
+/// Returns the project with the new list of groups.
+async fn change_ldap_group(
+    config: Arc<Config>,
+    requester: &Person<DbNoMatrix>,
+    project_id: i32,
+    group_dn: &str,
+    link: bool,
+) -> Result<Project<FullId>, LdapGroupError> {
+    let group_dn = group_dn.trim();
+    if group_dn.is_empty() {
+        return Err(LdapGroupError::EmptyGroupDn);
+    };
+    let mut tx = config
+        .pg_pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let mut project = get_project(&mut tx, project_id)
+        .await?
+        .ok_or(LdapGroupError::ProjectDoesNotExist)?;
+    match require_access(requester, &project, ProjectAccess::Maintainer) {
+        Ok(_) => (),
+        Err(AccessDenied::Hidden) => return Err(LdapGroupError::ProjectDoesNotExist),
+        Err(AccessDenied::Insufficient) => {
+            return Err(LdapGroupError::RequesterHasNoPermission(project.name));
+        }
+    };
+    if project.is_archived {
+        return Err(LdapGroupError::ArchivedProject(project.name));
+    };
+
+    let changed = if link {
+        add_project_ldap_group(&mut tx, project_id, group_dn).await?
+    } else {
+        remove_project_ldap_group(&mut tx, project_id, group_dn).await?
+    };
+    if !changed {
+        // nothing to do; no need to record anything
+        return Ok(project);
+    };
+    let event = if link {
+        AuditEvent::new(requester, AuditAction::LinkLdapGroup)
+            .with_project(&project)
+            .with_values(None, Some(group_dn.to_owned()))
+    } else {
+        AuditEvent::new(requester, AuditAction::UnlinkLdapGroup)
+            .with_project(&project)
+            .with_values(Some(group_dn.to_owned()), None)
+    };
+    add_audit_event(&mut tx, &event).await?;
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+    info!(
+        "{} LDAP group {group_dn} for project {}. Request made by {}.",
+        if link { "Linked" } else { "Unlinked" },
+        project.name,
+        requester.name
+    );
+    if link {
+        project.ldap_groups.push(group_dn.to_owned());
+        project.ldap_groups.sort();
+    } else {
+        project.ldap_groups.retain(|g| g != group_dn);
+    };
+    Ok(project)
+}
+
+/// Sync the members of an LDAP group into a project from now on.
+pub async fn link_ldap_group(
+    config: Arc<Config>,
+    requester: &Person<DbNoMatrix>,
+    project_id: i32,
+    group_dn: &str,
+) -> Result<Project<FullId>, LdapGroupError> {
+    change_ldap_group(config, requester, project_id, group_dn, true).await
+}
+
+/// Stop syncing the members of an LDAP group into a project.
+///
+/// The next LDAP sync removes the members that were only in this group.
+pub async fn unlink_ldap_group(
+    config: Arc<Config>,
+    requester: &Person<DbNoMatrix>,
+    project_id: i32,
+    group_dn: &str,
+) -> Result<Project<FullId>, LdapGroupError> {
+    change_ldap_group(config, requester, project_id, group_dn, false).await
+}
+
+/// The errors that can occur while trying to archive a project.
+#[derive(Debug)]
+pub(super) enum ArchiveProjectError {
+    /// Name of the Project the requester wanted to archive
+    RequesterHasNoPermission(String),
+    ProjectDoesNotExist,
+    /// Name of the Project, which is archived and cannot be changed anymore
+    ArchivedProject(String),
+    DB(DBError),
+    Matrix(MatrixClientError),
+}
+impl core::fmt::Display for ArchiveProjectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RequesterHasNoPermission(x) => {
+                write!(
+                    f,
+                    "The requester does not have the necessary permissions in group {x}."
+                )
+            }
+            Self::ProjectDoesNotExist => {
+                write!(f, "The project does not exist.")
+            }
+            Self::ArchivedProject(x) => {
+                write!(f, "The project {x} is archived.")
+            }
+            Self::DB(e) => {
+                write!(f, "The DB returned this error: {e}.")
+            }
+            Self::Matrix(e) => {
+                write!(f, "Error communicating with matrix server: {e}")
+            }
+        }
+    }
+}
+impl std::error::Error for ArchiveProjectError {}
+impl From<DBError> for ArchiveProjectError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+impl From<MatrixClientError> for ArchiveProjectError {
+    fn from(value: MatrixClientError) -> Self {
+        Self::Matrix(value)
+    }
+}
+
+/// Archive a project: hide it in the DB and make its room read-only in matrix.
+///
+/// Afterwards, the actions that change projects or their diary refuse to change it.
+///
+/// This function also checks permission of the requester.
+pub(super) async fn archive_project(
+    config: Arc<Config>,
+    requester: &Person<DbNoMatrix>,
+    project_id: i32,
+) -> Result<Project<FullId>, ArchiveProjectError> {
+    let mut con = config
+        .pg_pool
+        .clone()
+        .acquire()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let mut project = get_project(&mut con, project_id)
+        .await?
+        .ok_or(ArchiveProjectError::ProjectDoesNotExist)?;
+
+    match require_access(requester, &project, ProjectAccess::Maintainer) {
+        Ok(_) => (),
+        Err(AccessDenied::Hidden) => return Err(ArchiveProjectError::ProjectDoesNotExist),
+        Err(AccessDenied::Insufficient) => {
+            return Err(ArchiveProjectError::RequesterHasNoPermission(project.name));
+        }
+    };
+    if project.is_archived {
+        return Err(ArchiveProjectError::ArchivedProject(project.name));
+    };
+
+    let mut tx = archive_project_prepare(config.pg_pool.clone(), project_id).await?;
+    let event = AuditEvent::new(requester, AuditAction::ArchiveProject).with_project(&project);
+    add_audit_event(&mut tx, &event).await?;
+    debug!(
+        "Prepared a transaction to archive {}. Now trying to make the room read-only in Matrix...",
+        project.name
+    );
+    let mut our_client = config.matrix_client.clone();
+    our_client.set_room_read_only(&project).await?;
+    debug!(
+        "Made the room for {} read-only in Matrix. Now trying to commit the held DB transaction...",
+        project.name
+    );
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+    project.is_archived = true;
+
+    info!(
+        "Archived project {}; request made by {}.",
+        project.name, requester.name
+    );
+    Ok(project)
+}
+
+/// The errors that can occur while trying to delete a project.
+#[derive(Debug)]
+pub(super) enum DeleteProjectError {
+    /// Name of the Project the requester wanted to delete
+    RequesterHasNoPermission(String),
+    ProjectDoesNotExist,
+    DB(DBError),
+    Matrix(MatrixClientError),
+}
+impl core::fmt::Display for DeleteProjectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RequesterHasNoPermission(x) => {
+                write!(
+                    f,
+                    "The requester does not have the necessary permissions to delete {x}."
+                )
+            }
+            Self::ProjectDoesNotExist => {
+                write!(f, "The project does not exist.")
+            }
+            Self::DB(e) => {
+                write!(f, "The DB returned this error: {e}.")
+            }
+            Self::Matrix(e) => {
+                write!(f, "Error communicating with matrix server: {e}")
+            }
+        }
+    }
+}
+impl std::error::Error for DeleteProjectError {}
+impl From<DBError> for DeleteProjectError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+impl From<MatrixClientError> for DeleteProjectError {
+    fn from(value: MatrixClientError) -> Self {
+        Self::Matrix(value)
+    }
+}
+
+/// Delete a project for good: remove it from the DB and close its room in matrix.
+///
+/// This requires owning the project or global admin permissions.
+///
+/// Return the deleted project, or the appropriate error
+pub(super) async fn delete_project(
+    config: Arc<Config>,
+    requester: &Person<DbNoMatrix>,
+    project_id: i32,
+) -> Result<Project<FullId>, DeleteProjectError> {
+    let mut con = config
+        .pg_pool
+        .clone()
+        .acquire()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let project = get_project(&mut con, project_id)
+        .await?
+        .ok_or(DeleteProjectError::ProjectDoesNotExist)?;
+
+    match require_access(requester, &project, ProjectAccess::Owner) {
+        Ok(_) => (),
+        Err(AccessDenied::Hidden) => return Err(DeleteProjectError::ProjectDoesNotExist),
+        Err(AccessDenied::Insufficient) => {
+            return Err(DeleteProjectError::RequesterHasNoPermission(project.name));
+        }
+    };
+
+    let mut tx = delete_project_prepare(config.pg_pool.clone(), project_id).await?;
+    let event = AuditEvent::new(requester, AuditAction::DeleteProject)
+        .with_project(&project)
+        .with_values(Some(project.name.clone()), None);
+    add_audit_event(&mut tx, &event).await?;
+    debug!(
+        "Prepared a transaction to delete {}. Now trying to close the room in Matrix...",
+        project.name
+    );
+    let mut our_client = config.matrix_client.clone();
+    our_client.tombstone_room(&project).await?;
+    debug!(
+        "Closed the room for {} in Matrix. Now trying to commit the held DB transaction...",
+        project.name
+    );
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+
+    info!(
+        "Deleted project {} with {} members; request made by {}.",
+        project.name,
+        project.members.len(),
+        requester.name
+    );
+    Ok(project)
+}
+
+/// The errors that can occur while trying to add a diary entry.
+#[derive(Debug)]
+pub(super) enum AddEntryError {
+    ProjectDoesNotExist,
+    /// Name of the Project, which is archived and cannot be changed anymore
+    ArchivedProject(String),
+    /// Name of the Project the requester wanted to add to
+    /// (the caller does not know how that project is called yet)
+    RequesterHasNoPermission(String),
+    DB(DBError),
+}
+impl core::fmt::Display for AddEntryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ProjectDoesNotExist => {
+                write!(f, "The project does not exist.")
+            }
+            Self::ArchivedProject(x) => {
+                write!(f, "The project {x} is archived.")
+            }
+            Self::RequesterHasNoPermission(_) => {
+                write!(f, "The requester does not have the necessary permissions.")
+            }
+            Self::DB(e) => {
+                write!(f, "The DB returned this error: {e}.")
+            }
+        }
+    }
+}
+impl std::error::Error for AddEntryError {}
+impl From<DBError> for AddEntryError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+
+/// Add a new entry to the diary of a project.
+///
+/// This function also checks permission of the requester.
+///
+/// Return:
+/// - the entry that was added
+/// - the project the entry was added to
+///
+/// Or the appropriate error
+pub(super) async fn add_entry(
+    config: Arc<Config>,
+    requester: &Person<DbNoMatrix>,
+    project_id: i32,
+    title: String,
+    body: String,
+) -> Result<(Entry<DbNoMatrix>, Project<FullId>), AddEntryError> {
+    let mut con = config
+        .pg_pool
+        .clone()
+        .acquire()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let project = get_project(&mut con, project_id)
+        .await?
+        .ok_or(AddEntryError::ProjectDoesNotExist)?;
+
+    match require_access(requester, &project, ProjectAccess::Member) {
+        Ok(_) => (),
+        Err(AccessDenied::Hidden) => return Err(AddEntryError::ProjectDoesNotExist),
+        Err(AccessDenied::Insufficient) => {
+            return Err(AddEntryError::RequesterHasNoPermission(project.name));
+        }
+    };
+    if project.is_archived {
+        return Err(AddEntryError::ArchivedProject(project.name));
+    };
+
+    let entry = Entry::<NoId>::new(&project, requester.clone(), title, body);
+    let mut tx = config
+        .pg_pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let idd_entry = crate::db::add_entry(&mut tx, entry).await?;
+    let event = AuditEvent::new(requester, AuditAction::AddEntry)
+        .with_project(&project)
+        .with_values(None, Some(idd_entry.title.clone()));
+    add_audit_event(&mut tx, &event).await?;
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+    info!(
+        "Added entry {} to {}; request made by {}.",
+        idd_entry.title, project.name, requester.name
+    );
+    Ok((idd_entry, project))
+}
+
+/// The errors that can occur while trying to edit a diary entry.
+#[derive(Debug)]
+pub(super) enum EditEntryError {
+    ProjectDoesNotExist,
+    /// Name of the Project, which is archived and cannot be changed anymore
+    ArchivedProject(String),
+    /// The entry does not exist or does not belong to the project
+    EntryDoesNotExist,
+    /// Name of the Project the requester wanted to edit in
+    /// (the caller does not know how that project is called yet)
+    RequesterHasNoPermission(String),
+    DB(DBError),
+}
+impl core::fmt::Display for EditEntryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ProjectDoesNotExist => {
+                write!(f, "The project does not exist.")
+            }
+            Self::ArchivedProject(x) => {
+                write!(f, "The project {x} is archived.")
+            }
+            Self::EntryDoesNotExist => {
+                write!(f, "The entry does not exist in this project.")
+            }
+            Self::RequesterHasNoPermission(_) => {
+                write!(f, "The requester does not have the necessary permissions.")
+            }
+            Self::DB(e) => {
+                write!(f, "The DB returned this error: {e}.")
+            }
+        }
+    }
+}
+impl std::error::Error for EditEntryError {}
+impl From<DBError> for EditEntryError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+
+/// Change title and body of a diary entry.
+///
+/// This function also checks permission of the requester. Members may only edit their own entries.
+///
+/// Return:
+/// - the entry as it is now
+///
+/// Or the appropriate error
+pub(super) async fn edit_entry(
+    config: Arc<Config>,
+    requester: &Person<DbNoMatrix>,
+    project_id: i32,
+    entry_id: i32,
+    new_title: String,
+    new_body: String,
+) -> Result<Entry<DbNoMatrix>, EditEntryError> {
+    let mut con = config
+        .pg_pool
+        .clone()
+        .acquire()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let project = get_project(&mut con, project_id)
+        .await?
+        .ok_or(EditEntryError::ProjectDoesNotExist)?;
+
+    let access = match require_access(requester, &project, ProjectAccess::Member) {
+        Ok(x) => x,
+        Err(AccessDenied::Hidden) => return Err(EditEntryError::ProjectDoesNotExist),
+        Err(AccessDenied::Insufficient) => {
+            return Err(EditEntryError::RequesterHasNoPermission(project.name));
+        }
+    };
+    if project.is_archived {
+        return Err(EditEntryError::ArchivedProject(project.name));
+    };
+
+    let mut entry = match get_entry(&mut con, entry_id).await? {
+        Some(x) if x.project_id() == project_id => x,
+        _ => {
+            return Err(EditEntryError::EntryDoesNotExist);
+        }
+    };
+    if !may_change_entry(requester, access, &entry) {
+        return Err(EditEntryError::RequesterHasNoPermission(project.name));
+    };
+
+    let mut tx = config
+        .pg_pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    crate::db::update_entry(&mut tx, entry_id, &new_title, &new_body).await?;
+    let event = AuditEvent::new(requester, AuditAction::EditEntry)
+        .with_project(&project)
+        .with_values(Some(entry.title.clone()), Some(new_title.clone()));
+    add_audit_event(&mut tx, &event).await?;
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+    info!(
+        "Edited entry {} (now {}) in {}; request made by {}.",
+        entry.title, new_title, project.name, requester.name
+    );
+    entry.title = new_title;
+    entry.body = new_body;
+    Ok(entry)
+}
+
+/// The errors that can occur while trying to delete a diary entry.
+#[derive(Debug)]
+pub(super) enum DeleteEntryError {
+    ProjectDoesNotExist,
+    /// Name of the Project, which is archived and cannot be changed anymore
+    ArchivedProject(String),
+    /// The entry does not exist or does not belong to the project
+    EntryDoesNotExist,
+    /// Name of the Project the requester wanted to delete from
+    /// (the caller does not know how that project is called yet)
+    RequesterHasNoPermission(String),
+    DB(DBError),
+}
+impl core::fmt::Display for DeleteEntryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ProjectDoesNotExist => {
+                write!(f, "The project does not exist.")
+            }
+            Self::ArchivedProject(x) => {
+                write!(f, "The project {x} is archived.")
+            }
+            Self::EntryDoesNotExist => {
+                write!(f, "The entry does not exist in this project.")
+            }
+            Self::RequesterHasNoPermission(_) => {
+                write!(f, "The requester does not have the necessary permissions.")
+            }
+            Self::DB(e) => {
+                write!(f, "The DB returned this error: {e}.")
+            }
+        }
+    }
+}
+impl std::error::Error for DeleteEntryError {}
+impl From<DBError> for DeleteEntryError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+
+/// Remove an entry from the diary of a project.
+///
+/// This function also checks permission of the requester. Members may only delete their own
+/// entries.
+pub(super) async fn delete_entry(
+    config: Arc<Config>,
+    requester: &Person<DbNoMatrix>,
+    project_id: i32,
+    entry_id: i32,
+) -> Result<(), DeleteEntryError> {
+    let mut con = config
+        .pg_pool
+        .clone()
+        .acquire()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let project = get_project(&mut con, project_id)
+        .await?
+        .ok_or(DeleteEntryError::ProjectDoesNotExist)?;
+
+    let access = match require_access(requester, &project, ProjectAccess::Member) {
+        Ok(x) => x,
+        Err(AccessDenied::Hidden) => return Err(DeleteEntryError::ProjectDoesNotExist),
+        Err(AccessDenied::Insufficient) => {
+            return Err(DeleteEntryError::RequesterHasNoPermission(project.name));
+        }
+    };
+    if project.is_archived {
+        return Err(DeleteEntryError::ArchivedProject(project.name));
+    };
+
+    let entry = match get_entry(&mut con, entry_id).await? {
+        Some(x) if x.project_id() == project_id => x,
+        _ => {
+            return Err(DeleteEntryError::EntryDoesNotExist);
+        }
+    };
+    if !may_change_entry(requester, access, &entry) {
+        return Err(DeleteEntryError::RequesterHasNoPermission(project.name));
+    };
+
+    let mut tx = config
+        .pg_pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    crate::db::delete_entry(&mut tx, entry_id).await?;
+    let event = AuditEvent::new(requester, AuditAction::DeleteEntry)
+        .with_project(&project)
+        .with_values(Some(entry.title.clone()), None);
+    add_audit_event(&mut tx, &event).await?;
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+    info!(
+        "Deleted entry {} from {}; request made by {}.",
+        entry.title, project.name, requester.name
+    );
+    Ok(())
+}
+
+/// The errors that can occur while trying to create an API token.
+#[derive(Debug)]
+pub(super) enum CreateApiTokenError {
+    /// The expiry date is already in the past
+    AlreadyExpired,
+    DB(DBError),
+}
+impl core::fmt::Display for CreateApiTokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AlreadyExpired => {
+                write!(f, "The token would already be expired.")
+            }
+            Self::DB(e) => {
+                write!(f, "The DB returned this error: {e}.")
+            }
+        }
+    }
+}
+impl std::error::Error for CreateApiTokenError {}
+impl From<DBError> for CreateApiTokenError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+
+/// Create a new API token acting as the requester.
+///
+/// Return:
+/// - the token that was created
+/// - its secret. This is the only time the secret is available.
+///
+/// Or the appropriate error
+pub(super) async fn create_api_token(
+    config: Arc<Config>,
+    requester: &Person<DbNoMatrix>,
+    label: String,
+    expires_at: Option<OffsetDateTime>,
+) -> Result<(ApiToken<DbNoMatrix>, String), CreateApiTokenError> {
+    if expires_at.is_some_and(|x| x <= OffsetDateTime::now_utc()) {
+        return Err(CreateApiTokenError::AlreadyExpired);
+    };
+    let (token, secret) = ApiToken::generate(requester, label, expires_at);
+    let mut tx = config
+        .pg_pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let idd_token = add_api_token(&mut tx, token, &hash_api_token_secret(&secret)).await?;
+    let event = AuditEvent::new(requester, AuditAction::CreateApiToken)
+        .with_values(None, Some(idd_token.label.clone()));
+    add_audit_event(&mut tx, &event).await?;
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+    info!(
+        "Created API token {} for {}.",
+        idd_token.label, requester.name
+    );
+    Ok((idd_token, secret))
+}
+
+/// The errors that can occur while trying to revoke an API token.
+#[derive(Debug)]
+pub(super) enum RevokeApiTokenError {
+    /// The token does not exist or does not belong to the requester
+    TokenDoesNotExist,
+    DB(DBError),
+}
+impl core::fmt::Display for RevokeApiTokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TokenDoesNotExist => {
+                write!(f, "The API token does not exist.")
+            }
+            Self::DB(e) => {
+                write!(f, "The DB returned this error: {e}.")
+            }
+        }
+    }
+}
+impl std::error::Error for RevokeApiTokenError {}
+impl From<DBError> for RevokeApiTokenError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+
+/// Revoke one of the requesters own API tokens.
+pub(super) async fn revoke_api_token(
+    config: Arc<Config>,
+    requester: &Person<DbNoMatrix>,
+    api_token_id: i32,
+) -> Result<(), RevokeApiTokenError> {
+    let mut tx = config
+        .pg_pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let label = crate::db::revoke_api_token(&mut tx, requester.db_id(), api_token_id)
+        .await?
+        .ok_or(RevokeApiTokenError::TokenDoesNotExist)?;
+    let event =
+        AuditEvent::new(requester, AuditAction::RevokeApiToken).with_values(Some(label), None);
+    add_audit_event(&mut tx, &event).await?;
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+    info!(
+        "Revoked API token {api_token_id}; request made by {}.",
+        requester.name
+    );
+    Ok(())
+}
+
+#[derive(Debug)]
+pub(super) enum ProvisionUserError {
+    Auth(AuthError),
+    DB(DBError),
+}
+impl core::fmt::Display for ProvisionUserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auth(e) => {
+                write!(f, "The auth backend returned this error: {e}.")
+            }
+            Self::DB(e) => {
+                write!(f, "The DB returned this error: {e}.")
+            }
+        }
+    }
+}
+impl std::error::Error for ProvisionUserError {}
+impl From<AuthError> for ProvisionUserError {
+    fn from(value: AuthError) -> Self {
+        Self::Auth(value)
+    }
+}
+impl From<DBError> for ProvisionUserError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+
+/// Put a user that just logged in into the DB, with what the auth backend knows about them.
+///
+/// This way, users do not have to wait for the next sync. None if the backend does not know the
+/// user (anymore) or cannot be asked about single users.
+pub(super) async fn provision_user(
+    config: Arc<Config>,
+    username: &str,
+) -> Result<Option<Person<DbNoMatrix>>, ProvisionUserError> {
+    let Some(person) = config.auth_backend.get_person(username).await? else {
+        debug!("Cannot provision {username}, because the auth backend does not know them.");
+        return Ok(None);
+    };
+    Ok(Some(
+        provision_person(config.pg_pool.clone(), person).await?,
+    ))
+}
+
+/// Record a failed login in the history, so that admins can see passwords being guessed
+pub(super) async fn record_failed_login(
+    config: Arc<Config>,
+    username: &str,
+    address: IpAddr,
+) -> Result<(), DBError> {
+    let mut tx = config
+        .pg_pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    add_audit_event(&mut tx, &AuditEvent::failed_login(username, address)).await?;
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::{get_audit_events, get_entries, AuditEventFilter};
+    use sqlx::PgPool;
+
+    #[sqlx::test(fixtures("two_projects", "entries", "archived_project"))]
+    async fn test_archived_project_is_read_only(
+        pool: PgPool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let config = Arc::new(Config::for_test(pool.clone()).await);
+        // Adam maintains project 1
+        let adam = get_person(pool.clone(), "Adam").await?.unwrap();
+
+        assert!(matches!(
+            add_entry(
+                config.clone(),
+                &adam,
+                1,
+                "Titel".to_owned(),
+                "Inhalt".to_owned()
+            )
+            .await,
+            Err(AddEntryError::ArchivedProject(_))
+        ));
+        assert!(matches!(
+            edit_entry(
+                config.clone(),
+                &adam,
+                1,
+                1,
+                "Titel".to_owned(),
+                "Inhalt".to_owned()
+            )
+            .await,
+            Err(EditEntryError::ArchivedProject(_))
+        ));
+        assert!(matches!(
+            delete_entry(config.clone(), &adam, 1, 1).await,
+            Err(DeleteEntryError::ArchivedProject(_))
+        ));
+        assert!(matches!(
+            rename_project(config.clone(), &adam, 1, "3Basil".to_owned()).await,
+            Err(RenameProjectError::ArchivedProject(_))
+        ));
+        assert!(matches!(
+            add_member_to_project(config.clone(), &adam, "Gamaliel", 1).await,
+            Err(AddMemberError::ArchivedProject(_))
+        ));
+        assert!(matches!(
+            remove_member_from_project(config.clone(), &adam, "Beth", 1).await,
+            Err(RemoveMemberError::ArchivedProject(_))
+        ));
+        assert!(matches!(
+            archive_project(config.clone(), &adam, 1).await,
+            Err(ArchiveProjectError::ArchivedProject(_))
+        ));
+
+        // nothing was changed or recorded
+        let project = get_project(&mut pool.clone().acquire().await.unwrap(), 1)
+            .await?
+            .unwrap();
+        assert_eq!(project.name, "1Basil");
+        assert_eq!(project.members.len(), 2);
+        let entries = get_entries(&mut pool.clone().acquire().await.unwrap(), 1).await?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].title, "Kickoff");
+        let events = get_audit_events(pool.clone(), &AuditEventFilter::default()).await?;
+        assert!(events.is_empty());
+
+        // other projects can still be changed
+        assert!(add_entry(
+            config.clone(),
+            &adam,
+            2,
+            "Titel".to_owned(),
+            "Inhalt".to_owned()
+        )
+        .await
+        .is_ok());
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects", "entries"))]
+    async fn test_members_only_change_their_own_entries(
+        pool: PgPool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let config = Arc::new(Config::for_test(pool.clone()).await);
+        // Adam maintains project 1 and wrote entry 1, Beth is a member and wrote entry 2
+        let adam = get_person(pool.clone(), "Adam").await?.unwrap();
+        let beth = get_person(pool.clone(), "Beth").await?.unwrap();
+
+        assert!(matches!(
+            edit_entry(
+                config.clone(),
+                &beth,
+                1,
+                1,
+                "Titel".to_owned(),
+                "Inhalt".to_owned()
+            )
+            .await,
+            Err(EditEntryError::RequesterHasNoPermission(_))
+        ));
+        assert!(matches!(
+            delete_entry(config.clone(), &beth, 1, 1).await,
+            Err(DeleteEntryError::RequesterHasNoPermission(_))
+        ));
+        let entry = edit_entry(
+            config.clone(),
+            &beth,
+            1,
+            2,
+            "Drittes Treffen".to_owned(),
+            "Inhalt".to_owned(),
+        )
+        .await?;
+        assert_eq!(entry.title, "Drittes Treffen");
+        // maintainers may change every entry
+        delete_entry(config.clone(), &adam, 1, 2).await?;
+
+        let entries = get_entries(&mut pool.clone().acquire().await.unwrap(), 1).await?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].title, "Kickoff");
+        Ok(())
+    }
+}
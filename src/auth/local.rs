@@ -0,0 +1,245 @@
+//! Users in a table of the DB, for small installs and for testing
+//!
+//! Admins manage these users with the `user` subcommands. Passwords are stored as argon2id hashes
+//! in the PHC string format.
+
+use argon2::{
+    password_hash::{PasswordHasher, PasswordVerifier, SaltString},
+    Argon2, PasswordHash,
+};
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    db::{get_local_user, get_local_users, DBError},
+    types::{NoId, Person, UserPermission},
+};
+
+use super::{User, UserCredentials};
+
+/// Hash `password` with a new random salt, in the format stored in the DB
+pub(crate) fn hash_password(password: &str) -> Result<String, LocalAuthError> {
+    // a v4 UUID gives us 122 random bits
+    let salt = SaltString::encode_b64(Uuid::new_v4().as_bytes())
+        .map_err(LocalAuthError::CannotHashPassword)?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|x| x.to_string())
+        .map_err(LocalAuthError::CannotHashPassword)
+}
+
+/// Whether `password` hashes to `hash`. False if `hash` cannot be read.
+fn verify_password(hash: &str, password: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(x) => Argon2::default()
+            .verify_password(password.as_bytes(), &x)
+            .is_ok(),
+        Err(e) => {
+            warn!("Rejecting a password, because the stored hash cannot be read: {e}");
+            false
+        }
+    }
+}
+
+/// A user as stored in the DB, without their password
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LocalUser {
+    pub(crate) username: String,
+    pub(crate) firstname: Option<String>,
+    pub(crate) surname: Option<String>,
+    pub(crate) email: Option<String>,
+    pub(crate) display_name: Option<String>,
+    /// Whether the user has write access
+    pub(crate) admin: bool,
+}
+impl LocalUser {
+    pub(crate) fn new(username: String, admin: bool) -> Self {
+        Self {
+            username,
+            firstname: None,
+            surname: None,
+            email: None,
+            display_name: None,
+            admin,
+        }
+    }
+
+    fn person(&self) -> Person<NoId> {
+        let permission = if self.admin {
+            UserPermission::Admin
+        } else {
+            UserPermission::User
+        };
+        Person::<NoId>::new(
+            (),
+            self.username.clone(),
+            permission,
+            self.surname.clone(),
+            self.firstname.clone(),
+        )
+        .with_contact(self.email.clone(), self.display_name.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct LocalBackend {
+    pool: PgPool,
+    /// Checked for unknown users, so that the time a login takes does not tell whether a user
+    /// exists
+    dummy_hash: String,
+}
+impl LocalBackend {
+    pub(crate) fn new(pool: PgPool) -> Result<Self, LocalAuthError> {
+        Ok(Self {
+            pool,
+            dummy_hash: hash_password(&Uuid::new_v4().to_string())?,
+        })
+    }
+
+    pub(crate) async fn authenticate(
+        &self,
+        creds: UserCredentials,
+    ) -> Result<Option<User>, LocalAuthError> {
+        let (hash, known) = match get_local_user(self.pool.clone(), &creds.username).await? {
+            Some((_user, hash)) => (hash, true),
+            None => (self.dummy_hash.clone(), false),
+        };
+        // hashing takes a while on purpose, so it must not block the runtime
+        let password = creds.password;
+        let matches = tokio::task::spawn_blocking(move || verify_password(&hash, &password))
+            .await
+            .map_err(LocalAuthError::CannotVerifyPassword)?;
+        if matches && known {
+            Ok(Some(User {
+                username: creds.username,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub(crate) async fn get_user(&self, id: &str) -> Result<Option<User>, LocalAuthError> {
+        Ok(get_local_user(self.pool.clone(), id)
+            .await?
+            .map(|(user, _hash)| User {
+                username: user.username,
+            }))
+    }
+
+    pub(crate) async fn get_person(
+        &self,
+        id: &str,
+    ) -> Result<Option<Person<NoId>>, LocalAuthError> {
+        Ok(get_local_user(self.pool.clone(), id)
+            .await?
+            .map(|(user, _hash)| user.person()))
+    }
+
+    pub(crate) async fn get_all_users(&self) -> Result<Vec<Person<NoId>>, LocalAuthError> {
+        Ok(get_local_users(self.pool.clone())
+            .await?
+            .iter()
+            .map(LocalUser::person)
+            .collect())
+    }
+}
+
+#[derive(Debug)]
+pub enum LocalAuthError {
+    CannotHashPassword(argon2::password_hash::Error),
+    CannotVerifyPassword(tokio::task::JoinError),
+    DB(DBError),
+}
+impl std::fmt::Display for LocalAuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CannotHashPassword(x) => write!(f, "Cannot hash a password: {x}"),
+            Self::CannotVerifyPassword(x) => write!(f, "Cannot verify a password: {x}"),
+            Self::DB(x) => write!(f, "Cannot read the local users: {x}"),
+        }
+    }
+}
+impl std::error::Error for LocalAuthError {}
+impl From<DBError> for LocalAuthError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::add_local_user;
+
+    fn creds(username: &str, password: &str) -> UserCredentials {
+        UserCredentials {
+            username: username.to_owned(),
+            password: password.to_owned(),
+        }
+    }
+
+    #[test]
+    fn hashes_roundtrip() {
+        let hash = hash_password("hunter2").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password(&hash, "hunter2"));
+        assert!(!verify_password(&hash, "hunter3"));
+        // every hash gets its own salt
+        assert_ne!(hash, hash_password("hunter2").unwrap());
+
+        assert!(!verify_password("hunter2", "hunter2"));
+        assert!(!verify_password("", ""));
+    }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("empty")))]
+    async fn login_with_local_users(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut adam = LocalUser::new("adam".to_owned(), true);
+        adam.firstname = Some("Adam".to_owned());
+        let mut con = pool.acquire().await?;
+        assert!(add_local_user(&mut con, &adam, &hash_password("hunter2")?).await?);
+        assert!(
+            add_local_user(
+                &mut con,
+                &LocalUser::new("beth".to_owned(), false),
+                &hash_password("pw")?
+            )
+            .await?
+        );
+        // usernames are unique
+        assert!(!add_local_user(&mut con, &adam, &hash_password("other")?).await?);
+
+        let backend = LocalBackend::new(pool.clone())?;
+        assert_eq!(
+            backend
+                .authenticate(creds("adam", "hunter2"))
+                .await?
+                .unwrap()
+                .username,
+            "adam"
+        );
+        assert!(backend.authenticate(creds("adam", "pw")).await?.is_none());
+        assert!(backend
+            .authenticate(creds("adam", "other"))
+            .await?
+            .is_none());
+        assert!(backend
+            .authenticate(creds("eve", "hunter2"))
+            .await?
+            .is_none());
+        assert!(backend.get_user("beth").await?.is_some());
+        assert!(backend.get_user("eve").await?.is_none());
+        assert_eq!(
+            backend.get_person("beth").await?.unwrap().global_permission,
+            UserPermission::User
+        );
+        assert!(backend.get_person("eve").await?.is_none());
+
+        let persons = backend.get_all_users().await?;
+        assert_eq!(persons.len(), 2);
+        assert_eq!(persons[0].global_permission, UserPermission::Admin);
+        assert_eq!(persons[0].firstname.as_deref(), Some("Adam"));
+        assert_eq!(persons[1].global_permission, UserPermission::User);
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+//! Where users log in and where the user sync reads them from
+//!
+//! A deployment chooses one backend in the `[auth]` section of the config. LDAP and local users
+//! check passwords and list all of their users as [`Person`]s, which the user sync then writes
+//! into the DB. OpenID Connect users log in at their IdP instead and are written to the DB then.
+//! The web server only ever sees the [`AuthBackend`].
+
+use axum_login::{AuthUser, AuthnBackend, UserId};
+use serde::Deserialize;
+use tracing::Level;
+
+use crate::{
+    ldap::{LDAPBackend, LDAPError},
+    types::{NoId, Person},
+};
+
+pub(crate) mod local;
+pub(crate) mod oidc;
+
+use local::{LocalAuthError, LocalBackend};
+use oidc::OidcBackend;
+
+/// A user that is logged in
+#[derive(Clone)]
+pub(crate) struct User {
+    /// the unique name the user logged in with
+    pub(crate) username: String,
+}
+impl std::fmt::Debug for User {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("User")
+            .field("username", &self.username)
+            .finish()
+    }
+}
+
+impl AuthUser for User {
+    type Id = String;
+    fn id(&self) -> Self::Id {
+        self.username.clone()
+    }
+    fn session_auth_hash(&self) -> &[u8] {
+        "constant".as_bytes()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct UserCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// The backends that can be chosen in the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AuthBackendKind {
+    /// Users and their permissions come from the `[ldap]` section
+    #[default]
+    Ldap,
+    /// Users are kept in a table of the DB, with hashed passwords
+    Local,
+    /// Users log in at the IdP from the `[oidc]` section
+    Oidc,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum AuthBackend {
+    Ldap(Box<LDAPBackend>),
+    Local(LocalBackend),
+    Oidc(Box<OidcBackend>),
+}
+impl AuthBackend {
+    /// The LDAP backend, if users come from LDAP
+    pub(crate) fn ldap(&self) -> Option<&LDAPBackend> {
+        match self {
+            Self::Ldap(x) => Some(x.as_ref()),
+            Self::Local(_) | Self::Oidc(_) => None,
+        }
+    }
+
+    /// The OpenID Connect backend, if users log in at an IdP
+    pub(crate) fn oidc(&self) -> Option<&OidcBackend> {
+        match self {
+            Self::Oidc(x) => Some(x.as_ref()),
+            Self::Ldap(_) | Self::Local(_) => None,
+        }
+    }
+
+    /// Get a single user with their global permission, as they should be in the DB.
+    ///
+    /// None if the user does not exist. The IdP cannot be asked, so this is always None for OIDC.
+    pub(crate) async fn get_person(&self, id: &str) -> Result<Option<Person<NoId>>, AuthError> {
+        match self {
+            Self::Ldap(x) => Ok(x.get_person(id).await?),
+            Self::Local(x) => Ok(x.get_person(id).await?),
+            Self::Oidc(_) => Ok(None),
+        }
+    }
+
+    /// Get all users with their global permission, as they should be in the DB.
+    ///
+    /// None if the backend cannot list its users.
+    pub(crate) async fn get_all_users(&self) -> Result<Option<Vec<Person<NoId>>>, AuthError> {
+        match self {
+            Self::Ldap(x) => Ok(Some(x.get_all_users().await?)),
+            Self::Local(x) => Ok(Some(x.get_all_users().await?)),
+            Self::Oidc(_) => Ok(None),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthnBackend for AuthBackend {
+    type User = User;
+    type Credentials = UserCredentials;
+    type Error = AuthError;
+
+    #[tracing::instrument(level=Level::TRACE,skip_all,err)]
+    async fn authenticate(&self, creds: UserCredentials) -> Result<Option<User>, AuthError> {
+        match self {
+            Self::Ldap(x) => Ok(x.authenticate(&creds).await?),
+            Self::Local(x) => Ok(x.authenticate(creds).await?),
+            // there are no passwords, users log in at the IdP
+            Self::Oidc(_) => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(level=Level::TRACE,skip_all,err)]
+    async fn get_user(&self, id: &UserId<Self>) -> Result<Option<User>, AuthError> {
+        match self {
+            Self::Ldap(x) => Ok(x.get_user(id).await?),
+            Self::Local(x) => Ok(x.get_user(id).await?),
+            // the IdP cannot be asked later; the session only exists after a successful login
+            Self::Oidc(_) => Ok(Some(User {
+                username: id.to_owned(),
+            })),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    Ldap(LDAPError),
+    Local(LocalAuthError),
+}
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ldap(x) => write!(f, "LDAP error: {x}"),
+            Self::Local(x) => write!(f, "Local authentication error: {x}"),
+        }
+    }
+}
+impl std::error::Error for AuthError {}
+impl From<LDAPError> for AuthError {
+    fn from(value: LDAPError) -> Self {
+        Self::Ldap(value)
+    }
+}
+impl From<LocalAuthError> for AuthError {
+    fn from(value: LocalAuthError) -> Self {
+        Self::Local(value)
+    }
+}
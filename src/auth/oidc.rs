@@ -0,0 +1,598 @@
+//! Login with OpenID Connect, using the authorization code flow with PKCE
+//!
+//! The ID token is fetched from the token endpoint of the issuer directly, so the TLS connection
+//! already proves where it comes from. As allowed by OpenID Connect Core 3.1.3.7, its signature is
+//! therefore not checked; issuer, audience, expiry and nonce are. This only holds over TLS, so
+//! the issuer and all of its endpoints must use https.
+//!
+//! An IdP cannot list its users, so users are written to the DB when they log in.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::types::{NoId, Person, UserPermission};
+
+/// The key under which a login that was started is kept in the session
+pub(crate) const PENDING_LOGIN_KEY: &str = "oidc_pending_login";
+
+/// Which claims of the ID token hold what we know about a user
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub(crate) struct OidcClaims {
+    /// The unique name users are known by. Has to match the LDAP uid if both are used.
+    pub(crate) username: String,
+    pub(crate) firstname: String,
+    pub(crate) surname: String,
+    pub(crate) email: String,
+    pub(crate) display_name: String,
+    /// Users with `admin_value` in this claim have write access
+    pub(crate) admin: String,
+    pub(crate) admin_value: Option<String>,
+}
+impl Default for OidcClaims {
+    fn default() -> Self {
+        Self {
+            username: "preferred_username".to_owned(),
+            firstname: "given_name".to_owned(),
+            surname: "family_name".to_owned(),
+            email: "email".to_owned(),
+            display_name: "name".to_owned(),
+            admin: "groups".to_owned(),
+            admin_value: None,
+        }
+    }
+}
+
+/// The part of the discovery document we need
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// A login that was started, kept in the session until the IdP redirects back
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct PendingLogin {
+    state: String,
+    nonce: String,
+    code_verifier: String,
+}
+
+/// 256 random bits, which is also a valid PKCE code verifier
+fn random_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// Whether `url` is reached over TLS, which is what makes the ID token trustworthy.
+///
+/// Tests run their IdP on plain http on localhost.
+fn require_https(url: &str) -> Result<(), OidcError> {
+    let parsed = Url::parse(url).map_err(|_| OidcError::InvalidEndpoint(url.to_owned()))?;
+    #[cfg(test)]
+    if parsed.scheme() == "http" && parsed.host_str() == Some("127.0.0.1") {
+        return Ok(());
+    };
+    if parsed.scheme() != "https" {
+        return Err(OidcError::InsecureEndpoint(url.to_owned()));
+    };
+    Ok(())
+}
+
+#[derive(Clone)]
+pub(crate) struct OidcBackend {
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    /// where the IdP sends users back to, ending in `/login/oidc/callback`
+    redirect_url: String,
+    scopes: Vec<String>,
+    claims: OidcClaims,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    http: reqwest::Client,
+}
+impl std::fmt::Debug for OidcBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("OidcBackend")
+            .field("issuer", &self.issuer)
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"[redacted]")
+            .field("redirect_url", &self.redirect_url)
+            .field("scopes", &self.scopes)
+            .field("claims", &self.claims)
+            .finish()
+    }
+}
+impl OidcBackend {
+    /// Read the endpoints of the issuer from its discovery document
+    pub async fn discover(
+        issuer: &str,
+        client_id: &str,
+        client_secret: Option<&str>,
+        redirect_url: &str,
+    ) -> Result<Self, OidcError> {
+        let http = reqwest::Client::new();
+        let issuer = issuer.trim_end_matches('/');
+        require_https(issuer)?;
+        let metadata = http
+            .get(format!("{issuer}/.well-known/openid-configuration"))
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(OidcError::CannotDiscover)?
+            .json::<ProviderMetadata>()
+            .await
+            .map_err(OidcError::CannotDiscover)?;
+        if metadata.issuer.trim_end_matches('/') != issuer {
+            return Err(OidcError::IssuerMismatch(metadata.issuer));
+        };
+        require_https(&metadata.authorization_endpoint)?;
+        require_https(&metadata.token_endpoint)?;
+        Ok(Self {
+            issuer: metadata.issuer,
+            client_id: client_id.to_owned(),
+            client_secret: client_secret.map(str::to_owned),
+            redirect_url: redirect_url.to_owned(),
+            scopes: vec![
+                "openid".to_owned(),
+                "profile".to_owned(),
+                "email".to_owned(),
+            ],
+            claims: OidcClaims::default(),
+            authorization_endpoint: metadata.authorization_endpoint,
+            token_endpoint: metadata.token_endpoint,
+            http,
+        })
+    }
+
+    /// Request these scopes and read users from these claims
+    pub fn with_claims(mut self, scopes: Vec<String>, claims: OidcClaims) -> Self {
+        if !scopes.iter().any(|s| s == "openid") {
+            self.scopes = vec!["openid".to_owned()];
+        } else {
+            self.scopes = vec![];
+        };
+        self.scopes.extend(scopes);
+        self.claims = claims;
+        self
+    }
+
+    /// Where to send the user to log in, and what to remember until they come back
+    pub(crate) fn start_login(&self) -> Result<(Url, PendingLogin), OidcError> {
+        let pending = PendingLogin {
+            state: random_token(),
+            nonce: random_token(),
+            code_verifier: random_token(),
+        };
+        let url = Url::parse_with_params(
+            &self.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &self.client_id),
+                ("redirect_uri", &self.redirect_url),
+                ("scope", &self.scopes.join(" ")),
+                ("state", &pending.state),
+                ("nonce", &pending.nonce),
+                ("code_challenge", &code_challenge(&pending.code_verifier)),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|_| OidcError::InvalidEndpoint(self.authorization_endpoint.clone()))?;
+        Ok((url, pending))
+    }
+
+    /// Redeem the code the IdP sent the user back with and read the user from the ID token.
+    pub(crate) async fn finish_login(
+        &self,
+        pending: &PendingLogin,
+        code: &str,
+        state: &str,
+    ) -> Result<Person<NoId>, OidcError> {
+        if pending.state != state {
+            return Err(OidcError::StateMismatch);
+        };
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.redirect_url),
+            ("client_id", &self.client_id),
+            ("code_verifier", &pending.code_verifier),
+        ];
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret", secret));
+        };
+        let response = self
+            .http
+            .post(&self.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(OidcError::CannotExchangeCode)?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(OidcError::TokenEndpointError(format!("{status}: {body}")));
+        };
+        let tokens = response
+            .json::<TokenResponse>()
+            .await
+            .map_err(OidcError::CannotExchangeCode)?;
+        let claims = self.validate_id_token(
+            &tokens.id_token,
+            &pending.nonce,
+            OffsetDateTime::now_utc().unix_timestamp(),
+        )?;
+        self.person_from_claims(&claims)
+    }
+
+    /// The claims of `id_token`, if it was issued for us, for this login and is still valid
+    fn validate_id_token(
+        &self,
+        id_token: &str,
+        nonce: &str,
+        now: i64,
+    ) -> Result<Map<String, Value>, OidcError> {
+        let payload = id_token
+            .split('.')
+            .nth(1)
+            .ok_or(OidcError::MalformedIdToken)?;
+        let claims = URL_SAFE_NO_PAD
+            .decode(payload.trim_end_matches('='))
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Map<String, Value>>(&json).ok())
+            .ok_or(OidcError::MalformedIdToken)?;
+
+        if claims.get("iss").and_then(Value::as_str) != Some(self.issuer.as_str()) {
+            return Err(OidcError::InvalidClaim("iss"));
+        };
+        let audience_ok = match claims.get("aud") {
+            Some(Value::String(aud)) => aud == &self.client_id,
+            Some(Value::Array(auds)) => auds.iter().any(|a| a.as_str() == Some(&self.client_id)),
+            _ => false,
+        };
+        if !audience_ok {
+            return Err(OidcError::InvalidClaim("aud"));
+        };
+        match claims.get("exp").and_then(Value::as_i64) {
+            Some(exp) if exp > now => (),
+            _ => return Err(OidcError::InvalidClaim("exp")),
+        };
+        if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+            return Err(OidcError::InvalidClaim("nonce"));
+        };
+        Ok(claims)
+    }
+
+    fn person_from_claims(&self, claims: &Map<String, Value>) -> Result<Person<NoId>, OidcError> {
+        let string_claim = |name: &str| claims.get(name).and_then(Value::as_str).map(str::to_owned);
+        let username = string_claim(&self.claims.username)
+            .ok_or(OidcError::ClaimMissing(self.claims.username.clone()))?;
+
+        // like write_access_filter in LDAP: the claim may hold a single value or a list of them
+        let is_admin = match (&self.claims.admin_value, claims.get(&self.claims.admin)) {
+            (Some(wanted), Some(Value::String(value))) => value == wanted,
+            (Some(wanted), Some(Value::Array(values))) => {
+                values.iter().any(|v| v.as_str() == Some(wanted))
+            }
+            _ => false,
+        };
+        let permission = if is_admin {
+            UserPermission::Admin
+        } else {
+            UserPermission::User
+        };
+        Ok(Person::<NoId>::new(
+            (),
+            username,
+            permission,
+            string_claim(&self.claims.surname),
+            string_claim(&self.claims.firstname),
+        )
+        .with_contact(
+            string_claim(&self.claims.email),
+            string_claim(&self.claims.display_name),
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub enum OidcError {
+    CannotDiscover(reqwest::Error),
+    IssuerMismatch(String),
+    InvalidEndpoint(String),
+    InsecureEndpoint(String),
+    StateMismatch,
+    CannotExchangeCode(reqwest::Error),
+    TokenEndpointError(String),
+    MalformedIdToken,
+    InvalidClaim(&'static str),
+    ClaimMissing(String),
+}
+impl std::fmt::Display for OidcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CannotDiscover(x) => write!(f, "Cannot read the discovery document: {x}"),
+            Self::IssuerMismatch(x) => {
+                write!(f, "The discovery document is for another issuer: {x}")
+            }
+            Self::InvalidEndpoint(x) => write!(f, "The endpoint {x} is not a valid URL"),
+            Self::InsecureEndpoint(x) => write!(f, "The endpoint {x} does not use https"),
+            Self::StateMismatch => write!(f, "The state does not belong to this session"),
+            Self::CannotExchangeCode(x) => write!(f, "Cannot redeem the authorization code: {x}"),
+            Self::TokenEndpointError(x) => write!(f, "The token endpoint returned {x}"),
+            Self::MalformedIdToken => write!(f, "The ID token is malformed"),
+            Self::InvalidClaim(x) => write!(f, "The ID token has an invalid {x} claim"),
+            Self::ClaimMissing(x) => write!(f, "The ID token has no {x} claim"),
+        }
+    }
+}
+impl std::error::Error for OidcError {}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{
+        extract::State,
+        http::StatusCode,
+        response::IntoResponse,
+        routing::{get, post},
+        Form, Json, Router,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    /// What the mock IdP remembers from the authorization request
+    #[derive(Default)]
+    struct MockIdp {
+        issuer: String,
+        nonce: String,
+        code_challenge: String,
+        claims: Map<String, Value>,
+    }
+
+    fn id_token(claims: &Map<String, Value>) -> String {
+        // the signature is not checked, so it does not matter
+        format!(
+            "{}.{}.c2lnbmF0dXJl",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256"}"#),
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).unwrap())
+        )
+    }
+
+    async fn discovery(State(idp): State<Arc<Mutex<MockIdp>>>) -> Json<Value> {
+        let issuer = idp.lock().unwrap().issuer.clone();
+        Json(json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{issuer}/authorize"),
+            "token_endpoint": format!("{issuer}/token"),
+        }))
+    }
+
+    async fn token(
+        State(idp): State<Arc<Mutex<MockIdp>>>,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> impl IntoResponse {
+        let idp = idp.lock().unwrap();
+        if form.get("code").map(String::as_str) != Some("the-code")
+            || form.get("client_id").map(String::as_str) != Some("projekttagebuch")
+            || form.get("code_verifier").map(|v| code_challenge(v))
+                != Some(idp.code_challenge.clone())
+        {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_grant"})),
+            );
+        };
+        let mut claims = idp.claims.clone();
+        claims.insert("iss".to_owned(), json!(idp.issuer));
+        claims.insert("nonce".to_owned(), json!(idp.nonce));
+        (StatusCode::OK, Json(json!({"id_token": id_token(&claims)})))
+    }
+
+    /// Start a mock IdP on a free port
+    async fn start_idp(claims: Value) -> (String, Arc<Mutex<MockIdp>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let idp = Arc::new(Mutex::new(MockIdp {
+            issuer: issuer.clone(),
+            claims: claims.as_object().unwrap().clone(),
+            ..Default::default()
+        }));
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/token", post(token))
+            .with_state(idp.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (issuer, idp)
+    }
+
+    fn claims(issuer: &str, exp: i64) -> Map<String, Value> {
+        json!({
+            "iss": issuer,
+            "aud": ["other", "projekttagebuch"],
+            "exp": exp,
+            "nonce": "n",
+            "preferred_username": "adam",
+        })
+        .as_object()
+        .unwrap()
+        .clone()
+    }
+
+    #[tokio::test]
+    async fn login_against_mock_idp() {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let (issuer, idp) = start_idp(json!({
+            "aud": "projekttagebuch",
+            "exp": now + 300,
+            "preferred_username": "adam",
+            "given_name": "Adam",
+            "groups": ["users", "projekttagebuch_admins"],
+        }))
+        .await;
+        let backend = OidcBackend::discover(
+            &issuer,
+            "projekttagebuch",
+            None,
+            "https://projekttagebuch.example.com/login/oidc/callback",
+        )
+        .await
+        .unwrap()
+        .with_claims(
+            vec![],
+            OidcClaims {
+                admin_value: Some("projekttagebuch_admins".to_owned()),
+                ..Default::default()
+            },
+        );
+
+        let (url, pending) = backend.start_login().unwrap();
+        assert!(url.as_str().starts_with(&format!("{issuer}/authorize?")));
+        let params = url.query_pairs().into_owned().collect::<HashMap<_, _>>();
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert_eq!(params["scope"], "openid");
+        {
+            let mut idp = idp.lock().unwrap();
+            idp.nonce = params["nonce"].clone();
+            idp.code_challenge = params["code_challenge"].clone();
+        }
+
+        let person = backend
+            .finish_login(&pending, "the-code", &params["state"])
+            .await
+            .unwrap();
+        assert_eq!(person.name, "adam");
+        assert_eq!(person.firstname.as_deref(), Some("Adam"));
+        assert_eq!(person.global_permission, UserPermission::Admin);
+
+        assert!(matches!(
+            backend.finish_login(&pending, "the-code", "forged").await,
+            Err(OidcError::StateMismatch)
+        ));
+        assert!(matches!(
+            backend
+                .finish_login(&pending, "wrong-code", &params["state"])
+                .await,
+            Err(OidcError::TokenEndpointError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn id_tokens_are_validated() {
+        let (issuer, _idp) = start_idp(json!({})).await;
+        let backend = OidcBackend::discover(&issuer, "projekttagebuch", None, "https://x")
+            .await
+            .unwrap();
+        let valid = claims(&issuer, 1000);
+        assert!(backend
+            .validate_id_token(&id_token(&valid), "n", 999)
+            .is_ok());
+        assert!(matches!(
+            backend.validate_id_token(&id_token(&valid), "n", 1000),
+            Err(OidcError::InvalidClaim("exp"))
+        ));
+        assert!(matches!(
+            backend.validate_id_token(&id_token(&valid), "other", 999),
+            Err(OidcError::InvalidClaim("nonce"))
+        ));
+        let mut wrong_aud = valid.clone();
+        wrong_aud.insert("aud".to_owned(), json!("other"));
+        assert!(matches!(
+            backend.validate_id_token(&id_token(&wrong_aud), "n", 999),
+            Err(OidcError::InvalidClaim("aud"))
+        ));
+        let wrong_iss = claims("https://evil.example.com", 1000);
+        assert!(matches!(
+            backend.validate_id_token(&id_token(&wrong_iss), "n", 999),
+            Err(OidcError::InvalidClaim("iss"))
+        ));
+        assert!(matches!(
+            backend.validate_id_token("not a token", "n", 999),
+            Err(OidcError::MalformedIdToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn only_https_endpoints_are_used() {
+        assert!(require_https("https://idp.example.com/token").is_ok());
+        assert!(matches!(
+            require_https("http://idp.example.com/token"),
+            Err(OidcError::InsecureEndpoint(_))
+        ));
+        assert!(matches!(
+            require_https("not a url"),
+            Err(OidcError::InvalidEndpoint(_))
+        ));
+        // refused before the discovery document is fetched
+        assert!(matches!(
+            OidcBackend::discover(
+                "http://idp.example.com",
+                "projekttagebuch",
+                None,
+                "https://x"
+            )
+            .await,
+            Err(OidcError::InsecureEndpoint(_))
+        ));
+    }
+
+    #[test]
+    fn admin_claim_may_be_a_single_value() {
+        let backend = OidcBackend {
+            issuer: "https://idp".to_owned(),
+            client_id: "projekttagebuch".to_owned(),
+            client_secret: None,
+            redirect_url: "https://x".to_owned(),
+            scopes: vec![],
+            claims: OidcClaims {
+                admin: "role".to_owned(),
+                admin_value: Some("admin".to_owned()),
+                ..Default::default()
+            },
+            authorization_endpoint: "https://idp/authorize".to_owned(),
+            token_endpoint: "https://idp/token".to_owned(),
+            http: reqwest::Client::new(),
+        };
+        let mut claims = claims("https://idp", 1000);
+        assert_eq!(
+            backend
+                .person_from_claims(&claims)
+                .unwrap()
+                .global_permission,
+            UserPermission::User
+        );
+        claims.insert("role".to_owned(), json!("admin"));
+        assert_eq!(
+            backend
+                .person_from_claims(&claims)
+                .unwrap()
+                .global_permission,
+            UserPermission::Admin
+        );
+        claims.remove("preferred_username");
+        assert!(matches!(
+            backend.person_from_claims(&claims),
+            Err(OidcError::ClaimMissing(x)) if x == "preferred_username"
+        ));
+    }
+}
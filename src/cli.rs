@@ -0,0 +1,660 @@
+//! The command line of the `projekttagebuch` binary
+//!
+//! Without a subcommand, the binary serves the web interface like it always did. The other
+//! subcommands let admins script what they would otherwise do in the web interface or in Postgres
+//! by hand. They call the same actions as the web server, so permissions are checked and the
+//! history is written the same way. Commands that change projects or tokens act as the person
+//! given with `--as`.
+
+use std::{io::IsTerminal, path::PathBuf, sync::Arc};
+
+use time::{macros::format_description, Date};
+
+use crate::{
+    actions::{
+        add_member_to_project, archive_project, create_api_token, create_project,
+        remove_member_from_project, rename_project, revoke_api_token, set_member_permission,
+    },
+    auth::{
+        local::{hash_password, LocalUser},
+        AuthBackend,
+    },
+    config::{Config, DEFAULT_CONFIG_PATH},
+    db::{
+        add_local_user, get_local_users, get_person, get_projects, remove_local_user,
+        set_local_user_password,
+    },
+    ldap_sync::sync_once,
+    types::{DbNoMatrix, Person, ProjectRole},
+};
+
+pub(crate) const USAGE: &str = "\
+Usage: projekttagebuch [--config <path>] [--as <username>] [<command>]
+
+Commands:
+  serve                                      run the web server and the syncs (default)
+  migrate                                    bring the DB schema up to date
+  sync-users [--once]                        sync users from the auth backend into the DB
+  project list                               list all projects
+  project create <name>                      create a project
+  project rename <project-id> <name>         rename a project
+  project archive <project-id>               archive a project
+  member add <project-id> <username>         add a member to a project
+  member remove <project-id> <username>      remove a member from a project
+  member set-role <project-id> <username> <viewer|member|maintainer|owner>
+                                             change the role of a member
+  token create <label> [--expires YYYY-MM-DD]
+                                             create an API token and print its secret
+  token revoke <token-id>                    revoke an API token
+  user list                                  list the users of the local auth backend
+  user add <username> [--admin] [--firstname <name>] [--surname <name>] [--email <address>]
+           [--display-name <name>]           add a user to the local auth backend
+  user passwd <username>                     set a new password for a local user
+  user remove <username>                     remove a user from the local auth backend
+  check-config                               read the config and connect to all services
+  help                                       show this text
+
+Options:
+  --config <path>    read the config from here instead of $PROJEKTTAGEBUCH_CONFIG
+                     or /etc/projekttagebuch/config.toml
+  --as <username>    the person doing the change; needed by project, member and token
+                     commands except project list
+
+user add and user passwd ask for the password, or read it as one line from stdin if that is not
+a terminal.";
+
+/// What the binary was asked to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Command {
+    Serve,
+    Migrate,
+    SyncUsers {
+        once: bool,
+    },
+    ProjectList,
+    ProjectCreate {
+        name: String,
+    },
+    ProjectRename {
+        project_id: i32,
+        name: String,
+    },
+    ProjectArchive {
+        project_id: i32,
+    },
+    MemberAdd {
+        project_id: i32,
+        username: String,
+    },
+    MemberRemove {
+        project_id: i32,
+        username: String,
+    },
+    MemberSetRole {
+        project_id: i32,
+        username: String,
+        role: ProjectRole,
+    },
+    TokenCreate {
+        label: String,
+        /// the token expires at the end of this day (UTC)
+        expires: Option<Date>,
+    },
+    TokenRevoke {
+        token_id: i32,
+    },
+    UserList,
+    UserAdd {
+        user: LocalUser,
+    },
+    UserPasswd {
+        username: String,
+    },
+    UserRemove {
+        username: String,
+    },
+    CheckConfig,
+    Help,
+}
+impl Command {
+    /// Whether the command changes something in the name of a person
+    fn needs_requester(&self) -> bool {
+        matches!(
+            self,
+            Self::ProjectCreate { .. }
+                | Self::ProjectRename { .. }
+                | Self::ProjectArchive { .. }
+                | Self::MemberAdd { .. }
+                | Self::MemberRemove { .. }
+                | Self::MemberSetRole { .. }
+                | Self::TokenCreate { .. }
+                | Self::TokenRevoke { .. }
+        )
+    }
+}
+
+/// The parsed command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Cli {
+    pub(crate) config_path: PathBuf,
+    /// the person given with `--as`
+    pub(crate) acting_as: Option<String>,
+    pub(crate) command: Command,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum CliError {
+    /// The arguments do not form a command
+    Usage(String),
+    /// The command needs `--as`
+    RequesterMissing,
+    /// The person given with `--as` is not in the DB
+    UnknownRequester(String),
+    /// The password was empty
+    EmptyPassword,
+    /// The password and its repetition differ
+    PasswordsDiffer,
+    /// There is no local user with this name
+    UnknownLocalUser(String),
+    /// There already is a local user with this name
+    LocalUserExists(String),
+    /// The command keeps running or needs no config, so [`run`] cannot run it
+    NotRunHere,
+}
+impl core::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Usage(x) => write!(f, "{x}"),
+            Self::RequesterMissing => {
+                write!(f, "This command needs --as <username>.")
+            }
+            Self::UnknownRequester(x) => write!(f, "There is no person named {x} in the DB."),
+            Self::EmptyPassword => write!(f, "The password must not be empty."),
+            Self::PasswordsDiffer => write!(f, "The passwords do not match."),
+            Self::UnknownLocalUser(x) => write!(f, "There is no local user named {x}."),
+            Self::LocalUserExists(x) => write!(f, "There already is a local user named {x}."),
+            Self::NotRunHere => write!(
+                f,
+                "serve, migrate, sync-users without --once and help are not run from here."
+            ),
+        }
+    }
+}
+impl std::error::Error for CliError {}
+
+fn usage_error(message: impl Into<String>) -> CliError {
+    CliError::Usage(message.into())
+}
+
+fn parse_id(value: &str, what: &str) -> Result<i32, CliError> {
+    value
+        .parse()
+        .map_err(|_| usage_error(format!("{what} must be a number, not {value}.")))
+}
+
+/// Parse `args`, starting with the name of the binary. `env_config` is the value of
+/// [`crate::config::CONFIG_PATH_ENV`].
+pub(crate) fn parse(args: &[String], env_config: Option<String>) -> Result<Cli, CliError> {
+    let mut config_path = env_config.map(PathBuf::from);
+    let mut acting_as = None;
+    let mut positional = Vec::<&str>::new();
+    let mut once = false;
+    let mut expires = None;
+    let mut help = false;
+    // the details of a new local user
+    let mut new_user = LocalUser::new(String::new(), false);
+
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_owned())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = |name: &str| {
+            inline_value
+                .clone()
+                .or_else(|| args.next().cloned())
+                .ok_or_else(|| usage_error(format!("{name} needs a value.")))
+        };
+        match name {
+            "--config" => config_path = Some(PathBuf::from(value("--config")?)),
+            "--as" => acting_as = Some(value("--as")?),
+            "--expires" => {
+                let date = value("--expires")?;
+                expires = Some(
+                    Date::parse(&date, format_description!("[year]-[month]-[day]")).map_err(
+                        |_| {
+                            usage_error(format!(
+                                "--expires needs a date like 2025-12-31, not {date}."
+                            ))
+                        },
+                    )?,
+                );
+            }
+            "--once" => once = true,
+            "--admin" => new_user.admin = true,
+            "--firstname" => new_user.firstname = Some(value("--firstname")?),
+            "--surname" => new_user.surname = Some(value("--surname")?),
+            "--email" => new_user.email = Some(value("--email")?),
+            "--display-name" => new_user.display_name = Some(value("--display-name")?),
+            "-h" | "--help" => help = true,
+            x if x.starts_with("--") => return Err(usage_error(format!("Unknown option {x}."))),
+            x => positional.push(x),
+        };
+    }
+    if help {
+        positional = vec!["help"];
+    };
+
+    let command = match positional.as_slice() {
+        [] | ["serve"] => Command::Serve,
+        ["migrate"] => Command::Migrate,
+        ["sync-users"] => Command::SyncUsers { once },
+        ["project", "list"] => Command::ProjectList,
+        ["project", "create", name] => Command::ProjectCreate {
+            name: name.to_string(),
+        },
+        ["project", "rename", project_id, name] => Command::ProjectRename {
+            project_id: parse_id(project_id, "The project id")?,
+            name: name.to_string(),
+        },
+        ["project", "archive", project_id] => Command::ProjectArchive {
+            project_id: parse_id(project_id, "The project id")?,
+        },
+        ["member", "add", project_id, username] => Command::MemberAdd {
+            project_id: parse_id(project_id, "The project id")?,
+            username: username.to_string(),
+        },
+        ["member", "remove", project_id, username] => Command::MemberRemove {
+            project_id: parse_id(project_id, "The project id")?,
+            username: username.to_string(),
+        },
+        ["member", "set-role", project_id, username, role] => Command::MemberSetRole {
+            project_id: parse_id(project_id, "The project id")?,
+            username: username.to_string(),
+            role: ProjectRole::try_from(*role).map_err(|x| {
+                usage_error(format!(
+                    "{x} is not a role; use viewer, member, maintainer or owner."
+                ))
+            })?,
+        },
+        ["token", "create", label] => Command::TokenCreate {
+            label: label.to_string(),
+            expires,
+        },
+        ["token", "revoke", token_id] => Command::TokenRevoke {
+            token_id: parse_id(token_id, "The token id")?,
+        },
+        ["user", "list"] => Command::UserList,
+        ["user", "add", username] => Command::UserAdd {
+            user: LocalUser {
+                username: username.to_string(),
+                ..new_user.clone()
+            },
+        },
+        ["user", "passwd", username] => Command::UserPasswd {
+            username: username.to_string(),
+        },
+        ["user", "remove", username] => Command::UserRemove {
+            username: username.to_string(),
+        },
+        ["check-config"] => Command::CheckConfig,
+        ["help"] => Command::Help,
+        x => return Err(usage_error(format!("Unknown command: {}", x.join(" ")))),
+    };
+    if once && !matches!(command, Command::SyncUsers { .. }) {
+        return Err(usage_error("--once only applies to sync-users."));
+    };
+    if expires.is_some() && !matches!(command, Command::TokenCreate { .. }) {
+        return Err(usage_error("--expires only applies to token create."));
+    };
+    if new_user != LocalUser::new(String::new(), false)
+        && !matches!(command, Command::UserAdd { .. })
+    {
+        return Err(usage_error(
+            "--admin, --firstname, --surname, --email and --display-name only apply to user add.",
+        ));
+    };
+    if command.needs_requester() && acting_as.is_none() {
+        return Err(CliError::RequesterMissing);
+    };
+
+    Ok(Cli {
+        config_path: config_path.unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH)),
+        acting_as,
+        command,
+    })
+}
+
+/// The person given with `--as`
+async fn requester(
+    config: &Config,
+    acting_as: Option<&str>,
+) -> Result<Person<DbNoMatrix>, Box<dyn std::error::Error>> {
+    let Some(name) = acting_as else {
+        return Err(CliError::RequesterMissing.into());
+    };
+    match get_person(config.pg_pool.clone(), name).await? {
+        Some(x) => Ok(x),
+        None => Err(CliError::UnknownRequester(name.to_owned()).into()),
+    }
+}
+
+/// Ask for a new password on the terminal, or read it as one line from stdin if that is not a
+/// terminal, so that scripts can pipe it in
+fn read_new_password() -> Result<String, Box<dyn std::error::Error>> {
+    let password = if std::io::stdin().is_terminal() {
+        let password = rpassword::prompt_password("New password: ")?;
+        if rpassword::prompt_password("Repeat the password: ")? != password {
+            return Err(CliError::PasswordsDiffer.into());
+        };
+        password
+    } else {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line)?;
+        line.trim_end_matches(['\r', '\n']).to_owned()
+    };
+    if password.is_empty() {
+        return Err(CliError::EmptyPassword.into());
+    };
+    Ok(password)
+}
+
+/// Run one of the commands that do their work and exit.
+///
+/// `serve`, `migrate`, `sync-users` without `--once` and `help` are handled by `main`.
+pub(crate) async fn run(
+    config: Arc<Config>,
+    acting_as: Option<&str>,
+    command: Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::Serve | Command::Migrate | Command::Help | Command::SyncUsers { once: false } => {
+            return Err(CliError::NotRunHere.into());
+        }
+        Command::SyncUsers { once: true } => {
+            sync_once(config).await?;
+            println!("Synced users from the auth backend.");
+        }
+        Command::ProjectList => {
+            for project in get_projects(config.pg_pool.clone(), true).await? {
+                println!(
+                    "{}\t{}\t{}{}\t{} members",
+                    project.db_id(),
+                    project.name,
+                    project.metadata.status.as_str(),
+                    if project.is_archived {
+                        " (archived)"
+                    } else {
+                        ""
+                    },
+                    project.members.len()
+                );
+            }
+        }
+        Command::ProjectCreate { name } => {
+            let requester = requester(&config, acting_as).await?;
+            let project = create_project(config, &requester, name).await?;
+            println!("Created project {} ({}).", project.name, project.db_id());
+        }
+        Command::ProjectRename { project_id, name } => {
+            let requester = requester(&config, acting_as).await?;
+            let project = rename_project(config, &requester, project_id, name).await?;
+            println!("Renamed project {} to {}.", project.db_id(), project.name);
+        }
+        Command::ProjectArchive { project_id } => {
+            let requester = requester(&config, acting_as).await?;
+            let project = archive_project(config, &requester, project_id).await?;
+            println!("Archived project {} ({}).", project.name, project.db_id());
+        }
+        Command::MemberAdd {
+            project_id,
+            username,
+        } => {
+            let requester = requester(&config, acting_as).await?;
+            let (person, project) =
+                add_member_to_project(config, &requester, &username, project_id).await?;
+            println!("Added {} to {}.", person.name, project.name);
+        }
+        Command::MemberRemove {
+            project_id,
+            username,
+        } => {
+            let requester = requester(&config, acting_as).await?;
+            let (person, project) =
+                remove_member_from_project(config, &requester, &username, project_id).await?;
+            println!("Removed {} from {}.", person.name, project.name);
+        }
+        Command::MemberSetRole {
+            project_id,
+            username,
+            role,
+        } => {
+            let requester = requester(&config, acting_as).await?;
+            let (person, project) =
+                set_member_permission(config, &requester, &username, project_id, role).await?;
+            println!(
+                "{} is now {} of {}.",
+                person.name,
+                role.as_str(),
+                project.name
+            );
+        }
+        Command::TokenCreate { label, expires } => {
+            let requester = requester(&config, acting_as).await?;
+            let expires_at = expires.map(|x| x.next_day().unwrap_or(x).midnight().assume_utc());
+            let (token, secret) = create_api_token(config, &requester, label, expires_at).await?;
+            eprintln!(
+                "Created API token {} ({}) for {}. The secret is shown only once:",
+                token.label,
+                token.db_id(),
+                requester.name
+            );
+            // only the secret on stdout, so that scripts can capture it
+            println!("{secret}");
+        }
+        Command::TokenRevoke { token_id } => {
+            let requester = requester(&config, acting_as).await?;
+            revoke_api_token(config, &requester, token_id).await?;
+            println!("Revoked API token {token_id}.");
+        }
+        Command::UserList => {
+            for user in get_local_users(config.pg_pool.clone()).await? {
+                let name = [user.firstname.as_deref(), user.surname.as_deref()]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                println!(
+                    "{}\t{}\t{}",
+                    user.username,
+                    name,
+                    if user.admin { "admin" } else { "user" }
+                );
+            }
+        }
+        Command::UserAdd { user } => {
+            if !matches!(config.auth_backend, AuthBackend::Local(_)) {
+                eprintln!("Local users can only log in with backend = \"local\" in [auth].");
+            };
+            let password_hash = hash_password(&read_new_password()?)?;
+            let mut con = config.pg_pool.acquire().await?;
+            if !add_local_user(&mut con, &user, &password_hash).await? {
+                return Err(CliError::LocalUserExists(user.username).into());
+            };
+            println!("Added local user {}.", user.username);
+        }
+        Command::UserPasswd { username } => {
+            let password_hash = hash_password(&read_new_password()?)?;
+            let mut con = config.pg_pool.acquire().await?;
+            if !set_local_user_password(&mut con, &username, &password_hash).await? {
+                return Err(CliError::UnknownLocalUser(username).into());
+            };
+            println!("Set a new password for {username}.");
+        }
+        Command::UserRemove { username } => {
+            let mut con = config.pg_pool.acquire().await?;
+            if !remove_local_user(&mut con, &username).await? {
+                return Err(CliError::UnknownLocalUser(username).into());
+            };
+            println!("Removed local user {username}. The next user sync removes them from their projects.");
+        }
+        Command::CheckConfig => {
+            let backend = match config.auth_backend {
+                AuthBackend::Ldap(_) => "LDAP",
+                AuthBackend::Local(_) => "local users",
+                AuthBackend::Oidc(_) => "OpenID Connect",
+            };
+            println!(
+                "The config is fine. Connected to the DB and Matrix; users come from {backend}."
+            );
+        }
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Cli, CliError> {
+        let args = std::iter::once("projekttagebuch")
+            .chain(args.iter().copied())
+            .map(|x| x.to_owned())
+            .collect::<Vec<_>>();
+        parse(&args, None)
+    }
+
+    #[test]
+    fn serve_is_the_default() {
+        let cli = parse_args(&[]).unwrap();
+        assert_eq!(cli.command, Command::Serve);
+        assert_eq!(cli.config_path, PathBuf::from(DEFAULT_CONFIG_PATH));
+        assert_eq!(parse_args(&["serve"]).unwrap().command, Command::Serve);
+    }
+
+    #[test]
+    fn config_path_from_args_or_env() {
+        let args = vec!["projekttagebuch".to_owned()];
+        assert_eq!(
+            parse(&args, Some("/env.toml".to_owned()))
+                .unwrap()
+                .config_path,
+            PathBuf::from("/env.toml")
+        );
+        let args = ["projekttagebuch", "--config", "/arg.toml", "migrate"]
+            .map(|x| x.to_owned())
+            .to_vec();
+        let cli = parse(&args, Some("/env.toml".to_owned())).unwrap();
+        assert_eq!(cli.config_path, PathBuf::from("/arg.toml"));
+        assert_eq!(cli.command, Command::Migrate);
+        assert_eq!(
+            parse_args(&["--config=/arg.toml"]).unwrap().config_path,
+            PathBuf::from("/arg.toml")
+        );
+        assert!(matches!(parse_args(&["--config"]), Err(CliError::Usage(_))));
+    }
+
+    #[test]
+    fn subcommands_are_parsed() {
+        assert_eq!(
+            parse_args(&["sync-users", "--once"]).unwrap().command,
+            Command::SyncUsers { once: true }
+        );
+        assert_eq!(
+            parse_args(&["--as", "adam", "project", "rename", "3", "Neues Dach"])
+                .unwrap()
+                .command,
+            Command::ProjectRename {
+                project_id: 3,
+                name: "Neues Dach".to_owned()
+            }
+        );
+        let cli =
+            parse_args(&["member", "set-role", "3", "beth", "maintainer", "--as=adam"]).unwrap();
+        assert_eq!(cli.acting_as.as_deref(), Some("adam"));
+        assert_eq!(
+            cli.command,
+            Command::MemberSetRole {
+                project_id: 3,
+                username: "beth".to_owned(),
+                role: ProjectRole::Maintainer
+            }
+        );
+        assert_eq!(
+            parse_args(&[
+                "--as",
+                "adam",
+                "token",
+                "create",
+                "backup",
+                "--expires",
+                "2030-01-31"
+            ])
+            .unwrap()
+            .command,
+            Command::TokenCreate {
+                label: "backup".to_owned(),
+                expires: Some(time::macros::date!(2030 - 01 - 31))
+            }
+        );
+        assert_eq!(
+            parse_args(&["project", "list"]).unwrap().command,
+            Command::ProjectList
+        );
+        assert_eq!(
+            parse_args(&["check-config"]).unwrap().command,
+            Command::CheckConfig
+        );
+        assert_eq!(parse_args(&["--help"]).unwrap().command, Command::Help);
+        assert_eq!(
+            parse_args(&["user", "add", "adam", "--admin", "--firstname", "Adam"])
+                .unwrap()
+                .command,
+            Command::UserAdd {
+                user: LocalUser {
+                    firstname: Some("Adam".to_owned()),
+                    ..LocalUser::new("adam".to_owned(), true)
+                }
+            }
+        );
+        assert_eq!(
+            parse_args(&["user", "passwd", "adam"]).unwrap().command,
+            Command::UserPasswd {
+                username: "adam".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn broken_command_lines_are_rejected() {
+        assert_eq!(
+            parse_args(&["project", "archive", "3"]),
+            Err(CliError::RequesterMissing)
+        );
+        assert!(matches!(
+            parse_args(&["--as", "adam", "project", "archive", "three"]),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse_args(&["--as", "adam", "member", "set-role", "3", "beth", "boss"]),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse_args(&["migrate", "--once"]),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse_args(&["user", "passwd", "adam", "--admin"]),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse_args(&["frobnicate"]),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse_args(&["--verbose"]),
+            Err(CliError::Usage(_))
+        ));
+    }
+}
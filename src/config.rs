@@ -1,21 +1,37 @@
 //! Handling the Config and associated objects.
 
+use std::path::Path;
+
 use axum_server::tls_rustls::RustlsConfig;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use matrix_sdk::{Client, ClientBuildError};
 use serde::Deserialize;
 use sqlx::{Pool, Postgres};
+use tower_sessions::cookie::Key;
 use tracing::{event, Level};
 
-use crate::ldap::{LDAPBackend, LDAPError};
+use crate::auth::{
+    local::{LocalAuthError, LocalBackend},
+    oidc::{OidcBackend, OidcClaims, OidcError},
+    AuthBackend, AuthBackendKind,
+};
+use crate::ldap::{LDAPBackend, LDAPError, LdapAttributes, SearchScope, DEFAULT_PAGE_SIZE};
+use crate::ldap_pool::DEFAULT_POOL_SIZE;
 use crate::matrix::MatrixClient;
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub(crate) enum ConfigError {
     PoolCreationError(sqlx::Error),
     TlsCertKeyError(std::io::Error),
+    SessionKeyError(String),
+    SecretError(SecretError),
     ReadConfigFileError(std::io::Error),
     ParseConfigFileError(toml::de::Error),
     LdapConnectionError(LDAPError),
+    AuthSectionMissing(&'static str),
+    LocalUserError(LocalAuthError),
+    OidcDiscoveryError(OidcError),
     MatrixClientCreationError(ClientBuildError),
     MatrixLoginError(matrix_sdk::Error),
 }
@@ -28,6 +44,12 @@ impl core::fmt::Display for ConfigError {
             Self::TlsCertKeyError(x) => {
                 write!(f, "Error creating TLS config: {x}")
             }
+            Self::SessionKeyError(x) => {
+                write!(f, "Error reading the session key: {x}")
+            }
+            Self::SecretError(x) => {
+                write!(f, "Error reading a secret: {x}")
+            }
             Self::ReadConfigFileError(x) => {
                 write!(f, "Error reading Config File: {x}")
             }
@@ -37,6 +59,15 @@ impl core::fmt::Display for ConfigError {
             Self::LdapConnectionError(x) => {
                 write!(f, "Error connecting to LDAP: {x}")
             }
+            Self::AuthSectionMissing(x) => {
+                write!(f, "The {x} auth backend needs an [{x}] section")
+            }
+            Self::LocalUserError(x) => {
+                write!(f, "Error setting up the local users: {x}")
+            }
+            Self::OidcDiscoveryError(x) => {
+                write!(f, "Error discovering the OpenID Connect provider: {x}")
+            }
             Self::MatrixClientCreationError(e) => {
                 write!(f, "Error creating Matrix Client: {e}")
             }
@@ -47,27 +78,178 @@ impl core::fmt::Display for ConfigError {
     }
 }
 impl std::error::Error for ConfigError {}
+impl From<SecretError> for ConfigError {
+    fn from(value: SecretError) -> Self {
+        Self::SecretError(value)
+    }
+}
+
+/// Where the config is read from if neither `--config` nor [`CONFIG_PATH_ENV`] is given
+pub(crate) const DEFAULT_CONFIG_PATH: &str = "/etc/projekttagebuch/config.toml";
+
+/// The environment variable that may name the config file
+pub(crate) const CONFIG_PATH_ENV: &str = "PROJEKTTAGEBUCH_CONFIG";
+
+/// Secrets are read from environment variables starting with this, e.g. PROJEKTTAGEBUCH_DB_PASSWORD
+const SECRET_ENV_PREFIX: &str = "PROJEKTTAGEBUCH_";
+
+#[derive(Debug)]
+pub(crate) enum SecretError {
+    /// The secret was given nowhere
+    Missing(&'static str),
+    /// The file the secret should be in cannot be read
+    CannotReadFile(String, std::io::Error),
+}
+impl core::fmt::Display for SecretError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing(x) => write!(
+                f,
+                "The secret {x} is missing. Put it into the config, into a file named there or into the environment variable {SECRET_ENV_PREFIX}{}.",
+                x.to_uppercase()
+            ),
+            Self::CannotReadFile(path, e) => write!(f, "Cannot read {path}: {e}"),
+        }
+    }
+}
+impl std::error::Error for SecretError {}
+
+/// Read a secret such as a password.
+///
+/// The environment variable `PROJEKTTAGEBUCH_<NAME>` takes precedence, so that containers can
+/// override the config. Then comes the file named in the config, e.g. a Docker secret, and last
+/// the value in the config itself. A trailing newline in the file is not part of the secret.
+fn read_secret(
+    name: &'static str,
+    value: Option<String>,
+    file: Option<&str>,
+) -> Result<Option<String>, SecretError> {
+    read_secret_with_env(name, value, file, |x| std::env::var(x).ok())
+}
+
+fn read_secret_with_env(
+    name: &'static str,
+    value: Option<String>,
+    file: Option<&str>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Option<String>, SecretError> {
+    if let Some(x) = env(&format!("{SECRET_ENV_PREFIX}{}", name.to_uppercase())) {
+        return Ok(Some(x));
+    };
+    if let Some(path) = file {
+        return match std::fs::read_to_string(path) {
+            Ok(x) => Ok(Some(x.trim_end_matches(['\n', '\r']).to_owned())),
+            Err(e) => Err(SecretError::CannotReadFile(path.to_owned(), e)),
+        };
+    };
+    Ok(value)
+}
+
+/// Like [`read_secret`], but the secret has to be given somewhere
+fn require_secret(
+    name: &'static str,
+    value: Option<String>,
+    file: Option<&str>,
+) -> Result<String, SecretError> {
+    match read_secret(name, value, file) {
+        Ok(Some(x)) => Ok(x),
+        Ok(None) => Err(SecretError::Missing(name)),
+        Err(e) => Err(e),
+    }
+}
 
 /// Config as present in file. This object will be used to create a Config object.
 #[derive(Debug, Deserialize)]
 struct ConfigData {
     log_level: String,
     user_resync_interval: Option<u32>,
-    ldap: LdapConfigData,
+    room_reconcile_interval: Option<u32>,
+    #[serde(default)]
+    auth: AuthConfigData,
+    ldap: Option<LdapConfigData>,
+    oidc: Option<OidcConfigData>,
     db: DbConfigData,
     web: WebConfigData,
     matrix: MatrixConfigData,
 }
 
+/// The part of the config file [`DbConfig`] needs; the other sections are not read
+#[derive(Debug, Deserialize)]
+struct DbOnlyConfigData {
+    log_level: String,
+    db: DbConfigData,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AuthConfigData {
+    /// Where users log in and are synced from
+    #[serde(default)]
+    backend: AuthBackendKind,
+}
+impl AuthConfigData {
+    async fn try_into_auth_backend(
+        self,
+        ldap: Option<LdapConfigData>,
+        oidc: Option<OidcConfigData>,
+        pg_pool: Pool<Postgres>,
+    ) -> Result<AuthBackend, ConfigError> {
+        match self.backend {
+            AuthBackendKind::Ldap => {
+                let Some(ldap) = ldap else {
+                    event!(
+                        Level::ERROR,
+                        "The ldap auth backend needs an [ldap] section."
+                    );
+                    return Err(ConfigError::AuthSectionMissing("ldap"));
+                };
+                Ok(AuthBackend::Ldap(Box::new(
+                    ldap.try_into_ldap_backend().await?,
+                )))
+            }
+            AuthBackendKind::Local => match LocalBackend::new(pg_pool) {
+                Ok(x) => Ok(AuthBackend::Local(x)),
+                Err(e) => {
+                    event!(Level::ERROR, "The local auth backend cannot be set up: {e}");
+                    Err(ConfigError::LocalUserError(e))
+                }
+            },
+            AuthBackendKind::Oidc => {
+                let Some(oidc) = oidc else {
+                    event!(
+                        Level::ERROR,
+                        "The oidc auth backend needs an [oidc] section."
+                    );
+                    return Err(ConfigError::AuthSectionMissing("oidc"));
+                };
+                Ok(AuthBackend::Oidc(Box::new(
+                    oidc.try_into_oidc_backend().await?,
+                )))
+            }
+        }
+    }
+}
+
 #[derive(Deserialize)]
 struct LdapConfigData {
     server_host: String,
     server_port: u16,
     bind_dn: String,
-    bind_password: String,
+    bind_password: Option<String>,
+    /// file containing the bind password, e.g. a Docker secret
+    bind_password_file: Option<String>,
     user_base_dn: String,
     user_filter: String,
     write_access_filter: String,
+    /// Search only directly below `user_base_dn` (one) or in the whole subtree (sub)
+    #[serde(default)]
+    search_scope: SearchScope,
+    /// The attributes users are read from
+    #[serde(default)]
+    attributes: LdapAttributes,
+    /// Number of entries per page when reading all users
+    search_page_size: Option<i32>,
+    /// Number of connections kept bound as `bind_dn` for searches
+    pool_size: Option<usize>,
 }
 impl core::fmt::Debug for LdapConfigData {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -76,12 +258,127 @@ impl core::fmt::Debug for LdapConfigData {
             .field("server_port", &self.server_port)
             .field("bind_dn", &self.bind_dn)
             .field("bind_password", &"[redacted]")
+            .field("bind_password_file", &self.bind_password_file)
             .field("user_base_dn", &self.user_base_dn)
             .field("user_filter", &self.user_filter)
             .field("write_access_filter", &self.write_access_filter)
+            .field("search_scope", &self.search_scope)
+            .field("attributes", &self.attributes)
+            .field("search_page_size", &self.search_page_size)
+            .field("pool_size", &self.pool_size)
             .finish()
     }
 }
+impl LdapConfigData {
+    async fn try_into_ldap_backend(self) -> Result<LDAPBackend, ConfigError> {
+        let bind_password = match require_secret(
+            "ldap_bind_password",
+            self.bind_password,
+            self.bind_password_file.as_deref(),
+        ) {
+            Ok(x) => x,
+            Err(e) => {
+                event!(Level::ERROR, "The LDAP bind password is not available: {e}");
+                return Err(e.into());
+            }
+        };
+        match LDAPBackend::new(
+            &self.server_host,
+            self.server_port,
+            &self.bind_dn,
+            &bind_password,
+            &self.user_filter,
+            &self.write_access_filter,
+            &self.user_base_dn,
+        )
+        .await
+        {
+            Ok(x) => Ok(x
+                .with_mapping(self.attributes, self.search_scope)
+                .with_page_size(self.search_page_size.unwrap_or(DEFAULT_PAGE_SIZE))
+                .with_pool_size(self.pool_size.unwrap_or(DEFAULT_POOL_SIZE))),
+            Err(e) => {
+                event!(
+                    Level::ERROR,
+                    "LDAP connection could not be established: {e}"
+                );
+                Err(ConfigError::LdapConnectionError(e))
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OidcConfigData {
+    /// URL of the IdP, without /.well-known/openid-configuration
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    /// file containing the client secret, e.g. a Docker secret
+    client_secret_file: Option<String>,
+    /// The public URL of /login/oidc/callback
+    redirect_url: String,
+    /// Scopes requested in addition to openid
+    scopes: Option<Vec<String>>,
+    /// The claims users are read from
+    #[serde(default)]
+    claims: OidcClaims,
+}
+impl core::fmt::Debug for OidcConfigData {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("OidcConfigData")
+            .field("issuer", &self.issuer)
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"[redacted]")
+            .field("client_secret_file", &self.client_secret_file)
+            .field("redirect_url", &self.redirect_url)
+            .field("scopes", &self.scopes)
+            .field("claims", &self.claims)
+            .finish()
+    }
+}
+impl OidcConfigData {
+    async fn try_into_oidc_backend(self) -> Result<OidcBackend, ConfigError> {
+        // public clients have no secret
+        let client_secret = match read_secret(
+            "oidc_client_secret",
+            self.client_secret,
+            self.client_secret_file.as_deref(),
+        ) {
+            Ok(x) => x,
+            Err(e) => {
+                event!(Level::ERROR, "The OIDC client secret is not available: {e}");
+                return Err(e.into());
+            }
+        };
+        match OidcBackend::discover(
+            &self.issuer,
+            &self.client_id,
+            client_secret.as_deref(),
+            &self.redirect_url,
+        )
+        .await
+        {
+            Ok(x) => Ok(x.with_claims(
+                self.scopes.unwrap_or_else(|| {
+                    vec![
+                        "openid".to_owned(),
+                        "profile".to_owned(),
+                        "email".to_owned(),
+                    ]
+                }),
+                self.claims,
+            )),
+            Err(e) => {
+                event!(
+                    Level::ERROR,
+                    "The OpenID Connect provider could not be discovered: {e}"
+                );
+                Err(ConfigError::OidcDiscoveryError(e))
+            }
+        }
+    }
+}
 
 #[derive(Deserialize)]
 struct DbConfigData {
@@ -89,7 +386,9 @@ struct DbConfigData {
     port: u16,
     database: String,
     user: String,
-    password: String,
+    password: Option<String>,
+    /// file containing the password, e.g. a Docker secret
+    password_file: Option<String>,
 }
 impl core::fmt::Debug for DbConfigData {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -99,17 +398,136 @@ impl core::fmt::Debug for DbConfigData {
             .field("database", &self.database)
             .field("user", &self.user)
             .field("password", &"[redacted]")
+            .field("password_file", &self.password_file)
             .finish()
     }
 }
 
-#[derive(Debug, Deserialize)]
+/// Where sessions are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SessionStoreKind {
+    /// A SQLite file, see `session_db_path`
+    #[default]
+    Sqlite,
+    /// The Postgres DB from the `[db]` section, so that several instances can share sessions
+    Postgres,
+}
+
+/// Default location of the SQLite session store, relative to the working directory
+const DEFAULT_SESSION_DB_PATH: &str = ".session_data.db";
+
+/// Sessions expire after this many hours without a request if not configured otherwise
+const DEFAULT_SESSION_IDLE_HOURS: u32 = 12;
+
+/// Failed logins per client and per username before logins are slowed down
+const DEFAULT_LOGIN_FREE_ATTEMPTS: u32 = 5;
+
+/// Wait after the first failure beyond the free attempts; doubles with every further failure
+const DEFAULT_LOGIN_BACKOFF_SECONDS: u64 = 1;
+
+/// The wait never gets longer than this
+const DEFAULT_LOGIN_MAX_BACKOFF_SECONDS: u64 = 900;
+
+/// How failed logins slow down further attempts
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LoginLimits {
+    /// failures before the first wait
+    pub(crate) free_attempts: u32,
+    /// the first wait
+    pub(crate) backoff: std::time::Duration,
+    /// the longest wait; failures longer ago than this are forgotten
+    pub(crate) max_backoff: std::time::Duration,
+}
+
+/// Read the key that signs session cookies.
+///
+/// The key is either given in the config or read from `key_file`, both base64-encoded. A key file
+/// that does not exist yet is created with a new key, so that sessions survive restarts. Without
+/// either, a new key is generated on every start.
+fn load_session_key(key: Option<&str>, key_file: Option<&str>) -> Result<Key, String> {
+    let encoded = match (key, key_file) {
+        (Some(key), _) => key.to_owned(),
+        (None, Some(path)) => match std::fs::read_to_string(path) {
+            Ok(x) => x,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let key = Key::generate();
+                write_session_key_file(path, &key).map_err(|e| format!("{path}: {e}"))?;
+                event!(Level::INFO, "Created a new session key in {path}.");
+                return Ok(key);
+            }
+            Err(e) => return Err(format!("{path}: {e}")),
+        },
+        (None, None) => {
+            event!(
+                Level::WARN,
+                "No session key configured. Everyone will be logged out on restart."
+            );
+            return Ok(Key::generate());
+        }
+    };
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("The key is not base64: {e}"))?;
+    Key::try_from(bytes.as_slice()).map_err(|e| e.to_string())
+}
+
+/// Write `key` to a new file only we may read
+fn write_session_key_file(path: &str, key: &Key) -> Result<(), std::io::Error> {
+    use std::{io::Write, os::unix::fs::OpenOptionsExt};
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(STANDARD.encode(key.master()).as_bytes())
+}
+
+#[derive(Deserialize)]
 struct WebConfigData {
     bind_address: String,
     bind_port: u16,
     bind_port_tls: u16,
     cert_file: String,
     key_file: String,
+    /// base64 of at least 64 random bytes, used to sign session cookies
+    session_key: Option<String>,
+    /// file containing the session key, created if it does not exist
+    session_key_file: Option<String>,
+    #[serde(default)]
+    session_store: SessionStoreKind,
+    /// path of the SQLite session store
+    session_db_path: Option<String>,
+    /// hours without a request after which a session expires
+    session_idle_hours: Option<u32>,
+    /// whether session cookies are only sent over HTTPS
+    secure_cookies: Option<bool>,
+    /// failed logins per client and username before further attempts are delayed
+    login_free_attempts: Option<u32>,
+    /// seconds to wait after the first delayed failure
+    login_backoff_seconds: Option<u64>,
+    /// maximum seconds to wait between attempts
+    login_max_backoff_seconds: Option<u64>,
+}
+impl core::fmt::Debug for WebConfigData {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("WebConfigData")
+            .field("bind_address", &self.bind_address)
+            .field("bind_port", &self.bind_port)
+            .field("bind_port_tls", &self.bind_port_tls)
+            .field("cert_file", &self.cert_file)
+            .field("key_file", &self.key_file)
+            .field("session_key", &"[redacted]")
+            .field("session_key_file", &self.session_key_file)
+            .field("session_store", &self.session_store)
+            .field("session_db_path", &self.session_db_path)
+            .field("session_idle_hours", &self.session_idle_hours)
+            .field("secure_cookies", &self.secure_cookies)
+            .field("login_free_attempts", &self.login_free_attempts)
+            .field("login_backoff_seconds", &self.login_backoff_seconds)
+            .field("login_max_backoff_seconds", &self.login_max_backoff_seconds)
+            .finish()
+    }
 }
 impl WebConfigData {
     async fn try_into_web_config(self) -> Result<WebConfig, ConfigError> {
@@ -123,11 +541,54 @@ impl WebConfigData {
                 return Err(ConfigError::TlsCertKeyError(e));
             }
         };
+        // the key file is handled by load_session_key, because it is created if missing
+        let session_key = match read_secret("web_session_key", self.session_key, None) {
+            Ok(x) => x,
+            Err(e) => {
+                event!(Level::ERROR, "The session key is not available: {e}");
+                return Err(e.into());
+            }
+        };
+        let session_key =
+            match load_session_key(session_key.as_deref(), self.session_key_file.as_deref()) {
+                Ok(x) => x,
+                Err(e) => {
+                    event!(
+                        Level::ERROR,
+                        "There was a problem with the session key: {e}"
+                    );
+                    return Err(ConfigError::SessionKeyError(e));
+                }
+            };
         Ok(WebConfig {
             bind_address: self.bind_address,
             bind_port: self.bind_port,
             bind_port_tls: self.bind_port_tls,
             rustls_config,
+            session_key,
+            session_store: self.session_store,
+            session_db_path: self
+                .session_db_path
+                .unwrap_or_else(|| DEFAULT_SESSION_DB_PATH.to_owned()),
+            session_idle_expiry: time::Duration::hours(
+                self.session_idle_hours
+                    .unwrap_or(DEFAULT_SESSION_IDLE_HOURS)
+                    .into(),
+            ),
+            secure_cookies: self.secure_cookies.unwrap_or(true),
+            login_limits: LoginLimits {
+                free_attempts: self
+                    .login_free_attempts
+                    .unwrap_or(DEFAULT_LOGIN_FREE_ATTEMPTS),
+                backoff: std::time::Duration::from_secs(
+                    self.login_backoff_seconds
+                        .unwrap_or(DEFAULT_LOGIN_BACKOFF_SECONDS),
+                ),
+                max_backoff: std::time::Duration::from_secs(
+                    self.login_max_backoff_seconds
+                        .unwrap_or(DEFAULT_LOGIN_MAX_BACKOFF_SECONDS),
+                ),
+            },
         })
     }
 }
@@ -144,7 +605,11 @@ pub(crate) struct MatrixConfigData {
     /// This user will be used to create rooms and invite users
     username: String,
     /// password for that user
-    password: String,
+    password: Option<String>,
+    /// file containing the password, e.g. a Docker secret
+    password_file: Option<String>,
+    /// Set the room topic to the project description whenever either changes
+    mirror_description_to_topic: Option<bool>,
 }
 impl core::fmt::Debug for MatrixConfigData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -154,11 +619,23 @@ impl core::fmt::Debug for MatrixConfigData {
             .field("element_servername", &self.element_servername)
             .field("username", &self.username)
             .field("password", &"[redacted]")
+            .field("password_file", &self.password_file)
             .finish()
     }
 }
 impl MatrixConfigData {
     async fn try_into_matrix_client(self) -> Result<MatrixClient, ConfigError> {
+        let password = match require_secret(
+            "matrix_password",
+            self.password,
+            self.password_file.as_deref(),
+        ) {
+            Ok(x) => x,
+            Err(e) => {
+                event!(Level::ERROR, "The Matrix password is not available: {e}");
+                return Err(e.into());
+            }
+        };
         let client = Client::builder()
             .homeserver_url(self.homeserver_url)
             .build()
@@ -166,10 +643,7 @@ impl MatrixConfigData {
             .map_err(ConfigError::MatrixClientCreationError)?;
         client
             .matrix_auth()
-            .login_username(
-                format!("@{}:{}", self.username, self.servername),
-                &self.password,
-            )
+            .login_username(format!("@{}:{}", self.username, self.servername), &password)
             .send()
             .await
             .map_err(ConfigError::MatrixLoginError)?;
@@ -181,22 +655,57 @@ impl MatrixConfigData {
     }
 }
 
-#[derive(Debug)]
 pub(crate) struct WebConfig {
     pub(crate) bind_address: String,
     pub(crate) bind_port: u16,
     pub(crate) bind_port_tls: u16,
     pub(crate) rustls_config: RustlsConfig,
+    /// signs the session cookies
+    pub(crate) session_key: Key,
+    pub(crate) session_store: SessionStoreKind,
+    pub(crate) session_db_path: String,
+    pub(crate) session_idle_expiry: time::Duration,
+    pub(crate) secure_cookies: bool,
+    pub(crate) login_limits: LoginLimits,
+}
+impl core::fmt::Debug for WebConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("WebConfig")
+            .field("bind_address", &self.bind_address)
+            .field("bind_port", &self.bind_port)
+            .field("bind_port_tls", &self.bind_port_tls)
+            .field("rustls_config", &self.rustls_config)
+            .field("session_key", &"[redacted]")
+            .field("session_store", &self.session_store)
+            .field("session_db_path", &self.session_db_path)
+            .field("session_idle_expiry", &self.session_idle_expiry)
+            .field("secure_cookies", &self.secure_cookies)
+            .field("login_limits", &self.login_limits)
+            .finish()
+    }
 }
 
 /// Create a pg_pool from the [`DbConfigData`]
 async fn pg_pool_from_db_config_data(value: DbConfigData) -> Result<Pool<Postgres>, ConfigError> {
-    // postgres settings
-    let url = format!(
-        "postgres://{}:{}@{}:{}/{}",
-        value.user, value.password, value.host, value.port, value.database
-    );
-    match sqlx::postgres::PgPool::connect(&url).await {
+    let password = match require_secret(
+        "db_password",
+        value.password,
+        value.password_file.as_deref(),
+    ) {
+        Ok(x) => x,
+        Err(e) => {
+            event!(Level::ERROR, "The DB password is not available: {e}");
+            return Err(e.into());
+        }
+    };
+    // postgres settings; not as an URL, so that the password may contain any character
+    let options = sqlx::postgres::PgConnectOptions::new()
+        .host(&value.host)
+        .port(value.port)
+        .database(&value.database)
+        .username(&value.user)
+        .password(&password);
+    match sqlx::postgres::PgPool::connect_with(options).await {
         Ok(pool) => Ok(pool),
         Err(e) => {
             event!(Level::ERROR, "Could not connect to postgres: {e}");
@@ -209,56 +718,225 @@ async fn pg_pool_from_db_config_data(value: DbConfigData) -> Result<Pool<Postgre
 pub(crate) struct Config {
     pub(crate) log_level: String,
     pub(crate) user_resync_interval: u32,
-    pub(crate) ldap_backend: LDAPBackend,
+    /// Minutes between two runs of the DB -> Matrix reconciliation
+    pub(crate) room_reconcile_interval: u32,
+    pub(crate) auth_backend: AuthBackend,
     pub(crate) pg_pool: Pool<Postgres>,
     pub(crate) web_config: WebConfig,
     pub(crate) matrix_client: MatrixClient,
+    /// Keep the room topic equal to the project description
+    pub(crate) mirror_description_to_topic: bool,
 }
 impl Config {
-    pub async fn create() -> Result<Self, ConfigError> {
-        let content = std::fs::read_to_string("/etc/projekttagebuch/config.toml")
-            .map_err(ConfigError::ReadConfigFileError)?;
+    pub async fn create(path: &Path) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path).map_err(ConfigError::ReadConfigFileError)?;
         let config_data: ConfigData =
             toml::from_str(&content).map_err(ConfigError::ParseConfigFileError)?;
 
-        // LDAP
-        let ldap_backend = match crate::ldap::LDAPBackend::new(
-            &config_data.ldap.server_host,
-            config_data.ldap.server_port,
-            &config_data.ldap.bind_dn,
-            &config_data.ldap.bind_password,
-            &config_data.ldap.user_filter,
-            &config_data.ldap.write_access_filter,
-            &config_data.ldap.user_base_dn,
-        )
-        .await
-        {
-            Ok(x) => x,
-            Err(e) => {
-                event!(
-                    Level::ERROR,
-                    "LDAP connection could not be established: {e}"
-                );
-                return Err(ConfigError::LdapConnectionError(e));
-            }
-        };
-
         // DB
         let pg_pool = pg_pool_from_db_config_data(config_data.db).await?;
 
+        // Auth
+        let auth_backend = config_data
+            .auth
+            .try_into_auth_backend(config_data.ldap, config_data.oidc, pg_pool.clone())
+            .await?;
+
         // Web
         let web_config = config_data.web.try_into_web_config().await?;
 
         // Matrix
+        let mirror_description_to_topic = config_data
+            .matrix
+            .mirror_description_to_topic
+            .unwrap_or(false);
         let matrix_client = config_data.matrix.try_into_matrix_client().await?;
 
         Ok(Self {
             log_level: config_data.log_level,
             user_resync_interval: config_data.user_resync_interval.unwrap_or(10),
-            ldap_backend,
+            room_reconcile_interval: config_data.room_reconcile_interval.unwrap_or(30),
+            auth_backend,
             pg_pool,
             web_config,
             matrix_client,
+            mirror_description_to_topic,
         })
     }
 }
+
+/// Only what is needed to work on the DB, e.g. to migrate it
+///
+/// Unlike [`Config`], this does not log in to Matrix or set up the auth backend, so it also works
+/// while those are unreachable.
+#[derive(Debug)]
+pub(crate) struct DbConfig {
+    pub(crate) log_level: String,
+    pub(crate) pg_pool: Pool<Postgres>,
+}
+impl DbConfig {
+    pub async fn create(path: &Path) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path).map_err(ConfigError::ReadConfigFileError)?;
+        let config_data: DbOnlyConfigData =
+            toml::from_str(&content).map_err(ConfigError::ParseConfigFileError)?;
+        Ok(Self {
+            log_level: config_data.log_level,
+            pg_pool: pg_pool_from_db_config_data(config_data.db).await?,
+        })
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    /// A config around `pg_pool` for tests of the actions
+    ///
+    /// Nothing here talks to the outside world: there are no users to log in, the matrix client
+    /// is not logged in and the TLS config has no certificate.
+    pub(crate) async fn for_test(pg_pool: Pool<Postgres>) -> Self {
+        /// Never hands out a certificate
+        #[derive(Debug)]
+        struct NoCertificate;
+        impl rustls::server::ResolvesServerCert for NoCertificate {
+            fn resolve(
+                &self,
+                _client_hello: rustls::server::ClientHello<'_>,
+            ) -> Option<std::sync::Arc<rustls::sign::CertifiedKey>> {
+                None
+            }
+        }
+        let server_config = rustls::ServerConfig::builder_with_provider(std::sync::Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .expect("ring supports the default protocol versions")
+        .with_no_client_auth()
+        .with_cert_resolver(std::sync::Arc::new(NoCertificate));
+
+        let client = Client::builder()
+            .homeserver_url("http://localhost:1")
+            .build()
+            .await
+            .expect("building a matrix client does not need the server");
+
+        Self {
+            log_level: "info".to_owned(),
+            user_resync_interval: 10,
+            room_reconcile_interval: 30,
+            auth_backend: AuthBackend::Local(
+                LocalBackend::new(pg_pool.clone()).expect("hashing a password works"),
+            ),
+            pg_pool,
+            web_config: WebConfig {
+                bind_address: "127.0.0.1".to_owned(),
+                bind_port: 0,
+                bind_port_tls: 0,
+                rustls_config: RustlsConfig::from_config(std::sync::Arc::new(server_config)),
+                session_key: Key::generate(),
+                session_store: SessionStoreKind::Sqlite,
+                session_db_path: String::new(),
+                session_idle_expiry: time::Duration::hours(1),
+                secure_cookies: true,
+                login_limits: LoginLimits {
+                    free_attempts: 5,
+                    backoff: std::time::Duration::from_secs(1),
+                    max_backoff: std::time::Duration::from_secs(900),
+                },
+            },
+            matrix_client: MatrixClient::new(
+                client,
+                "matrix.example.org".to_owned(),
+                "element.example.org".to_owned(),
+            ),
+            mirror_description_to_topic: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn migrating_needs_only_the_db_section() {
+        let config: DbOnlyConfigData = toml::from_str(
+            r#"
+            log_level = "info"
+            [db]
+            host = "localhost"
+            port = 5432
+            database = "projekttagebuch"
+            user = "projekttagebuch"
+            password_file = "/run/secrets/db_password"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.db.database, "projekttagebuch");
+        assert!(toml::from_str::<ConfigData>(
+            r#"
+            log_level = "info"
+            [db]
+            host = "localhost"
+            port = 5432
+            database = "projekttagebuch"
+            user = "projekttagebuch"
+            "#
+        )
+        .is_err());
+
+        // the other sections are ignored
+        let example = include_str!("../config.example.toml");
+        assert!(toml::from_str::<DbOnlyConfigData>(example).is_ok());
+    }
+
+    #[test]
+    fn session_key_from_config_or_file() {
+        let key = Key::generate();
+        let encoded = STANDARD.encode(key.master());
+        assert_eq!(
+            load_session_key(Some(&encoded), None).unwrap().master(),
+            key.master()
+        );
+        assert!(load_session_key(Some("dG9vIHNob3J0"), None).is_err());
+
+        // the key file is created on first start and read afterwards
+        let path = std::env::temp_dir().join(format!("session-key-{}", uuid::Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        let created = load_session_key(None, Some(path)).unwrap();
+        let read = load_session_key(None, Some(path)).unwrap();
+        assert_eq!(created.master(), read.master());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn secrets_from_env_file_or_config() {
+        let path = std::env::temp_dir().join(format!("secret-{}", uuid::Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "from file\n").unwrap();
+        let no_env = |_: &str| None;
+        let env = |x: &str| (x == "PROJEKTTAGEBUCH_DB_PASSWORD").then(|| "from env".to_owned());
+
+        assert_eq!(
+            read_secret_with_env("db_password", Some("inline".to_owned()), None, no_env).unwrap(),
+            Some("inline".to_owned())
+        );
+        assert_eq!(
+            read_secret_with_env("db_password", Some("inline".to_owned()), Some(path), no_env)
+                .unwrap(),
+            Some("from file".to_owned())
+        );
+        assert_eq!(
+            read_secret_with_env("db_password", Some("inline".to_owned()), Some(path), env)
+                .unwrap(),
+            Some("from env".to_owned())
+        );
+        assert_eq!(
+            read_secret_with_env("db_password", None, None, no_env).unwrap(),
+            None
+        );
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(
+            read_secret_with_env("db_password", None, Some(path), no_env),
+            Err(SecretError::CannotReadFile(..))
+        ));
+    }
+}
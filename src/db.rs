@@ -3,7 +3,13 @@
 use sqlx::{PgConnection, PgPool, Postgres, Transaction};
 use tracing::{info, trace, warn};
 
-use crate::types::{DbNoMatrix, FullId, MatrixNoDb, NoId, Person, Project, UserPermission};
+use time::{Date, OffsetDateTime};
+
+use crate::auth::local::LocalUser;
+use crate::types::{
+    ApiToken, AuditAction, AuditEvent, DbNoMatrix, Entry, FullId, MatrixNoDb, NoId, Person,
+    Project, ProjectMetadata, ProjectRole, ProjectStatus, ProjectVisibility, UserPermission,
+};
 
 #[derive(Debug)]
 pub(crate) enum DBError {
@@ -20,10 +26,35 @@ pub(crate) enum DBError {
     CannotUpdateGlobalPermissions(sqlx::Error, String),
     CannotUpdateFirstname(sqlx::Error, String),
     CannotUpdateSurname(sqlx::Error, String),
+    CannotUpdateContact(sqlx::Error, String),
     CannotSelectSimilarNames(sqlx::Error),
     CannotRemoveMember(sqlx::Error),
     CannotUpdateMemberPermission(sqlx::Error),
     CannotChangeProjectName(sqlx::Error),
+    CannotInsertEntry(sqlx::Error),
+    CannotSelectEntries(sqlx::Error),
+    CannotUpdateEntry(sqlx::Error),
+    CannotDeleteEntry(sqlx::Error),
+    CannotInsertApiToken(sqlx::Error),
+    CannotSelectApiTokens(sqlx::Error),
+    CannotDeleteApiToken(sqlx::Error),
+    CannotArchiveProject(sqlx::Error),
+    CannotDeleteProject(sqlx::Error),
+    CannotInsertAuditEvent(sqlx::Error),
+    CannotSelectAuditEvents(sqlx::Error),
+    UnknownAuditAction(String),
+    CannotUpdateProjectMetadata(sqlx::Error),
+    UnknownProjectStatus(String),
+    CannotSetProjectVisibility(sqlx::Error),
+    UnknownProjectVisibility(String),
+    UnknownProjectRole(String),
+    CannotSelectLdapGroups(sqlx::Error),
+    CannotInsertLdapGroup(sqlx::Error),
+    CannotRemoveLdapGroup(sqlx::Error),
+    CannotInsertLocalUser(sqlx::Error),
+    CannotSelectLocalUsers(sqlx::Error),
+    CannotUpdateLocalUser(sqlx::Error),
+    CannotDeleteLocalUser(sqlx::Error),
 
     // DATA Errors
     ProjectDoesNotExist(i32, String),
@@ -74,12 +105,87 @@ impl core::fmt::Display for DBError {
             Self::CannotUpdateSurname(x, y) => {
                 write!(f, "Cannot update surname for user {}: {}.", x, y)
             }
+            Self::CannotUpdateContact(x, y) => {
+                write!(f, "Cannot update contact details for user {y}: {x}.")
+            }
             Self::CannotSelectSimilarNames(x) => {
                 write!(f, "Cannot select similar names: {}.", x)
             }
             Self::CannotChangeProjectName(x) => {
                 write!(f, "Cannot rename a project: {x}")
             }
+            Self::CannotInsertEntry(x) => {
+                write!(f, "Unable to insert an entry: {x}")
+            }
+            Self::CannotSelectEntries(x) => {
+                write!(f, "Unable to select entries: {x}")
+            }
+            Self::CannotUpdateEntry(x) => {
+                write!(f, "Unable to update an entry: {x}")
+            }
+            Self::CannotDeleteEntry(x) => {
+                write!(f, "Unable to delete an entry: {x}")
+            }
+            Self::CannotInsertApiToken(x) => {
+                write!(f, "Unable to insert an API token: {x}")
+            }
+            Self::CannotSelectApiTokens(x) => {
+                write!(f, "Unable to select API tokens: {x}")
+            }
+            Self::CannotDeleteApiToken(x) => {
+                write!(f, "Unable to delete an API token: {x}")
+            }
+            Self::CannotArchiveProject(x) => {
+                write!(f, "Unable to archive a project: {x}")
+            }
+            Self::CannotDeleteProject(x) => {
+                write!(f, "Unable to delete a project: {x}")
+            }
+            Self::CannotInsertAuditEvent(x) => {
+                write!(f, "Unable to insert an audit event: {x}")
+            }
+            Self::CannotSelectAuditEvents(x) => {
+                write!(f, "Unable to select audit events: {x}")
+            }
+            Self::UnknownAuditAction(x) => {
+                write!(f, "The DB contains an audit event of unknown kind {x}.")
+            }
+            Self::CannotUpdateProjectMetadata(x) => {
+                write!(f, "Unable to update the metadata of a project: {x}")
+            }
+            Self::UnknownProjectStatus(x) => {
+                write!(f, "The DB contains a project with unknown status {x}.")
+            }
+            Self::CannotSetProjectVisibility(x) => {
+                write!(f, "Unable to set the visibility of a project: {x}")
+            }
+            Self::UnknownProjectVisibility(x) => {
+                write!(f, "The DB contains a project with unknown visibility {x}.")
+            }
+            Self::UnknownProjectRole(x) => {
+                write!(f, "The DB contains a project member with unknown role {x}.")
+            }
+            Self::CannotSelectLdapGroups(x) => {
+                write!(f, "Unable to select the LDAP groups of projects: {x}")
+            }
+            Self::CannotInsertLdapGroup(x) => {
+                write!(f, "Unable to link an LDAP group to a project: {x}")
+            }
+            Self::CannotRemoveLdapGroup(x) => {
+                write!(f, "Unable to unlink an LDAP group from a project: {x}")
+            }
+            Self::CannotInsertLocalUser(x) => {
+                write!(f, "Unable to insert a local user: {x}")
+            }
+            Self::CannotSelectLocalUsers(x) => {
+                write!(f, "Unable to select local users: {x}")
+            }
+            Self::CannotUpdateLocalUser(x) => {
+                write!(f, "Unable to update a local user: {x}")
+            }
+            Self::CannotDeleteLocalUser(x) => {
+                write!(f, "Unable to delete a local user: {x}")
+            }
             Self::ProjectDoesNotExist(x, y) => {
                 write!(f, "The project with id {x}, name {y} does not exist.")
             }
@@ -91,26 +197,67 @@ impl core::fmt::Display for DBError {
 }
 impl std::error::Error for DBError {}
 
+/// Create the metadata of a project from its columns.
+fn project_metadata_from_columns(
+    description: String,
+    status: &str,
+    start_date: Option<Date>,
+    end_date: Option<Date>,
+    project_number: Option<String>,
+) -> Result<ProjectMetadata, DBError> {
+    Ok(ProjectMetadata {
+        description,
+        status: ProjectStatus::try_from(status).map_err(DBError::UnknownProjectStatus)?,
+        start_date,
+        end_date,
+        project_number,
+    })
+}
+
 /// Get a list of projects
-pub async fn get_projects(pool: PgPool) -> Result<Vec<Project<FullId>>, DBError> {
+///
+/// Archived projects are only part of the result if `include_archived` is set.
+pub async fn get_projects(
+    pool: PgPool,
+    include_archived: bool,
+) -> Result<Vec<Project<FullId>>, DBError> {
     // first get all projects (required if projects are empty)
-    let rows = sqlx::query!("SELECT ProjectID, ProjectName, ProjectRoomId FROM Project;")
+    let rows = sqlx::query!(
+        "SELECT ProjectID, ProjectName, ProjectRoomId, IsArchived, Description, Status, StartDate, EndDate, ProjectNumber, Visibility
+        FROM Project WHERE $1 OR NOT IsArchived;",
+        include_archived,
+    )
         .fetch_all(&pool)
         .await
         .map_err(DBError::CannotSelectProjects)?;
     let mut result = rows
         .into_iter()
-        .map(|r| Project::new((r.projectroomid, r.projectid), r.projectname))
-        .collect::<Vec<Project<FullId>>>();
+        .map(|r| {
+            let mut project = Project::new((r.projectroomid, r.projectid), r.projectname);
+            project.is_archived = r.isarchived;
+            project.metadata = project_metadata_from_columns(
+                r.description,
+                &r.status,
+                r.startdate,
+                r.enddate,
+                r.projectnumber,
+            )?;
+            project.visibility = ProjectVisibility::try_from(r.visibility.as_str())
+                .map_err(DBError::UnknownProjectVisibility)?;
+            Ok(project)
+        })
+        .collect::<Result<Vec<Project<FullId>>, DBError>>()?;
 
     // Now get all users part of any projects
     let rows = sqlx::query!(
-        "SELECT Project.ProjectID, Project.ProjectName, Person.PersonID, Person.PersonName, Person.PersonSurname, Person.PersonFirstname, Person.IsGlobalAdmin, PersonProjectMap.IsProjectAdmin
+        "SELECT Project.ProjectID, Project.ProjectName, Person.PersonID, Person.PersonName, Person.PersonSurname, Person.PersonFirstname, Person.IsGlobalAdmin, Person.PersonEmail, Person.PersonDisplayName, PersonProjectMap.ProjectRole
             FROM Project
         INNER JOIN PersonProjectMap
             ON Project.ProjectID = PersonProjectMap.ProjectID
         INNER JOIN Person
-            ON PersonProjectMap.PersonID = Person.PersonID;"
+            ON PersonProjectMap.PersonID = Person.PersonID
+        WHERE $1 OR NOT Project.IsArchived;",
+        include_archived,
     )
         .fetch_all(&pool)
         .await
@@ -123,13 +270,15 @@ pub async fn get_projects(pool: PgPool) -> Result<Vec<Project<FullId>>, DBError>
             UserPermission::new_from_is_admin(row.isglobaladmin),
             row.personsurname,
             row.personfirstname,
-        );
+        )
+        .with_contact(row.personemail, row.persondisplayname);
 
         for project in result.iter_mut() {
             if project.db_id() == row.projectid {
                 project.add_member(
                     person,
-                    UserPermission::new_from_is_admin(row.isprojectadmin),
+                    ProjectRole::try_from(row.projectrole.as_str())
+                        .map_err(DBError::UnknownProjectRole)?,
                 );
                 continue 'row;
             };
@@ -146,6 +295,17 @@ pub async fn get_projects(pool: PgPool) -> Result<Vec<Project<FullId>>, DBError>
             row.projectid,
         ));
     }
+
+    let rows = sqlx::query!("SELECT ProjectID, GroupDN FROM ProjectLdapGroup ORDER BY GroupDN;",)
+        .fetch_all(&pool)
+        .await
+        .map_err(DBError::CannotSelectLdapGroups)?;
+    for row in rows {
+        // groups of archived projects were not selected above
+        if let Some(project) = result.iter_mut().find(|p| p.db_id() == row.projectid) {
+            project.ldap_groups.push(row.groupdn);
+        };
+    }
     Ok(result)
 }
 
@@ -169,10 +329,11 @@ pub(crate) async fn add_project_prepare(
     let idd_project = project.set_db_id(new_id.projectid);
 
     for member in idd_project.members.iter() {
-        sqlx::query!("INSERT INTO PersonProjectMap (PersonID, ProjectID, IsProjectAdmin) VALUES ($1, $2, $3);",
+        sqlx::query!(
+            "INSERT INTO PersonProjectMap (PersonID, ProjectID, ProjectRole) VALUES ($1, $2, $3);",
             member.0.db_id(),
             new_id.projectid,
-            member.1.is_admin(),
+            member.1.as_str(),
             )
             .execute(&mut *tx)
             .await
@@ -207,7 +368,8 @@ pub(crate) async fn get_project(
 ) -> Result<Option<Project<FullId>>, DBError> {
     // first get all projects (required if projects are empty)
     let rows = sqlx::query!(
-        "SELECT ProjectID, ProjectName, ProjectRoomId FROM Project WHERE ProjectID = $1;",
+        "SELECT ProjectID, ProjectName, ProjectRoomId, IsArchived, Description, Status, StartDate, EndDate, ProjectNumber, Visibility
+        FROM Project WHERE ProjectID = $1;",
         id,
     )
     .fetch_optional(&mut *con)
@@ -218,11 +380,24 @@ pub(crate) async fn get_project(
             trace!("Project {id} does not exist.");
             return Ok(None);
         }
-        Some(x) => Project::<FullId>::new((x.projectroomid, x.projectid), x.projectname),
+        Some(x) => {
+            let mut project = Project::<FullId>::new((x.projectroomid, x.projectid), x.projectname);
+            project.is_archived = x.isarchived;
+            project.metadata = project_metadata_from_columns(
+                x.description,
+                &x.status,
+                x.startdate,
+                x.enddate,
+                x.projectnumber,
+            )?;
+            project.visibility = ProjectVisibility::try_from(x.visibility.as_str())
+                .map_err(DBError::UnknownProjectVisibility)?;
+            project
+        }
     };
 
     let rows = sqlx::query!(
-        "SELECT Project.ProjectID, Project.ProjectName, Person.PersonID, Person.PersonName, Person.PersonFirstname, Person.PersonSurname, Person.IsGlobalAdmin, PersonProjectMap.IsProjectAdmin
+        "SELECT Project.ProjectID, Project.ProjectName, Person.PersonID, Person.PersonName, Person.PersonFirstname, Person.PersonSurname, Person.IsGlobalAdmin, Person.PersonEmail, Person.PersonDisplayName, PersonProjectMap.ProjectRole
             FROM Project
         INNER JOIN PersonProjectMap
             ON Project.ProjectID = PersonProjectMap.ProjectID
@@ -232,7 +407,7 @@ pub(crate) async fn get_project(
             Project.ProjectID = $1;",
         id,
     )
-        .fetch_all(con)
+        .fetch_all(&mut *con)
         .await
         .map_err(DBError::CannotSelectProjects)?;
     'row: for row in rows {
@@ -242,12 +417,14 @@ pub(crate) async fn get_project(
             UserPermission::new_from_is_admin(row.isglobaladmin),
             row.personsurname,
             row.personfirstname,
-        );
+        )
+        .with_contact(row.personemail, row.persondisplayname);
 
         if project.db_id() == row.projectid {
             project.add_member(
                 person,
-                UserPermission::new_from_is_admin(row.isprojectadmin),
+                ProjectRole::try_from(row.projectrole.as_str())
+                    .map_err(DBError::UnknownProjectRole)?,
             );
             continue 'row;
         };
@@ -263,16 +440,27 @@ pub(crate) async fn get_project(
             row.projectid,
         ));
     }
+
+    project.ldap_groups = sqlx::query!(
+        "SELECT GroupDN FROM ProjectLdapGroup WHERE ProjectID = $1 ORDER BY GroupDN;",
+        id,
+    )
+    .fetch_all(con)
+    .await
+    .map_err(DBError::CannotSelectLdapGroups)?
+    .into_iter()
+    .map(|r| r.groupdn)
+    .collect();
     Ok(Some(project))
 }
 
 /// Remove a member from a project; Prepare a transcation, but do not commit it.
 ///
 /// This is useful when we want to make commits dependent on another system also succeeding.
-pub(crate) async fn remove_members_prepare<'a, 'b, 't>(
+pub(crate) async fn remove_members_prepare<'t>(
     pool: PgPool,
     project_id: i32,
-    members_to_remove: &'a [&'b Person<DbNoMatrix>],
+    members_to_remove: &[&Person<DbNoMatrix>],
 ) -> Result<(i64, Transaction<'t, Postgres>), DBError> {
     let mut tx = pool
         .begin()
@@ -319,14 +507,14 @@ pub(crate) async fn remove_members(
 async fn add_members_in_transaction(
     con: &mut PgConnection,
     project_id: i32,
-    members_to_add: &[(&Person<DbNoMatrix>, &UserPermission)],
+    members_to_add: &[(&Person<DbNoMatrix>, &ProjectRole)],
 ) -> Result<(), DBError> {
-    for (mem, permission) in members_to_add.iter() {
+    for (mem, role) in members_to_add.iter() {
         sqlx::query!(
-            "INSERT INTO PersonProjectMap (PersonID, ProjectID, IsProjectAdmin) VALUES ($1, $2, $3);",
+            "INSERT INTO PersonProjectMap (PersonID, ProjectID, ProjectRole) VALUES ($1, $2, $3);",
             mem.db_id(),
             project_id,
-            permission.is_admin(),
+            role.as_str(),
         )
         .execute(&mut *con)
         .await
@@ -346,13 +534,13 @@ async fn add_members_in_transaction(
 async fn add_members(
     pool: PgPool,
     project_id: i32,
-    members_to_add: &[(&Person<DbNoMatrix>, &UserPermission)],
+    members_to_add: &[(&Person<DbNoMatrix>, &ProjectRole)],
 ) -> Result<(), DBError> {
     let mut tx = pool
         .begin()
         .await
         .map_err(DBError::CannotStartTransaction)?;
-    add_members_in_transaction(&mut *tx, project_id, members_to_add).await?;
+    add_members_in_transaction(&mut tx, project_id, members_to_add).await?;
 
     tx.commit()
         .await
@@ -365,9 +553,9 @@ async fn add_members(
     Ok(())
 }
 
-pub(crate) async fn update_project_members_prepare<'a, 't>(
+pub(crate) async fn update_project_members_prepare<'t>(
     pool: PgPool,
-    project: &'a Project<FullId>,
+    project: &Project<FullId>,
 ) -> Result<Transaction<'t, Postgres>, DBError> {
     let mut tx = pool
         .begin()
@@ -375,7 +563,7 @@ pub(crate) async fn update_project_members_prepare<'a, 't>(
         .map_err(DBError::CannotStartTransaction)?;
 
     let old_project =
-        get_project(&mut *tx, project.db_id())
+        get_project(&mut tx, project.db_id())
             .await?
             .ok_or(DBError::ProjectDoesNotExist(
                 project.db_id(),
@@ -393,13 +581,13 @@ pub(crate) async fn update_project_members_prepare<'a, 't>(
             }
         })
         .collect::<Vec<_>>();
-    // this also tracks admin-status
+    // this also tracks the role
     let members_to_add = project
         .members
         .iter()
-        .filter_map(|(m, is_adm)| {
+        .filter_map(|(m, role)| {
             if old_project.members.iter().all(|(n, _)| m != n) {
-                Some((m, is_adm))
+                Some((m, role))
             } else {
                 None
             }
@@ -411,7 +599,7 @@ pub(crate) async fn update_project_members_prepare<'a, 't>(
         remove_members_prepare(pool.clone(), project.db_id(), &members_to_remove).await?;
 
     // add new members
-    add_members_in_transaction(&mut *tx, project.db_id(), &members_to_add).await?;
+    add_members_in_transaction(&mut tx, project.db_id(), &members_to_add).await?;
 
     Ok(tx)
 }
@@ -432,25 +620,76 @@ pub(crate) async fn update_project_members(
     Ok(())
 }
 
-/// Set(overwrite) the members of a project
+/// Set(overwrite) the role of a member of a project
 pub(crate) async fn update_member_permission(
-    pool: PgPool,
+    con: &mut PgConnection,
     project_id: i32,
     person_id: i32,
-    new_permission: UserPermission,
+    new_role: ProjectRole,
 ) -> Result<(), DBError> {
     sqlx::query!(
-        "UPDATE PersonProjectMap SET IsProjectAdmin = $1 WHERE PersonID = $2 AND ProjectID = $3;",
-        new_permission.is_admin(),
+        "UPDATE PersonProjectMap SET ProjectRole = $1 WHERE PersonID = $2 AND ProjectID = $3;",
+        new_role.as_str(),
         person_id,
         project_id,
     )
-    .execute(&pool)
+    .execute(con)
     .await
     .map_err(DBError::CannotUpdateMemberPermission)?;
     Ok(())
 }
 
+/// Archive a project; Prepare a transaction, but do not commit it.
+///
+/// This is useful when we want to make commits dependent on another system also succeeding.
+pub(crate) async fn archive_project_prepare<'t>(
+    pool: PgPool,
+    project_id: i32,
+) -> Result<Transaction<'t, Postgres>, DBError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    sqlx::query!(
+        "UPDATE Project SET IsArchived = TRUE WHERE ProjectID = $1;",
+        project_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(DBError::CannotArchiveProject)?;
+
+    Ok(tx)
+}
+
+/// Delete a project together with its memberships and entries; Prepare a transaction, but do not
+/// commit it.
+///
+/// This is useful when we want to make commits dependent on another system also succeeding.
+pub(crate) async fn delete_project_prepare<'t>(
+    pool: PgPool,
+    project_id: i32,
+) -> Result<Transaction<'t, Postgres>, DBError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    // PersonProjectMap restricts deleting the project, so the members have to go first.
+    // Entries are deleted by the cascade.
+    sqlx::query!(
+        "DELETE FROM PersonProjectMap WHERE ProjectID = $1;",
+        project_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(DBError::CannotRemoveMember)?;
+    sqlx::query!("DELETE FROM Project WHERE ProjectID = $1;", project_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(DBError::CannotDeleteProject)?;
+
+    Ok(tx)
+}
+
 /// Change the name of a project in the db.
 pub(crate) async fn rename_project_in_tx(
     con: &mut PgConnection,
@@ -468,105 +707,740 @@ pub(crate) async fn rename_project_in_tx(
     Ok(())
 }
 
-/// Add a person.
-#[allow(dead_code)]
-async fn add_person(pool: PgPool, person: Person<NoId>) -> Result<Person<DbNoMatrix>, DBError> {
-    let mut tx = pool
-        .begin()
-        .await
-        .map_err(DBError::CannotStartTransaction)?;
-    let new_id_result = sqlx::query!(
-        "INSERT INTO Person (PersonName, PersonSurname, PersonFirstname, IsGlobalAdmin) VALUES ($1, $2, $3, $4) RETURNING PersonID",
-        &person.name,
-        person.surname,
-        person.firstname,
-        &person.is_global_admin(),
+/// Change the metadata of a project in the db.
+pub(crate) async fn update_project_metadata(
+    con: &mut PgConnection,
+    project_id: i32,
+    metadata: &ProjectMetadata,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "UPDATE Project SET Description = $1, Status = $2, StartDate = $3, EndDate = $4, ProjectNumber = $5 WHERE ProjectID = $6;",
+        metadata.description,
+        metadata.status.as_str(),
+        metadata.start_date,
+        metadata.end_date,
+        metadata.project_number,
+        project_id,
     )
-    .fetch_one(&mut *tx)
+    .execute(con)
     .await
-    .map_err(DBError::CannotInsertPerson)?;
+    .map_err(DBError::CannotUpdateProjectMetadata)?;
+    Ok(())
+}
 
-    tx.commit()
-        .await
-        .map_err(DBError::CannotCommitTransaction)?;
+/// Change who can see a project.
+pub(crate) async fn set_project_visibility(
+    con: &mut PgConnection,
+    project_id: i32,
+    visibility: ProjectVisibility,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "UPDATE Project SET Visibility = $1 WHERE ProjectID = $2;",
+        visibility.as_str(),
+        project_id,
+    )
+    .execute(con)
+    .await
+    .map_err(DBError::CannotSetProjectVisibility)?;
+    Ok(())
+}
 
-    Ok(Person::<DbNoMatrix>::new(
-        new_id_result.personid,
-        person.name,
-        person.global_permission,
-        person.surname,
-        person.firstname,
-    ))
+/// Sync the members of an LDAP group into a project.
+///
+/// Returns false if the group was already linked.
+pub(crate) async fn add_project_ldap_group(
+    con: &mut PgConnection,
+    project_id: i32,
+    group_dn: &str,
+) -> Result<bool, DBError> {
+    let res = sqlx::query!(
+        "INSERT INTO ProjectLdapGroup (ProjectID, GroupDN) VALUES ($1, $2) ON CONFLICT DO NOTHING;",
+        project_id,
+        group_dn,
+    )
+    .execute(con)
+    .await
+    .map_err(DBError::CannotInsertLdapGroup)?;
+    Ok(res.rows_affected() == 1)
 }
 
-/// Get a person from the DB by name (exact)
-pub async fn get_person(pool: PgPool, name: &str) -> Result<Option<Person<DbNoMatrix>>, DBError> {
-    let mut tx = pool
-        .begin()
-        .await
-        .map_err(DBError::CannotStartTransaction)?;
-    let id_result = sqlx::query!(
-        "SELECT PersonID, PersonFirstname, PersonSurname, IsGlobalAdmin from Person WHERE PersonName LIKE $1;",
-        name,
+/// Stop syncing the members of an LDAP group into a project.
+///
+/// Returns false if the group was not linked.
+pub(crate) async fn remove_project_ldap_group(
+    con: &mut PgConnection,
+    project_id: i32,
+    group_dn: &str,
+) -> Result<bool, DBError> {
+    let res = sqlx::query!(
+        "DELETE FROM ProjectLdapGroup WHERE ProjectID = $1 AND GroupDN = $2;",
+        project_id,
+        group_dn,
     )
-    .fetch_optional(&mut *tx)
+    .execute(con)
     .await
-    .map_err(DBError::CannotSelectPersonByExactName)?;
+    .map_err(DBError::CannotRemoveLdapGroup)?;
+    Ok(res.rows_affected() == 1)
+}
 
-    match id_result {
-        Some(x) => Ok(Some(Person::<DbNoMatrix>::new(
-            x.personid,
-            name.to_owned(),
-            UserPermission::new_from_is_admin(x.isglobaladmin),
-            x.personsurname,
-            x.personfirstname,
-        ))),
-        None => Ok(None),
+/// Get the names of the members of a project that were added by the LDAP group sync
+pub(crate) async fn get_synced_member_names(
+    con: &mut PgConnection,
+    project_id: i32,
+) -> Result<Vec<String>, DBError> {
+    Ok(sqlx::query!(
+        "SELECT Person.PersonName FROM PersonProjectMap
+        INNER JOIN Person
+            ON PersonProjectMap.PersonID = Person.PersonID
+        WHERE PersonProjectMap.ProjectID = $1 AND NOT PersonProjectMap.IsManual
+        ORDER BY Person.PersonName;",
+        project_id,
+    )
+    .fetch_all(con)
+    .await
+    .map_err(DBError::CannotSelectPersons)?
+    .into_iter()
+    .map(|r| r.personname)
+    .collect())
+}
+
+/// Add members on behalf of the LDAP group sync.
+///
+/// They are added as [`ProjectRole::Member`] and marked, so that the sync may remove them again.
+pub(crate) async fn add_synced_members(
+    con: &mut PgConnection,
+    project_id: i32,
+    members_to_add: &[&Person<DbNoMatrix>],
+) -> Result<(), DBError> {
+    for mem in members_to_add.iter() {
+        sqlx::query!(
+            "INSERT INTO PersonProjectMap (PersonID, ProjectID, ProjectRole, IsManual) VALUES ($1, $2, $3, FALSE);",
+            mem.db_id(),
+            project_id,
+            ProjectRole::Member.as_str(),
+        )
+        .execute(&mut *con)
+        .await
+        .map_err(DBError::CannotInsertPPMap)?;
     }
+    Ok(())
 }
 
-/// Get all persons from the DB
-async fn get_all_persons(pool: PgPool) -> Result<Vec<Person<DbNoMatrix>>, DBError> {
-    let mut tx = pool
-        .begin()
+/// Remove members on behalf of the LDAP group sync.
+///
+/// Members that were added by hand are never removed here.
+pub(crate) async fn remove_synced_members(
+    con: &mut PgConnection,
+    project_id: i32,
+    members_to_remove: &[&Person<DbNoMatrix>],
+) -> Result<(), DBError> {
+    for mem in members_to_remove.iter() {
+        sqlx::query!(
+            "DELETE FROM PersonProjectMap WHERE PersonID = $1 AND ProjectID = $2 AND NOT IsManual;",
+            mem.db_id(),
+            project_id,
+        )
+        .execute(&mut *con)
         .await
-        .map_err(DBError::CannotStartTransaction)?;
-    let res = sqlx::query!(
-        "SELECT PersonID, PersonName, PersonSurname, PersonFirstname, IsGlobalAdmin from Person;",
+        .map_err(DBError::CannotRemoveMember)?;
+    }
+    Ok(())
+}
+
+/// Add a diary entry to the project it belongs to.
+pub(crate) async fn add_entry(
+    con: &mut PgConnection,
+    entry: Entry<NoId>,
+) -> Result<Entry<DbNoMatrix>, DBError> {
+    let new_id = sqlx::query!(
+        "INSERT INTO Entry (ProjectID, PersonID, EntryTimestamp, EntryTitle, EntryBody) VALUES ($1, $2, $3, $4, $5) RETURNING EntryID;",
+        entry.project_id(),
+        entry.author.as_ref().map(|a| a.db_id()),
+        entry.timestamp,
+        entry.title,
+        entry.body,
     )
-    .fetch_all(&mut *tx)
+    .fetch_one(con)
     .await
-    .map_err(DBError::CannotSelectPersons)?;
-    Ok(res
+    .map_err(DBError::CannotInsertEntry)?;
+    Ok(entry.set_db_id(new_id.entryid))
+}
+
+/// Create the author of an entry from the (left-joined) Person columns.
+fn entry_author_from_columns(
+    person_id: Option<i32>,
+    name: Option<String>,
+    surname: Option<String>,
+    firstname: Option<String>,
+    is_global_admin: Option<bool>,
+) -> Option<Person<DbNoMatrix>> {
+    Some(Person::new(
+        person_id?,
+        name?,
+        UserPermission::new_from_is_admin(is_global_admin?),
+        surname,
+        firstname,
+    ))
+}
+
+/// Get all diary entries of a project, newest first
+pub(crate) async fn get_entries(
+    con: &mut PgConnection,
+    project_id: i32,
+) -> Result<Vec<Entry<DbNoMatrix>>, DBError> {
+    let rows = sqlx::query!(
+        r#"SELECT Entry.EntryID, Entry.ProjectID, Entry.EntryTimestamp, Entry.EntryTitle, Entry.EntryBody, Person.PersonID as "personid?", Person.PersonName as "personname?", Person.PersonSurname, Person.PersonFirstname, Person.IsGlobalAdmin as "isglobaladmin?"
+            FROM Entry
+        LEFT JOIN Person
+            ON Entry.PersonID = Person.PersonID
+        WHERE
+            Entry.ProjectID = $1
+        ORDER BY Entry.EntryTimestamp DESC;"#,
+        project_id,
+    )
+    .fetch_all(con)
+    .await
+    .map_err(DBError::CannotSelectEntries)?;
+    Ok(rows
         .into_iter()
         .map(|r| {
-            Person::new(
+            Entry::from_db(
+                r.entryid,
+                r.projectid,
+                entry_author_from_columns(
+                    r.personid,
+                    r.personname,
+                    r.personsurname,
+                    r.personfirstname,
+                    r.isglobaladmin,
+                ),
+                r.entrytimestamp,
+                r.entrytitle,
+                r.entrybody,
+            )
+        })
+        .collect::<Vec<_>>())
+}
+
+/// Get a single diary entry from known ID
+pub(crate) async fn get_entry(
+    con: &mut PgConnection,
+    entry_id: i32,
+) -> Result<Option<Entry<DbNoMatrix>>, DBError> {
+    let row = sqlx::query!(
+        r#"SELECT Entry.EntryID, Entry.ProjectID, Entry.EntryTimestamp, Entry.EntryTitle, Entry.EntryBody, Person.PersonID as "personid?", Person.PersonName as "personname?", Person.PersonSurname, Person.PersonFirstname, Person.IsGlobalAdmin as "isglobaladmin?"
+            FROM Entry
+        LEFT JOIN Person
+            ON Entry.PersonID = Person.PersonID
+        WHERE
+            Entry.EntryID = $1;"#,
+        entry_id,
+    )
+    .fetch_optional(con)
+    .await
+    .map_err(DBError::CannotSelectEntries)?;
+    Ok(row.map(|r| {
+        Entry::from_db(
+            r.entryid,
+            r.projectid,
+            entry_author_from_columns(
                 r.personid,
                 r.personname,
-                UserPermission::new_from_is_admin(r.isglobaladmin),
                 r.personsurname,
                 r.personfirstname,
-            )
-        })
-        .collect::<Vec<_>>())
+                r.isglobaladmin,
+            ),
+            r.entrytimestamp,
+            r.entrytitle,
+            r.entrybody,
+        )
+    }))
 }
 
-/// Update users in the DB such that exactly these users exist with these permissions.
-///
-/// NOTE: Permissions are global permissions here, not project-based.
-pub async fn update_users(pool: PgPool, users: Vec<Person<NoId>>) -> Result<(), DBError> {
-    trace!("Want these users to be in the db: {users:?}");
-    // first get users from DB to calculate diff
-    let users_in_db = get_all_persons(pool.clone()).await?;
+/// Change title and body of a diary entry.
+pub(crate) async fn update_entry(
+    con: &mut PgConnection,
+    entry_id: i32,
+    new_title: &str,
+    new_body: &str,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "UPDATE Entry SET EntryTitle = $1, EntryBody = $2 WHERE EntryID = $3;",
+        new_title,
+        new_body,
+        entry_id,
+    )
+    .execute(con)
+    .await
+    .map_err(DBError::CannotUpdateEntry)?;
+    Ok(())
+}
 
-    let users_to_delete = users_in_db
-        .iter()
-        .filter(|p| users.iter().all(|q| p.name != q.name));
+/// Delete a diary entry.
+pub(crate) async fn delete_entry(con: &mut PgConnection, entry_id: i32) -> Result<(), DBError> {
+    sqlx::query!("DELETE FROM Entry WHERE EntryID = $1;", entry_id)
+        .execute(con)
+        .await
+        .map_err(DBError::CannotDeleteEntry)?;
+    Ok(())
+}
+
+/// Add an API token. Only the hash of its secret is stored.
+pub(crate) async fn add_api_token(
+    con: &mut PgConnection,
+    token: ApiToken<NoId>,
+    token_hash: &str,
+) -> Result<ApiToken<DbNoMatrix>, DBError> {
+    let new_id = sqlx::query!(
+        "INSERT INTO ApiToken (PersonID, TokenHash, TokenLabel, CreatedAt, ExpiresAt) VALUES ($1, $2, $3, $4, $5) RETURNING ApiTokenID;",
+        token.person_id(),
+        token_hash,
+        token.label,
+        token.created_at,
+        token.expires_at,
+    )
+    .fetch_one(con)
+    .await
+    .map_err(DBError::CannotInsertApiToken)?;
+    Ok(token.set_db_id(new_id.apitokenid))
+}
+
+/// Get all API tokens of a person, newest first
+pub(crate) async fn get_api_tokens(
+    pool: PgPool,
+    person_id: i32,
+) -> Result<Vec<ApiToken<DbNoMatrix>>, DBError> {
+    let rows = sqlx::query!(
+        "SELECT ApiTokenID, PersonID, TokenLabel, CreatedAt, ExpiresAt, LastUsedAt FROM ApiToken WHERE PersonID = $1 ORDER BY CreatedAt DESC;",
+        person_id,
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(DBError::CannotSelectApiTokens)?;
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            ApiToken::from_db(
+                r.apitokenid,
+                r.personid,
+                r.tokenlabel,
+                r.createdat,
+                r.expiresat,
+                r.lastusedat,
+            )
+        })
+        .collect::<Vec<_>>())
+}
+
+/// Revoke an API token of a person.
+///
+/// Returns the label of the revoked token, or None if that person has no such token.
+pub(crate) async fn revoke_api_token(
+    con: &mut PgConnection,
+    person_id: i32,
+    api_token_id: i32,
+) -> Result<Option<String>, DBError> {
+    let res = sqlx::query!(
+        "DELETE FROM ApiToken WHERE ApiTokenID = $1 AND PersonID = $2 RETURNING TokenLabel;",
+        api_token_id,
+        person_id,
+    )
+    .fetch_optional(con)
+    .await
+    .map_err(DBError::CannotDeleteApiToken)?;
+    Ok(res.map(|r| r.tokenlabel))
+}
+
+/// Get the person an API token acts as, marking the token as used just now.
+///
+/// Returns None if no token has this hash or if the token has expired.
+pub(crate) async fn get_person_by_api_token(
+    pool: PgPool,
+    token_hash: &str,
+) -> Result<Option<Person<DbNoMatrix>>, DBError> {
+    let row = sqlx::query!(
+        r#"WITH Used AS (
+            UPDATE ApiToken SET LastUsedAt = now()
+            WHERE
+                TokenHash = $1
+                AND (ExpiresAt IS NULL OR ExpiresAt > now())
+            RETURNING PersonID
+        )
+        SELECT Person.PersonID as "personid!", Person.PersonName as "personname!", Person.PersonSurname, Person.PersonFirstname, Person.IsGlobalAdmin as "isglobaladmin!", Person.PersonEmail, Person.PersonDisplayName
+            FROM Person
+        INNER JOIN Used
+            ON Person.PersonID = Used.PersonID;"#,
+        token_hash,
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(DBError::CannotSelectApiTokens)?;
+    Ok(row.map(|r| {
+        Person::new(
+            r.personid,
+            r.personname,
+            UserPermission::new_from_is_admin(r.isglobaladmin),
+            r.personsurname,
+            r.personfirstname,
+        )
+        .with_contact(r.personemail, r.persondisplayname)
+    }))
+}
+
+/// Record an audit event.
+///
+/// Call this on the transaction that makes the change, so that both are committed together.
+pub(crate) async fn add_audit_event(
+    con: &mut PgConnection,
+    event: &AuditEvent<NoId>,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "INSERT INTO AuditEvent (CreatedAt, ActorName, ActionKind, ProjectID, ProjectName, TargetName, OldValue, NewValue) VALUES ($1, $2, $3, $4, $5, $6, $7, $8);",
+        event.created_at,
+        event.actor_name,
+        event.action.as_str(),
+        event.project_id,
+        event.project_name,
+        event.target_name,
+        event.old_value,
+        event.new_value,
+    )
+    .execute(con)
+    .await
+    .map_err(DBError::CannotInsertAuditEvent)?;
+    Ok(())
+}
+
+/// At most this many audit events are returned at once
+pub(crate) const AUDIT_EVENT_LIMIT: i64 = 500;
+
+/// Restrict the audit events returned by [`get_audit_events`]. Unset fields match everything.
+#[derive(Debug, Default)]
+pub(crate) struct AuditEventFilter {
+    pub(crate) actor_name: Option<String>,
+    pub(crate) project_id: Option<i32>,
+    /// only events at or after this time
+    pub(crate) from: Option<OffsetDateTime>,
+    /// only events before this time
+    pub(crate) until: Option<OffsetDateTime>,
+}
+
+/// Get audit events matching `filter`, newest first
+pub(crate) async fn get_audit_events(
+    pool: PgPool,
+    filter: &AuditEventFilter,
+) -> Result<Vec<AuditEvent<DbNoMatrix>>, DBError> {
+    let rows = sqlx::query!(
+        "SELECT AuditEventID, CreatedAt, ActorName, ActionKind, ProjectID, ProjectName, TargetName, OldValue, NewValue
+        FROM AuditEvent
+        WHERE ($1::TEXT IS NULL OR ActorName = $1)
+            AND ($2::INTEGER IS NULL OR ProjectID = $2)
+            AND ($3::TIMESTAMPTZ IS NULL OR CreatedAt >= $3)
+            AND ($4::TIMESTAMPTZ IS NULL OR CreatedAt < $4)
+        ORDER BY CreatedAt DESC, AuditEventID DESC
+        LIMIT $5;",
+        filter.actor_name,
+        filter.project_id,
+        filter.from,
+        filter.until,
+        AUDIT_EVENT_LIMIT,
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(DBError::CannotSelectAuditEvents)?;
+    rows.into_iter()
+        .map(|r| {
+            let action = AuditAction::try_from(r.actionkind.as_str())
+                .map_err(DBError::UnknownAuditAction)?;
+            Ok(AuditEvent::from_db(
+                r.auditeventid,
+                r.createdat,
+                r.actorname,
+                action,
+                r.projectid,
+                r.projectname,
+                r.targetname,
+                r.oldvalue,
+                r.newvalue,
+            ))
+        })
+        .collect()
+}
+
+/// This many projects are shown on one page of the landing page
+pub(crate) const PROJECT_PAGE_SIZE: i64 = 24;
+
+/// Restrict the projects returned by [`search_projects`]. Unset fields match everything.
+#[derive(Debug, Default)]
+pub(crate) struct ProjectFilter {
+    pub(crate) include_archived: bool,
+    /// matched against names, descriptions, project numbers and diary entries
+    pub(crate) search: Option<String>,
+    /// only projects this person is a member of
+    pub(crate) member_id: Option<i32>,
+    /// only projects this person is an admin of
+    pub(crate) admin_id: Option<i32>,
+    pub(crate) status: Option<ProjectStatus>,
+    /// Hide secret projects this person is not a member of, and only search the metadata and
+    /// diary of projects they may read.
+    /// None shows everything; this is meant for global admins.
+    pub(crate) viewer_id: Option<i32>,
+    /// 0-based, pages are [`PROJECT_PAGE_SIZE`] long
+    pub(crate) page: i64,
+}
+
+/// Get one page of the projects matching `filter`, together with the number of all matching
+/// projects.
+///
+/// Projects are ordered by how well their name matches the search, then by name.
+pub(crate) async fn search_projects(
+    pool: PgPool,
+    filter: &ProjectFilter,
+) -> Result<(Vec<Project<FullId>>, i64), DBError> {
+    let mut con = pool
+        .acquire()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let rows = sqlx::query!(
+        r#"SELECT ProjectID, COUNT(*) OVER () AS "total!"
+        FROM Project
+        WHERE ($1 OR NOT IsArchived)
+            AND ($2::TEXT IS NULL
+                -- the search is a substring, not a pattern
+                OR ProjectName ILIKE '%' || replace(replace(replace($2::TEXT, '\', '\\'), '%', '\%'), '_', '\_') || '%' ESCAPE '\'
+                OR $2::TEXT <% ProjectName
+                -- the rest may only be searched by those who may read the project
+                OR (($8::INTEGER IS NULL OR Visibility = 'public' OR EXISTS (SELECT 1 FROM PersonProjectMap
+                        WHERE PersonProjectMap.ProjectID = Project.ProjectID
+                            AND PersonProjectMap.PersonID = $8))
+                    AND ($2::TEXT <% Description
+                        OR $2::TEXT <% ProjectNumber
+                        OR EXISTS (SELECT 1 FROM Entry
+                            WHERE Entry.ProjectID = Project.ProjectID
+                                AND ($2::TEXT <% EntryTitle OR $2::TEXT <% EntryBody)))))
+            AND ($3::INTEGER IS NULL OR EXISTS (SELECT 1 FROM PersonProjectMap
+                WHERE PersonProjectMap.ProjectID = Project.ProjectID
+                    AND PersonProjectMap.PersonID = $3))
+            AND ($4::INTEGER IS NULL OR EXISTS (SELECT 1 FROM PersonProjectMap
+                WHERE PersonProjectMap.ProjectID = Project.ProjectID
+                    AND PersonProjectMap.PersonID = $4
+                    AND PersonProjectMap.ProjectRole IN ('maintainer', 'owner')))
+            AND ($5::TEXT IS NULL OR Status = $5)
+            AND ($8::INTEGER IS NULL OR Visibility <> 'secret' OR EXISTS (SELECT 1 FROM PersonProjectMap
+                WHERE PersonProjectMap.ProjectID = Project.ProjectID
+                    AND PersonProjectMap.PersonID = $8))
+        ORDER BY
+            CASE WHEN $2::TEXT IS NULL THEN 0 ELSE word_similarity($2::TEXT, ProjectName) END DESC,
+            ProjectName,
+            ProjectID
+        LIMIT $6 OFFSET $7;"#,
+        filter.include_archived,
+        filter.search,
+        filter.member_id,
+        filter.admin_id,
+        filter.status.map(|s| s.as_str()),
+        PROJECT_PAGE_SIZE,
+        filter.page * PROJECT_PAGE_SIZE,
+        filter.viewer_id,
+    )
+    .fetch_all(&mut *con)
+    .await
+    .map_err(DBError::CannotSelectProjects)?;
+
+    let total = rows.first().map(|r| r.total).unwrap_or(0);
+    let mut result = vec![];
+    for row in rows {
+        if let Some(project) = get_project(&mut con, row.projectid).await? {
+            result.push(project);
+        };
+    }
+    Ok((result, total))
+}
+
+/// Add a person.
+#[allow(dead_code)]
+async fn add_person(pool: PgPool, person: Person<NoId>) -> Result<Person<DbNoMatrix>, DBError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let new_id_result = sqlx::query!(
+        "INSERT INTO Person (PersonName, PersonSurname, PersonFirstname, IsGlobalAdmin, PersonEmail, PersonDisplayName) VALUES ($1, $2, $3, $4, $5, $6) RETURNING PersonID",
+        &person.name,
+        person.surname,
+        person.firstname,
+        &person.is_global_admin(),
+        person.email,
+        person.display_name,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(DBError::CannotInsertPerson)?;
+
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+
+    Ok(Person::<DbNoMatrix>::new(
+        new_id_result.personid,
+        person.name,
+        person.global_permission,
+        person.surname,
+        person.firstname,
+    )
+    .with_contact(person.email, person.display_name))
+}
+
+/// Insert a person that just logged in, or update them if they already exist.
+///
+/// Used when a user logs in before the periodic sync has seen them.
+pub(crate) async fn provision_person(
+    pool: PgPool,
+    person: Person<NoId>,
+) -> Result<Person<DbNoMatrix>, DBError> {
+    let row = sqlx::query!(
+        "INSERT INTO Person (PersonName, PersonSurname, PersonFirstname, IsGlobalAdmin, PersonEmail, PersonDisplayName) VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (PersonName) DO UPDATE SET PersonSurname = EXCLUDED.PersonSurname, PersonFirstname = EXCLUDED.PersonFirstname, IsGlobalAdmin = EXCLUDED.IsGlobalAdmin, PersonEmail = EXCLUDED.PersonEmail, PersonDisplayName = EXCLUDED.PersonDisplayName
+        RETURNING PersonID",
+        &person.name,
+        person.surname,
+        person.firstname,
+        &person.is_global_admin(),
+        person.email,
+        person.display_name,
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(DBError::CannotInsertPerson)?;
+    info!(
+        "Provisioned user {} in the DB as {}.",
+        person.name, person.global_permission
+    );
+
+    Ok(Person::<DbNoMatrix>::new(
+        row.personid,
+        person.name,
+        person.global_permission,
+        person.surname,
+        person.firstname,
+    )
+    .with_contact(person.email, person.display_name))
+}
+
+/// Get a person from the DB by name (exact)
+pub async fn get_person(pool: PgPool, name: &str) -> Result<Option<Person<DbNoMatrix>>, DBError> {
     let mut tx = pool
         .begin()
         .await
         .map_err(DBError::CannotStartTransaction)?;
+    let id_result = sqlx::query!(
+        "SELECT PersonID, PersonFirstname, PersonSurname, IsGlobalAdmin, PersonEmail, PersonDisplayName from Person WHERE PersonName LIKE $1;",
+        name,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(DBError::CannotSelectPersonByExactName)?;
+
+    match id_result {
+        Some(x) => Ok(Some(
+            Person::<DbNoMatrix>::new(
+                x.personid,
+                name.to_owned(),
+                UserPermission::new_from_is_admin(x.isglobaladmin),
+                x.personsurname,
+                x.personfirstname,
+            )
+            .with_contact(x.personemail, x.persondisplayname),
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Get all persons from the DB
+async fn get_all_persons(pool: PgPool) -> Result<Vec<Person<DbNoMatrix>>, DBError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let res = sqlx::query!(
+        "SELECT PersonID, PersonName, PersonSurname, PersonFirstname, IsGlobalAdmin, PersonEmail, PersonDisplayName from Person;",
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(DBError::CannotSelectPersons)?;
+    Ok(res
+        .into_iter()
+        .map(|r| {
+            Person::new(
+                r.personid,
+                r.personname,
+                UserPermission::new_from_is_admin(r.isglobaladmin),
+                r.personsurname,
+                r.personfirstname,
+            )
+            .with_contact(r.personemail, r.persondisplayname)
+        })
+        .collect::<Vec<_>>())
+}
+
+/// Get all projects `person_id` is a member of, archived or not
+pub(crate) async fn get_projects_of_person(
+    con: &mut PgConnection,
+    person_id: i32,
+) -> Result<Vec<Project<FullId>>, DBError> {
+    let rows = sqlx::query!(
+        "SELECT ProjectID FROM PersonProjectMap WHERE PersonID = $1 ORDER BY ProjectID;",
+        person_id,
+    )
+    .fetch_all(&mut *con)
+    .await
+    .map_err(DBError::CannotSelectProjects)?;
+    let mut result = vec![];
+    for row in rows {
+        let project_id = match row.projectid {
+            Some(x) => x,
+            None => continue,
+        };
+        if let Some(project) = get_project(&mut *con, project_id).await? {
+            result.push(project);
+        };
+    }
+    Ok(result)
+}
+
+/// Update users in the DB such that exactly these users exist with these permissions.
+/// Prepare a transaction, but do not commit it.
+///
+/// Deleting a person drops their project memberships. Return every deleted person together with
+/// the projects they were a member of, so that the caller can remove them from matrix as well
+/// before committing.
+///
+/// NOTE: Permissions are global permissions here, not project-based.
+pub(crate) async fn update_users_prepare<'t>(
+    pool: PgPool,
+    users: Vec<Person<NoId>>,
+) -> Result<
+    (
+        Vec<(Person<DbNoMatrix>, Vec<Project<FullId>>)>,
+        Transaction<'t, Postgres>,
+    ),
+    DBError,
+> {
+    trace!("Want these users to be in the db: {users:?}");
+    // first get users from DB to calculate diff
+    let users_in_db = get_all_persons(pool.clone()).await?;
+
+    let users_to_delete = users_in_db
+        .into_iter()
+        .filter(|p| users.iter().all(|q| p.name != q.name));
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let mut deleted = vec![];
     for user in users_to_delete {
+        // the memberships are gone once the person is deleted, so get them first
+        let projects = get_projects_of_person(&mut tx, user.db_id()).await?;
         sqlx::query!("DELETE FROM Person WHERE PersonID = $1;", user.db_id(),)
             .execute(&mut *tx)
             .await
@@ -574,12 +1448,13 @@ pub async fn update_users(pool: PgPool, users: Vec<Person<NoId>>) -> Result<(),
         info!(
             "Removed user {} from DB. They no longer exist in LDAP.",
             user.name
-        )
+        );
+        deleted.push((user, projects));
     }
     for user in users {
         // get user by name
         let person = sqlx::query!(
-            "SELECT PersonID, PersonSurname, PersonFirstname, IsGlobalAdmin from Person WHERE PersonName LIKE $1;",
+            "SELECT PersonID, PersonSurname, PersonFirstname, IsGlobalAdmin, PersonEmail, PersonDisplayName from Person WHERE PersonName LIKE $1;",
             user.name,
         )
         .fetch_optional(&mut *tx)
@@ -588,11 +1463,13 @@ pub async fn update_users(pool: PgPool, users: Vec<Person<NoId>>) -> Result<(),
         match person {
             None => {
                 sqlx::query!(
-                    "INSERT INTO Person (PersonName, PersonSurname, PersonFirstname, IsGlobalAdmin) VALUES ($1, $2, $3, $4);",
+                    "INSERT INTO Person (PersonName, PersonSurname, PersonFirstname, IsGlobalAdmin, PersonEmail, PersonDisplayName) VALUES ($1, $2, $3, $4, $5, $6);",
                     user.name,
                     user.surname,
                     user.firstname,
-                    user.is_global_admin()
+                    user.is_global_admin(),
+                    user.email,
+                    user.display_name,
                 )
                 .execute(&mut *tx)
                 .await
@@ -646,10 +1523,36 @@ pub async fn update_users(pool: PgPool, users: Vec<Person<NoId>>) -> Result<(),
                     .map_err(|e| DBError::CannotUpdateSurname(e, user.name.to_owned()))?;
                     trace!("User {} Firstname set to: {:?}", user.name, user.firstname,);
                 };
+                if row.personemail != user.email || row.persondisplayname != user.display_name {
+                    sqlx::query!(
+                        "UPDATE Person SET PersonEmail = $1, PersonDisplayName = $2 WHERE PersonName = $3;",
+                        user.email,
+                        user.display_name,
+                        user.name,
+                    )
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| DBError::CannotUpdateContact(e, user.name.to_owned()))?;
+                    trace!(
+                        "User {} contact set to: {:?}, {:?}",
+                        user.name,
+                        user.email,
+                        user.display_name
+                    );
+                };
             }
         };
     }
 
+    Ok((deleted, tx))
+}
+
+/// Update users in the DB such that exactly these users exist with these permissions.
+///
+/// Internally calls [`update_users_prepare`] which implements the logic.
+#[allow(dead_code)]
+pub async fn update_users(pool: PgPool, users: Vec<Person<NoId>>) -> Result<(), DBError> {
+    let (_deleted, tx) = update_users_prepare(pool, users).await?;
     tx.commit()
         .await
         .map_err(DBError::CannotCommitTransaction)?;
@@ -661,7 +1564,7 @@ pub(crate) async fn get_persons_with_similar_name(
     name_like: &str,
 ) -> Result<Vec<Person<DbNoMatrix>>, DBError> {
     Ok(sqlx::query!(
-        "SELECT PersonID, PersonName, PersonSurname, PersonFirstname, IsGlobalAdmin, similarity($1, concat(PersonSurname, ' ', PersonFirstname))
+        "SELECT PersonID, PersonName, PersonSurname, PersonFirstname, IsGlobalAdmin, PersonEmail, PersonDisplayName, similarity($1, concat(PersonSurname, ' ', PersonFirstname))
         FROM Person
         ORDER BY similarity DESC
         LIMIT 5;",
@@ -677,10 +1580,121 @@ pub(crate) async fn get_persons_with_similar_name(
                 UserPermission::new_from_is_admin(r.isglobaladmin),
                 r.personsurname,
                 r.personfirstname,)
+            .with_contact(r.personemail, r.persondisplayname)
         )
     .collect::<Vec<_>>())
 }
 
+/// Add a user of the local auth backend.
+///
+/// Returns false if a user with this name already exists.
+pub(crate) async fn add_local_user(
+    con: &mut PgConnection,
+    user: &LocalUser,
+    password_hash: &str,
+) -> Result<bool, DBError> {
+    let res = sqlx::query!(
+        "INSERT INTO LocalUser (LocalUserName, PasswordHash, LocalUserFirstname, LocalUserSurname, LocalUserEmail, LocalUserDisplayName, IsAdmin)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (LocalUserName) DO NOTHING
+            RETURNING LocalUserID;",
+        user.username,
+        password_hash,
+        user.firstname,
+        user.surname,
+        user.email,
+        user.display_name,
+        user.admin,
+    )
+    .fetch_optional(con)
+    .await
+    .map_err(DBError::CannotInsertLocalUser)?;
+    Ok(res.is_some())
+}
+
+/// Get a user of the local auth backend together with their password hash
+pub(crate) async fn get_local_user(
+    pool: PgPool,
+    username: &str,
+) -> Result<Option<(LocalUser, String)>, DBError> {
+    let res = sqlx::query!(
+        "SELECT LocalUserName, PasswordHash, LocalUserFirstname, LocalUserSurname, LocalUserEmail, LocalUserDisplayName, IsAdmin
+            FROM LocalUser WHERE LocalUserName = $1;",
+        username,
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(DBError::CannotSelectLocalUsers)?;
+    Ok(res.map(|r| {
+        (
+            LocalUser {
+                username: r.localusername,
+                firstname: r.localuserfirstname,
+                surname: r.localusersurname,
+                email: r.localuseremail,
+                display_name: r.localuserdisplayname,
+                admin: r.isadmin,
+            },
+            r.passwordhash,
+        )
+    }))
+}
+
+/// Get all users of the local auth backend, ordered by name
+pub(crate) async fn get_local_users(pool: PgPool) -> Result<Vec<LocalUser>, DBError> {
+    let rows = sqlx::query!(
+        "SELECT LocalUserName, LocalUserFirstname, LocalUserSurname, LocalUserEmail, LocalUserDisplayName, IsAdmin
+            FROM LocalUser ORDER BY LocalUserName;",
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(DBError::CannotSelectLocalUsers)?;
+    Ok(rows
+        .into_iter()
+        .map(|r| LocalUser {
+            username: r.localusername,
+            firstname: r.localuserfirstname,
+            surname: r.localusersurname,
+            email: r.localuseremail,
+            display_name: r.localuserdisplayname,
+            admin: r.isadmin,
+        })
+        .collect())
+}
+
+/// Set the password hash of a user of the local auth backend.
+///
+/// Returns false if there is no user with this name.
+pub(crate) async fn set_local_user_password(
+    con: &mut PgConnection,
+    username: &str,
+    password_hash: &str,
+) -> Result<bool, DBError> {
+    let res = sqlx::query!(
+        "UPDATE LocalUser SET PasswordHash = $1 WHERE LocalUserName = $2;",
+        password_hash,
+        username,
+    )
+    .execute(con)
+    .await
+    .map_err(DBError::CannotUpdateLocalUser)?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Remove a user of the local auth backend. The next user sync removes them from the projects.
+///
+/// Returns false if there is no user with this name.
+pub(crate) async fn remove_local_user(
+    con: &mut PgConnection,
+    username: &str,
+) -> Result<bool, DBError> {
+    let res = sqlx::query!("DELETE FROM LocalUser WHERE LocalUserName = $1;", username)
+        .execute(con)
+        .await
+        .map_err(DBError::CannotDeleteLocalUser)?;
+    Ok(res.rows_affected() > 0)
+}
+
 /// Test at runtime whether we can establish a connection to the DB
 pub(crate) async fn try_acquire_connection(pool: PgPool) -> Result<(), DBError> {
     pool.begin()
@@ -702,6 +1716,8 @@ mod test {
             global_permission: UserPermission::User,
             firstname: Some("John".to_owned()),
             surname: Some("Doe".to_owned()),
+            email: Some("john@example.com".to_owned()),
+            display_name: None,
         };
         add_person(pool.clone(), person).await.unwrap();
         let ps = get_all_persons(pool.clone()).await.unwrap();
@@ -714,20 +1730,51 @@ mod test {
                 Some("Doe".to_owned()),
                 Some("John".to_owned())
             )
+            .with_contact(Some("john@example.com".to_owned()), None)
         );
         Ok(())
     }
 
+    #[sqlx::test(fixtures("empty"))]
+    async fn test_provision_person(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        let person = Person::<NoId>::new(
+            (),
+            "adam".to_owned(),
+            UserPermission::User,
+            None,
+            Some("Adam".to_owned()),
+        );
+        let first = provision_person(pool.clone(), person).await.unwrap();
+        // logging in again updates the person instead of adding another one
+        let second = provision_person(
+            pool.clone(),
+            Person::<NoId>::new(
+                (),
+                "adam".to_owned(),
+                UserPermission::Admin,
+                Some("Abramovich".to_owned()),
+                Some("Adam".to_owned()),
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(first.db_id(), second.db_id());
+        let ps = get_all_persons(pool.clone()).await.unwrap();
+        assert_eq!(ps, vec![second]);
+        assert_eq!(ps[0].global_permission, UserPermission::Admin);
+        Ok(())
+    }
+
     #[sqlx::test(fixtures("two_projects"))]
     async fn test_get_projects(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
-        let projects = get_projects(pool.clone()).await.unwrap();
+        let projects = get_projects(pool.clone(), false).await.unwrap();
         assert_eq!(projects.len(), 2);
         Ok(())
     }
 
     #[sqlx::test(fixtures("empty_project"))]
     async fn test_get_projects_no_users(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
-        let projects = get_projects(pool.clone()).await.unwrap();
+        let projects = get_projects(pool.clone(), false).await.unwrap();
         assert_eq!(projects.len(), 1);
         Ok(())
     }
@@ -750,7 +1797,7 @@ mod test {
             Some("Adam".to_owned()),
             Some("Abrahamovitch".to_owned()),
         );
-        let persons = vec![(&adam, &UserPermission::User)];
+        let persons = vec![(&adam, &ProjectRole::Member)];
         add_members(pool.clone(), project_id, &persons).await?;
 
         let project = get_project(&mut pool.clone().acquire().await.unwrap(), project_id)
@@ -810,9 +1857,9 @@ mod test {
         let samuel = add_person(pool.clone(), samuel).await?;
 
         let mut basil_1 = Project::new(("matrix-id".to_owned(), 1), "1Basil".to_owned());
-        basil_1.members.push((david, UserPermission::User));
-        basil_1.members.push((hanna, UserPermission::Admin));
-        basil_1.members.push((samuel, UserPermission::Admin));
+        basil_1.members.push((david, ProjectRole::Member));
+        basil_1.members.push((hanna, ProjectRole::Maintainer));
+        basil_1.members.push((samuel, ProjectRole::Owner));
 
         update_project_members(pool.clone(), &basil_1).await?;
 
@@ -857,16 +1904,542 @@ mod test {
 
     #[sqlx::test(fixtures("two_projects"))]
     async fn test_update_member_permission(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
-        update_member_permission(pool.clone(), 1, 1, UserPermission::User).await?;
+        update_member_permission(
+            &mut pool.clone().acquire().await.unwrap(),
+            1,
+            1,
+            ProjectRole::Viewer,
+        )
+        .await?;
 
         let res = get_project(&mut pool.clone().acquire().await.unwrap(), 1)
             .await?
             .unwrap();
-        for (member, perm) in res.members {
+        for (member, role) in res.members {
             if member.db_id() == 1 {
-                assert_eq!(perm, UserPermission::User);
+                assert_eq!(role, ProjectRole::Viewer);
+            };
+        }
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects"))]
+    async fn test_archive_project(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        let tx = archive_project_prepare(pool.clone(), 1).await?;
+        tx.commit().await?;
+
+        let projects = get_projects(pool.clone(), false).await?;
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].db_id(), 2);
+
+        let projects = get_projects(pool.clone(), true).await?;
+        assert_eq!(projects.len(), 2);
+        let archived = get_project(&mut pool.clone().acquire().await.unwrap(), 1)
+            .await?
+            .unwrap();
+        assert!(archived.is_archived);
+        // the members stay with the archived project
+        assert_eq!(archived.members.len(), 2);
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects"))]
+    async fn test_update_project_metadata(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut con = pool.clone().acquire().await.unwrap();
+        // new projects start out active and without metadata
+        let project = get_project(&mut con, 1).await?.unwrap();
+        assert_eq!(project.metadata, ProjectMetadata::default());
+
+        let metadata = ProjectMetadata {
+            description: "# Ziel\nEin neues Dach".to_owned(),
+            status: ProjectStatus::Planned,
+            start_date: Some(time::macros::date!(2024 - 03 - 01)),
+            end_date: None,
+            project_number: Some("KST-4711".to_owned()),
+        };
+        update_project_metadata(&mut con, 1, &metadata).await?;
+        assert_eq!(get_project(&mut con, 1).await?.unwrap().metadata, metadata);
+        let projects = get_projects(pool.clone(), false).await?;
+        for project in projects {
+            if project.db_id() == 1 {
+                assert_eq!(project.metadata, metadata);
+            } else {
+                assert_eq!(project.metadata, ProjectMetadata::default());
+            }
+        }
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects", "entries"))]
+    async fn test_search_projects(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        async fn ids(
+            pool: &PgPool,
+            filter: ProjectFilter,
+        ) -> Result<(Vec<i32>, i64), Box<dyn std::error::Error>> {
+            let (projects, total) = search_projects(pool.clone(), &filter).await?;
+            Ok((projects.iter().map(|p| p.db_id()).collect(), total))
+        }
+
+        assert_eq!(ids(&pool, ProjectFilter::default()).await?, (vec![1, 2], 2));
+        // names, also by substring
+        let filter = ProjectFilter {
+            search: Some("2Bas".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&pool, filter).await?, (vec![2], 1));
+        // wildcards are searched for literally
+        for search in ["_", "%", "\\"] {
+            let filter = ProjectFilter {
+                search: Some(search.to_owned()),
+                ..Default::default()
             };
+            assert_eq!(ids(&pool, filter).await?, (vec![], 0));
         }
+        // diary entries
+        let filter = ProjectFilter {
+            search: Some("budget".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&pool, filter).await?, (vec![1], 1));
+
+        // Beth is only a member of 1, Gamaliel only an admin of 2
+        let filter = ProjectFilter {
+            member_id: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(ids(&pool, filter).await?, (vec![1], 1));
+        let filter = ProjectFilter {
+            admin_id: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(ids(&pool, filter).await?, (vec![2], 1));
+        let filter = ProjectFilter {
+            admin_id: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(ids(&pool, filter).await?, (vec![], 0));
+
+        let metadata = ProjectMetadata {
+            status: ProjectStatus::Done,
+            ..Default::default()
+        };
+        update_project_metadata(&mut pool.clone().acquire().await.unwrap(), 2, &metadata).await?;
+        let filter = ProjectFilter {
+            status: Some(ProjectStatus::Done),
+            ..Default::default()
+        };
+        assert_eq!(ids(&pool, filter).await?, (vec![2], 1));
+
+        archive_project_prepare(pool.clone(), 1)
+            .await?
+            .commit()
+            .await?;
+        assert_eq!(ids(&pool, ProjectFilter::default()).await?, (vec![2], 1));
+        let filter = ProjectFilter {
+            include_archived: true,
+            ..Default::default()
+        };
+        assert_eq!(ids(&pool, filter).await?, (vec![1, 2], 2));
+
+        // there is only one page
+        let filter = ProjectFilter {
+            include_archived: true,
+            page: 1,
+            ..Default::default()
+        };
+        assert!(ids(&pool, filter).await?.0.is_empty());
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects"))]
+    async fn test_project_visibility(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut con = pool.clone().acquire().await?;
+        assert_eq!(
+            get_project(&mut con, 2).await?.unwrap().visibility,
+            ProjectVisibility::Internal
+        );
+        set_project_visibility(&mut con, 2, ProjectVisibility::Secret).await?;
+        assert_eq!(
+            get_project(&mut con, 2).await?.unwrap().visibility,
+            ProjectVisibility::Secret
+        );
+
+        // Beth is not a member of the secret project 2, Gamaliel is
+        let filter = ProjectFilter {
+            viewer_id: Some(2),
+            ..Default::default()
+        };
+        let (projects, total) = search_projects(pool.clone(), &filter).await?;
+        assert_eq!(
+            projects.iter().map(|p| p.db_id()).collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert_eq!(total, 1);
+        let filter = ProjectFilter {
+            viewer_id: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(search_projects(pool.clone(), &filter).await?.1, 2);
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects", "entries"))]
+    async fn test_search_only_reads_readable_projects(
+        pool: PgPool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // "budget" only appears in an entry of the internal project 1, which Gamaliel is not a
+        // member of
+        let search = |viewer_id| ProjectFilter {
+            search: Some("budget".to_owned()),
+            viewer_id,
+            ..Default::default()
+        };
+        assert_eq!(search_projects(pool.clone(), &search(Some(3))).await?.1, 0);
+        assert_eq!(search_projects(pool.clone(), &search(Some(2))).await?.1, 1);
+        assert_eq!(search_projects(pool.clone(), &search(None)).await?.1, 1);
+
+        // the name can still be found
+        let filter = ProjectFilter {
+            search: Some("1Basil".to_owned()),
+            viewer_id: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(search_projects(pool.clone(), &filter).await?.1, 1);
+
+        let mut con = pool.clone().acquire().await?;
+        set_project_visibility(&mut con, 1, ProjectVisibility::Public).await?;
+        assert_eq!(search_projects(pool.clone(), &search(Some(3))).await?.1, 1);
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects", "entries"))]
+    async fn test_delete_project(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        // dropping the transaction rolls the deletion back
+        let tx = delete_project_prepare(pool.clone(), 1).await?;
+        drop(tx);
+        assert!(get_project(&mut pool.clone().acquire().await.unwrap(), 1)
+            .await?
+            .is_some());
+
+        let tx = delete_project_prepare(pool.clone(), 1).await?;
+        tx.commit().await?;
+        assert!(get_project(&mut pool.clone().acquire().await.unwrap(), 1)
+            .await?
+            .is_none());
+        let entries = get_entries(&mut pool.clone().acquire().await.unwrap(), 1).await?;
+        assert!(entries.is_empty());
+        assert_eq!(get_projects(pool.clone(), true).await?.len(), 1);
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects", "entries"))]
+    async fn test_get_entries(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        let entries = get_entries(&mut pool.clone().acquire().await.unwrap(), 1).await?;
+        assert_eq!(entries.len(), 2);
+        // newest first
+        assert_eq!(entries[0].title, "Zweites Treffen");
+        assert_eq!(entries[0].author.as_ref().unwrap().name, "Beth");
+
+        let entries = get_entries(&mut pool.clone().acquire().await.unwrap(), 2).await?;
+        assert!(entries.is_empty());
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects"))]
+    async fn test_add_entry(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        let project = get_project(&mut pool.clone().acquire().await.unwrap(), 2)
+            .await?
+            .unwrap();
+        let author = get_person(pool.clone(), "Gamaliel").await?.unwrap();
+        let entry = Entry::<NoId>::new(&project, author, "Titel".to_owned(), "Inhalt".to_owned());
+        let idd_entry = add_entry(&mut pool.clone().acquire().await.unwrap(), entry).await?;
+
+        let res = get_entry(
+            &mut pool.clone().acquire().await.unwrap(),
+            idd_entry.db_id(),
+        )
+        .await?
+        .unwrap();
+        assert_eq!(res.project_id(), 2);
+        assert_eq!(res.body, "Inhalt");
+        assert_eq!(res.author.unwrap().name, "Gamaliel");
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects", "entries"))]
+    async fn test_update_and_delete_entry(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        update_entry(
+            &mut pool.clone().acquire().await.unwrap(),
+            1,
+            "Neuer Titel",
+            "Neuer Inhalt",
+        )
+        .await?;
+        let res = get_entry(&mut pool.clone().acquire().await.unwrap(), 1)
+            .await?
+            .unwrap();
+        assert_eq!(res.title, "Neuer Titel");
+        assert_eq!(res.body, "Neuer Inhalt");
+
+        delete_entry(&mut pool.clone().acquire().await.unwrap(), 1).await?;
+        let res = get_entry(&mut pool.clone().acquire().await.unwrap(), 1).await?;
+        assert!(res.is_none());
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects", "entries"))]
+    async fn test_entry_survives_author_deletion(
+        pool: PgPool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let adam = Person::<NoId>::new(
+            (),
+            "Adam".to_owned(),
+            UserPermission::User,
+            Some("Abramovich".to_owned()),
+            Some("Adam".to_owned()),
+        );
+        // Beth and Gamaliel are removed
+        update_users(pool.clone(), vec![adam]).await?;
+
+        let entries = get_entries(&mut pool.clone().acquire().await.unwrap(), 1).await?;
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].author.is_none());
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects"))]
+    async fn test_update_users_reports_lost_memberships(
+        pool: PgPool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let gamaliel = Person::<NoId>::new(
+            (),
+            "Gamaliel".to_owned(),
+            UserPermission::User,
+            Some("Germof".to_owned()),
+            Some("Gamaliel".to_owned()),
+        );
+        // Adam and Beth are removed
+        let (deleted, tx) = update_users_prepare(pool.clone(), vec![gamaliel]).await?;
+        let mut deleted = deleted
+            .into_iter()
+            .map(|(person, projects)| {
+                (
+                    person.name,
+                    projects.iter().map(|p| p.db_id()).collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        deleted.sort();
+        assert_eq!(
+            deleted,
+            vec![
+                ("Adam".to_owned(), vec![1, 2]),
+                ("Beth".to_owned(), vec![1])
+            ]
+        );
+        tx.commit().await?;
+
+        let project = get_project(&mut pool.clone().acquire().await.unwrap(), 1)
+            .await?
+            .unwrap();
+        assert!(project.members.is_empty());
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects", "api_tokens"))]
+    async fn test_get_api_tokens(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        let tokens = get_api_tokens(pool.clone(), 1).await?;
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].label, "backup script");
+        assert!(!tokens[0].is_expired());
+        assert!(tokens[1].is_expired());
+        assert!(get_api_tokens(pool.clone(), 2).await?.is_empty());
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects", "api_tokens"))]
+    async fn test_get_person_by_api_token(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        let person = get_person_by_api_token(
+            pool.clone(),
+            &crate::types::hash_api_token_secret("ptb_valid"),
+        )
+        .await?;
+        assert_eq!(person.map(|p| p.name), Some("Adam".to_owned()));
+        assert!(get_api_tokens(pool.clone(), 1).await?[0]
+            .last_used_at
+            .is_some());
+
+        // expired and unknown tokens do not resolve to anyone
+        let expired = crate::types::hash_api_token_secret("ptb_expired");
+        assert!(get_person_by_api_token(pool.clone(), &expired)
+            .await?
+            .is_none());
+        assert!(get_api_tokens(pool.clone(), 1).await?[1]
+            .last_used_at
+            .is_none());
+        assert!(get_person_by_api_token(pool.clone(), "unknown")
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects"))]
+    async fn test_add_and_revoke_api_token(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        let beth = get_person(pool.clone(), "Beth").await?.unwrap();
+        let (token, secret) = ApiToken::generate(&beth, "ci".to_owned(), None);
+        let token = add_api_token(
+            &mut pool.clone().acquire().await.unwrap(),
+            token,
+            &crate::types::hash_api_token_secret(&secret),
+        )
+        .await?;
+        let tokens = get_api_tokens(pool.clone(), beth.db_id()).await?;
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].db_id(), token.db_id());
+        assert_eq!(tokens[0].label, "ci");
+        assert_eq!(
+            get_person_by_api_token(pool.clone(), &crate::types::hash_api_token_secret(&secret))
+                .await?,
+            Some(beth.clone())
+        );
+
+        // only the owner can revoke a token
+        assert!(
+            revoke_api_token(&mut pool.clone().acquire().await.unwrap(), 1, token.db_id())
+                .await?
+                .is_none()
+        );
+        let mut con = pool.clone().acquire().await.unwrap();
+        assert_eq!(
+            revoke_api_token(&mut con, beth.db_id(), token.db_id()).await?,
+            Some(token.label.clone())
+        );
+        assert!(get_api_tokens(pool.clone(), beth.db_id()).await?.is_empty());
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects"))]
+    async fn test_add_and_filter_audit_events(
+        pool: PgPool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let adam = get_person(pool.clone(), "Adam").await?.unwrap();
+        let beth = get_person(pool.clone(), "Beth").await?.unwrap();
+        let mut con = pool.clone().acquire().await.unwrap();
+        let first = get_project(&mut con, 1).await?.unwrap();
+        let second = get_project(&mut con, 2).await?.unwrap();
+
+        let mut rename = AuditEvent::new(&adam, AuditAction::RenameProject)
+            .with_project(&first)
+            .with_values(Some("1Basil".to_owned()), Some("Basil".to_owned()));
+        rename.created_at = time::macros::datetime!(2024-03-01 12:00 UTC);
+        add_audit_event(&mut con, &rename).await?;
+        let permission = AuditEvent::new(&adam, AuditAction::SetMemberPermission)
+            .with_project(&second)
+            .with_target(&beth)
+            .with_values(Some("User".to_owned()), Some("Admin".to_owned()));
+        add_audit_event(&mut con, &permission).await?;
+        let token = AuditEvent::new(&beth, AuditAction::CreateApiToken)
+            .with_values(None, Some("ci".to_owned()));
+        add_audit_event(&mut con, &token).await?;
+
+        // newest first
+        let all = get_audit_events(pool.clone(), &AuditEventFilter::default()).await?;
+        assert_eq!(
+            all.iter().map(|e| e.action).collect::<Vec<_>>(),
+            vec![
+                AuditAction::CreateApiToken,
+                AuditAction::SetMemberPermission,
+                AuditAction::RenameProject
+            ]
+        );
+        assert_eq!(all[1].target_name.as_deref(), Some("Beth"));
+        assert_eq!(all[1].project_name.as_deref(), Some("2Basil"));
+
+        let by_actor = AuditEventFilter {
+            actor_name: Some("Adam".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(get_audit_events(pool.clone(), &by_actor).await?.len(), 2);
+        let by_project = AuditEventFilter {
+            project_id: Some(1),
+            ..Default::default()
+        };
+        let events = get_audit_events(pool.clone(), &by_project).await?;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].old_value.as_deref(), Some("1Basil"));
+        let by_date = AuditEventFilter {
+            from: Some(time::macros::datetime!(2024-03-01 00:00 UTC)),
+            until: Some(time::macros::datetime!(2024-03-02 00:00 UTC)),
+            ..Default::default()
+        };
+        let events = get_audit_events(pool.clone(), &by_date).await?;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action, AuditAction::RenameProject);
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("two_projects"))]
+    async fn test_ldap_groups_and_synced_members(
+        pool: PgPool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut con = pool.clone().acquire().await.unwrap();
+        let group = "cn=team,ou=groups,dc=example,dc=com";
+        assert!(add_project_ldap_group(&mut con, 1, group).await?);
+        assert!(!add_project_ldap_group(&mut con, 1, group).await?);
+        let project = get_project(&mut con, 1).await?.unwrap();
+        assert_eq!(project.ldap_groups, vec![group.to_owned()]);
+        let projects = get_projects(pool.clone(), false).await?;
+        assert_eq!(projects[0].ldap_groups, vec![group.to_owned()]);
+        assert!(projects[1].ldap_groups.is_empty());
+
+        // Adam and Beth were added by hand, Gamaliel comes from the group
+        let adam = get_person(pool.clone(), "Adam").await?.unwrap();
+        let gamaliel = get_person(pool.clone(), "Gamaliel").await?.unwrap();
+        add_synced_members(&mut con, 1, &[&gamaliel]).await?;
+        assert_eq!(
+            get_synced_member_names(&mut con, 1).await?,
+            vec!["Gamaliel"]
+        );
+        let project = get_project(&mut con, 1).await?.unwrap();
+        assert_eq!(
+            project.local_role_for_user(&gamaliel),
+            Some(ProjectRole::Member)
+        );
+
+        // the sync never removes members added by hand
+        remove_synced_members(&mut con, 1, &[&adam, &gamaliel]).await?;
+        let project = get_project(&mut con, 1).await?.unwrap();
+        assert_eq!(project.members.len(), 2);
+        assert!(project.local_role_for_user(&adam).is_some());
+        assert!(project.local_role_for_user(&gamaliel).is_none());
+
+        assert!(remove_project_ldap_group(&mut con, 1, group).await?);
+        assert!(!remove_project_ldap_group(&mut con, 1, group).await?);
+        assert!(get_project(&mut con, 1)
+            .await?
+            .unwrap()
+            .ldap_groups
+            .is_empty());
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("empty"))]
+    async fn test_change_local_users(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut con = pool.acquire().await?;
+        let adam = LocalUser::new("adam".to_owned(), false);
+        assert!(add_local_user(&mut con, &adam, "old hash").await?);
+
+        assert!(set_local_user_password(&mut con, "adam", "new hash").await?);
+        assert!(!set_local_user_password(&mut con, "eve", "new hash").await?);
+        assert_eq!(
+            get_local_user(pool.clone(), "adam").await?,
+            Some((adam.clone(), "new hash".to_owned()))
+        );
+        assert_eq!(get_local_users(pool.clone()).await?, vec![adam]);
+
+        assert!(remove_local_user(&mut con, "adam").await?);
+        assert!(!remove_local_user(&mut con, "adam").await?);
+        assert!(get_local_user(pool.clone(), "adam").await?.is_none());
         Ok(())
     }
 }
@@ -0,0 +1,5 @@
+--- insert two tokens for Adam, one of them expired (requires two_projects)
+--- the secrets are 'ptb_valid' and 'ptb_expired'
+
+INSERT INTO ApiToken (PersonID, TokenHash, TokenLabel, CreatedAt, ExpiresAt) VALUES (1, '5abcaecc3c62fdf6c7810d3a78a6e041a9a27f6219920880c5990364db9f3347', 'backup script', '2024-09-01 10:00:00+00', NULL);
+INSERT INTO ApiToken (PersonID, TokenHash, TokenLabel, CreatedAt, ExpiresAt) VALUES (1, '88dc52e9de69ffe1383b4e10120cc9494f0f2b0a4c14b4986e5170550549fb10', 'old script', '2024-08-01 10:00:00+00', '2024-09-01 10:00:00+00');
@@ -0,0 +1,3 @@
+--- archive project 1 (requires two_projects)
+
+UPDATE Project SET IsArchived = TRUE WHERE ProjectID = 1;
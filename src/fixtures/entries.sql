@@ -0,0 +1,4 @@
+--- insert two entries into project 1 (requires two_projects)
+
+INSERT INTO Entry (ProjectID, PersonID, EntryTimestamp, EntryTitle, EntryBody) VALUES (1, 1, '2024-09-01 10:00:00+00', 'Kickoff', 'Alle Beteiligten waren anwesend.');
+INSERT INTO Entry (ProjectID, PersonID, EntryTimestamp, EntryTitle, EntryBody) VALUES (1, 2, '2024-09-08 10:00:00+00', 'Zweites Treffen', 'Budget besprochen.');
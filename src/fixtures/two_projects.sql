@@ -7,8 +7,8 @@ INSERT INTO Person (PersonName, PersonSurname, PersonFirstname) VALUES ('Gamalie
 INSERT INTO Project (ProjectName, ProjectRoomId) VALUES ('1Basil', 'matrix-id');
 INSERT INTO Project (ProjectName, ProjectRoomId) VALUES ('2Basil', 'matrix-id');
 
-INSERT INTO PersonProjectMap (ProjectID, PersonID, IsProjectAdmin) VALUES (1, 1, TRUE);
-INSERT INTO PersonProjectMap (ProjectID, PersonID, IsProjectAdmin) VALUES (1, 2, FALSE);
+INSERT INTO PersonProjectMap (ProjectID, PersonID, ProjectRole) VALUES (1, 1, 'maintainer');
+INSERT INTO PersonProjectMap (ProjectID, PersonID, ProjectRole) VALUES (1, 2, 'member');
 
-INSERT INTO PersonProjectMap (ProjectID, PersonID, IsProjectAdmin) VALUES (2, 1, FALSE);
-INSERT INTO PersonProjectMap (ProjectID, PersonID, IsProjectAdmin) VALUES (2, 3, TRUE);
+INSERT INTO PersonProjectMap (ProjectID, PersonID, ProjectRole) VALUES (2, 1, 'member');
+INSERT INTO PersonProjectMap (ProjectID, PersonID, ProjectRole) VALUES (2, 3, 'maintainer');
@@ -1,9 +1,17 @@
-use axum_login::{AuthUser, AuthnBackend, UserId};
-use ldap3::{Ldap, LdapConnAsync, LdapError, Scope, SearchEntry};
+use std::collections::{HashMap, HashSet};
+
+use ldap3::{
+    adapters::{Adapter, EntriesOnly, PagedResults},
+    Ldap, LdapError, Scope, SearchEntry, SearchResult,
+};
 use serde::Deserialize;
 use tracing::{info, warn, Level};
 
-use crate::types::{NoId, Person, UserPermission};
+use crate::{
+    auth::{User, UserCredentials},
+    ldap_pool::{LdapPool, DEFAULT_POOL_SIZE},
+    types::{NoId, Person, UserPermission},
+};
 
 /// escape parameter such that it may be used in a search filter
 /// uses RFC2254 Section 4 and RFC4514 Section 2.4
@@ -39,60 +47,178 @@ fn escape_ldap_search_filter_parameter(parameter: &str) -> String {
     res
 }
 
-/// Functions for accessing LDAP
-#[derive(Clone)]
-pub(crate) struct User {
+/// LDAP result code for a search base that does not exist
+const NO_SUCH_OBJECT: u32 = 32;
+
+/// The uids of the members of a group, given the attributes of the group.
+///
+/// `memberUid` (posixGroup) already contains uids. `member` (groupOfNames) contains DNs which
+/// are looked up in `uid_by_dn`, whose keys are lowercase. Members not found there are skipped.
+fn group_member_uids(
+    group_attrs: &HashMap<String, Vec<String>>,
+    uid_by_dn: &HashMap<String, String>,
+) -> Vec<String> {
+    let mut res = group_attrs
+        .get("memberUid")
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|uid| uid_by_dn.values().any(|known| known == uid))
+        .collect::<Vec<_>>();
+    for dn in group_attrs.get("member").into_iter().flatten() {
+        if let Some(uid) = uid_by_dn.get(&dn.to_lowercase()) {
+            res.push(uid.clone());
+        };
+    }
+    res.sort();
+    res.dedup();
+    res
+}
+
+/// A user found in LDAP
+struct LdapUser {
     /// the full dn used in LDAP
     ldap_dn: String,
     /// the uid in LDAP
+    username: String,
+}
+
+/// Which LDAP attributes hold what we know about a user
+///
+/// The defaults fit an OpenLDAP directory with `inetOrgPerson` entries. For Active Directory,
+/// `username` is usually `sAMAccountName`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub(crate) struct LdapAttributes {
+    /// The unique name users log in with
     pub(crate) username: String,
+    pub(crate) firstname: String,
+    pub(crate) surname: String,
+    pub(crate) email: String,
+    pub(crate) display_name: String,
 }
-impl std::fmt::Debug for User {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        f.debug_struct("User")
-            .field("username", &self.username)
-            .finish()
+impl Default for LdapAttributes {
+    fn default() -> Self {
+        Self {
+            username: "uid".to_owned(),
+            firstname: "givenName".to_owned(),
+            surname: "sn".to_owned(),
+            email: "mail".to_owned(),
+            display_name: "displayName".to_owned(),
+        }
     }
 }
-
-impl AuthUser for User {
-    type Id = String;
-    fn id(&self) -> Self::Id {
-        self.username.clone()
+impl LdapAttributes {
+    /// All attributes, as requested in a search
+    fn all(&self) -> Vec<&str> {
+        vec![
+            &self.username,
+            &self.firstname,
+            &self.surname,
+            &self.email,
+            &self.display_name,
+        ]
     }
-    fn session_auth_hash(&self) -> &[u8] {
-        "constant".as_bytes()
+}
+
+/// Where users are searched for below the base DN
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SearchScope {
+    /// Only the entries directly below the base DN
+    #[default]
+    One,
+    /// The whole subtree, for users in nested OUs
+    Sub,
+}
+impl From<SearchScope> for Scope {
+    fn from(value: SearchScope) -> Self {
+        match value {
+            SearchScope::One => Scope::OneLevel,
+            SearchScope::Sub => Scope::Subtree,
+        }
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub(crate) struct UserCredentials {
-    pub username: String,
-    pub password: String,
+/// The values of the attribute `name`. Attribute names are case-insensitive in LDAP.
+fn attribute_values<'a>(entry: &'a SearchEntry, name: &str) -> Option<&'a Vec<String>> {
+    entry
+        .attrs
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, values)| values)
+}
+
+/// The first value of the attribute `name`, if there is any
+fn first_attribute_value(entry: &SearchEntry, name: &str) -> Option<String> {
+    attribute_values(entry, name).and_then(|v| v.iter().next().cloned())
+}
+
+/// Turn the results of a user search into persons.
+///
+/// Users whose username is in `admin_uids` get write access.
+fn persons_from_entries(
+    entries: Vec<SearchEntry>,
+    admin_uids: &HashSet<String>,
+    attributes: &LdapAttributes,
+) -> Result<Vec<Person<NoId>>, LDAPError> {
+    let mut res = Vec::<Person<NoId>>::new();
+    for object in entries.into_iter() {
+        let uids = attribute_values(&object, &attributes.username)
+            .ok_or(LDAPError::AttributeMissing(attributes.username.to_owned()))?;
+        let Some(uid) = uids.iter().next() else {
+            return Err(LDAPError::NotExactlyOneOfAttribute(
+                attributes.username.to_owned(),
+            ));
+        };
+
+        let firstname = first_attribute_value(&object, &attributes.firstname);
+        let surname = first_attribute_value(&object, &attributes.surname);
+        let email = first_attribute_value(&object, &attributes.email);
+        let display_name = first_attribute_value(&object, &attributes.display_name);
+
+        let permission = if admin_uids.contains(uid) {
+            UserPermission::Admin
+        } else {
+            UserPermission::User
+        };
+        res.push(
+            Person::<NoId>::new((), uid.clone(), permission, surname, firstname)
+                .with_contact(email, display_name),
+        );
+    }
+    Ok(res)
 }
 
+/// Number of entries per page when reading all users
+pub(crate) const DEFAULT_PAGE_SIZE: i32 = 500;
+
 #[derive(Clone)]
 pub(crate) struct LDAPBackend {
-    /// String defining the ldaps server to bind against
-    bind_string: String,
+    /// connections bound as the search user
+    pool: LdapPool,
     /// filter to search for users.
     pub(crate) user_filter: String,
-    /// filter to search for users with write access. Contains {username} which will be replaced
+    /// filter to search for users with write access, combined with `user_filter`
     pub(crate) write_access_filter: String,
     /// the base dn under which users lie
     pub(crate) base_dn: String,
-    /// dn and password of the search user
-    bind_dn: String,
-    bind_pw: String,
+    /// which attributes hold the username, names and contact details
+    attributes: LdapAttributes,
+    /// where below `base_dn` users are searched for
+    scope: SearchScope,
+    /// number of entries per page in searches that may return many users
+    page_size: i32,
 }
 impl std::fmt::Debug for LDAPBackend {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         f.debug_struct("LDAPBackend")
-            .field("bind_string", &self.bind_string)
-            .field("bind_dn", &self.bind_dn)
+            .field("pool", &self.pool)
             .field("base_dn", &self.base_dn)
             .field("user_filter", &self.user_filter)
-            .field("bind_pw", &"[redacted]")
+            .field("attributes", &self.attributes)
+            .field("scope", &self.scope)
+            .field("page_size", &self.page_size)
             .finish()
     }
 }
@@ -109,53 +235,66 @@ impl LDAPBackend {
     ) -> Result<Self, LDAPError> {
         let bind_string = format!("ldaps://{hostname}:{port}");
         Ok(LDAPBackend {
-            bind_string,
+            pool: LdapPool::new(
+                bind_string,
+                bind_dn.to_owned(),
+                bind_pw.to_owned(),
+                DEFAULT_POOL_SIZE,
+            ),
             user_filter: user_filter.to_owned(),
             write_access_filter: write_access_filter.to_owned(),
             base_dn: base_dn.to_owned(),
-            bind_dn: bind_dn.to_owned(),
-            bind_pw: bind_pw.to_owned(),
+            attributes: LdapAttributes::default(),
+            scope: SearchScope::default(),
+            page_size: DEFAULT_PAGE_SIZE,
         })
     }
 
-    async fn new_bound_connection(&self) -> Result<Ldap, LDAPError> {
-        let (conn, mut ldap) = LdapConnAsync::new(&self.bind_string)
-            .await
-            .map_err(|e| LDAPError::CannotConnect(e))?;
-        // spawn a task that drives the connection until ldap is dropped
-        ldap3::drive!(conn);
-        // LDAP-bind the handle
-        ldap.simple_bind(&self.bind_dn, &self.bind_pw)
-            .await
-            .map_err(|_| LDAPError::CannotBind)?
-            .success()
-            .map_err(LDAPError::UserError)?;
-        Ok(ldap)
+    /// Read users from these attributes and search for them in this scope
+    pub fn with_mapping(mut self, attributes: LdapAttributes, scope: SearchScope) -> Self {
+        self.attributes = attributes;
+        self.scope = scope;
+        self
+    }
+
+    /// Read large results in pages of this many entries
+    pub fn with_page_size(mut self, page_size: i32) -> Self {
+        self.page_size = page_size;
+        self
     }
 
-    /// Bind, get a user (potentially) and DO NOT UNBIND, returning the (still live and bound)
-    /// connection on success
-    async fn get_user_no_unbind(&self, id: &str) -> Result<(Ldap, Option<User>), LDAPError> {
-        let mut our_handle = self.new_bound_connection().await?;
+    /// Keep up to this many connections for searches
+    pub fn with_pool_size(mut self, pool_size: usize) -> Self {
+        self.pool = self.pool.with_size(pool_size);
+        self
+    }
+
+    /// Search for a user with a pooled connection
+    async fn find_user(&self, id: &str) -> Result<Option<LdapUser>, LDAPError> {
+        let mut our_handle = self.pool.get().await?;
 
+        let username_attribute = &self.attributes.username;
         let complete_filter = format!(
-            "(&({})(uid={}))",
+            "(&({})({}={}))",
             &self.user_filter,
+            username_attribute,
             &escape_ldap_search_filter_parameter(id)
         );
-        let (rs, _res) = our_handle
+        let search = our_handle
             .search(
                 &self.base_dn,
-                Scope::OneLevel,
+                self.scope.into(),
                 &complete_filter,
-                vec!["uid"],
+                vec![username_attribute.as_str()],
             )
             .await
-            .map_err(LDAPError::CannotSearch)?
+            .map_err(LDAPError::CannotSearch);
+        let (rs, _res) = our_handle
+            .check(search)?
             .success()
             .map_err(LDAPError::UserError)?;
         if rs.is_empty() {
-            return Ok((our_handle, None));
+            return Ok(None);
         }
         if rs.len() != 1 {
             info!(
@@ -170,12 +309,12 @@ impl LDAPBackend {
                 .expect("Should have checked that we got a user"),
         );
 
-        let uids = user_obj
-            .attrs
-            .get("uid")
-            .ok_or(LDAPError::AttributeMissing("uid".to_string()))?;
+        let uids = attribute_values(&user_obj, username_attribute)
+            .ok_or(LDAPError::AttributeMissing(username_attribute.to_string()))?;
         let uid = if uids.len() != 1 {
-            return Err(LDAPError::NotExactlyOneOfAttribute("uid".to_string()));
+            return Err(LDAPError::NotExactlyOneOfAttribute(
+                username_attribute.to_string(),
+            ));
         } else {
             uids.iter()
                 .next()
@@ -183,104 +322,175 @@ impl LDAPBackend {
                 .to_string()
         };
 
-        let user = User {
+        let user = LdapUser {
             ldap_dn: user_obj.dn,
             username: uid,
         };
-        Ok((our_handle, Some(user)))
+        Ok(Some(user))
     }
 
-    /// Check if the user with the given uid has write access and return the appropriate
-    /// [`UserPermission`].
-    pub async fn permission<S>(&self, mut handle: Ldap, uid: S) -> Result<UserPermission, LDAPError>
-    where
-        S: AsRef<str>,
-    {
-        let complete_filter = format!(
-            "(&(uid={})(&({})({})))",
-            uid.as_ref(),
-            &self.user_filter,
-            &self.write_access_filter,
-        );
-        let (rs, _res) = handle
-            .search(
-                &self.base_dn,
-                Scope::OneLevel,
-                &complete_filter,
-                vec!["uid"],
-            )
+    /// Search below the base DN, reading the results page by page.
+    ///
+    /// Servers cap the number of results of a single search, so large directories can only be
+    /// read completely with paged results.
+    async fn paged_search(
+        &self,
+        handle: &mut Ldap,
+        filter: &str,
+        attrs: Vec<&str>,
+    ) -> Result<Vec<SearchEntry>, LDAPError> {
+        let adapters: Vec<Box<dyn Adapter<_, _>>> = vec![
+            Box::new(EntriesOnly::new()),
+            Box::new(PagedResults::new(self.page_size)),
+        ];
+        let mut search = handle
+            .streaming_search_with(adapters, &self.base_dn, self.scope.into(), filter, attrs)
+            .await
+            .map_err(LDAPError::CannotSearch)?;
+        let mut res = vec![];
+        while let Some(entry) = search.next().await.map_err(LDAPError::CannotSearch)? {
+            res.push(SearchEntry::construct(entry));
+        }
+        search
+            .finish()
             .await
-            .map_err(LDAPError::CannotSearch)?
             .success()
             .map_err(LDAPError::UserError)?;
-
-        if rs.len() == 1 {
-            Ok(UserPermission::Admin)
-        } else {
-            Ok(UserPermission::User)
-        }
+        Ok(res)
     }
 
     /// Get all users and find whether they are Admins (have write-access) or not.
+    ///
+    /// This takes two searches, no matter how many users there are: one for all users and one for
+    /// those with write access.
     pub async fn get_all_users(&self) -> Result<Vec<Person<NoId>>, LDAPError> {
-        let mut our_handle = self.new_bound_connection().await?;
+        let mut our_handle = self.pool.get().await?;
 
         let complete_filter = format!("({})", &self.user_filter,);
-        let (rs, _res) = our_handle
-            .search(
-                &self.base_dn,
-                Scope::OneLevel,
+        let users = self
+            .paged_search(&mut our_handle, &complete_filter, self.attributes.all())
+            .await;
+        let users = our_handle.check(users)?;
+
+        let admin_filter = format!("(&({})({}))", &self.user_filter, &self.write_access_filter,);
+        let admins = self
+            .paged_search(
+                &mut our_handle,
+                &admin_filter,
+                vec![self.attributes.username.as_str()],
+            )
+            .await;
+        let admins = our_handle
+            .check(admins)?
+            .iter()
+            .filter_map(|entry| first_attribute_value(entry, &self.attributes.username))
+            .collect::<HashSet<_>>();
+
+        persons_from_entries(users, &admins, &self.attributes)
+    }
+
+    /// Get a single user and find whether they are an Admin, like [`Self::get_all_users`] does.
+    pub async fn get_person(&self, id: &str) -> Result<Option<Person<NoId>>, LDAPError> {
+        let mut our_handle = self.pool.get().await?;
+
+        let username_filter = format!(
+            "({}={})",
+            &self.attributes.username,
+            &escape_ldap_search_filter_parameter(id)
+        );
+        let complete_filter = format!("(&({}){})", &self.user_filter, username_filter);
+        let users = self
+            .paged_search(&mut our_handle, &complete_filter, self.attributes.all())
+            .await;
+        let users = our_handle.check(users)?;
+        if users.len() > 1 {
+            return Err(LDAPError::MultipleUsersWithSameUid(id.to_string()));
+        };
+
+        let admin_filter = format!(
+            "(&({})({}){})",
+            &self.user_filter, &self.write_access_filter, username_filter
+        );
+        let admins = self
+            .paged_search(
+                &mut our_handle,
+                &admin_filter,
+                vec![self.attributes.username.as_str()],
+            )
+            .await;
+        let admins = our_handle
+            .check(admins)?
+            .iter()
+            .filter_map(|entry| first_attribute_value(entry, &self.attributes.username))
+            .collect::<HashSet<_>>();
+
+        Ok(persons_from_entries(users, &admins, &self.attributes)?
+            .into_iter()
+            .next())
+    }
+
+    /// Get the uids of the members of these groups.
+    ///
+    /// Only users matching the user filter are returned. Groups that do not exist are missing
+    /// from the result, so that the caller can tell them apart from empty groups.
+    pub async fn get_group_members(
+        &self,
+        group_dns: &[String],
+    ) -> Result<HashMap<String, Vec<String>>, LDAPError> {
+        let mut our_handle = self.pool.get().await?;
+
+        // groupOfNames lists DNs, so we need to know which DN belongs to which user
+        let complete_filter = format!("({})", &self.user_filter,);
+        let users = self
+            .paged_search(
+                &mut our_handle,
                 &complete_filter,
-                vec!["uid", "givenName", "sn"],
+                vec![self.attributes.username.as_str()],
             )
-            .await
-            .map_err(LDAPError::CannotSearch)?
-            .success()
-            .map_err(LDAPError::UserError)?;
-        let mut res = Vec::<Person<NoId>>::new();
-        for entry in rs.into_iter() {
-            let object = SearchEntry::construct(entry);
-            let uids = object
-                .attrs
-                .get("uid")
-                .ok_or(LDAPError::AttributeMissing("uid".to_owned()))?;
-            let Some(uid) = uids.iter().next() else {
-                return Err(LDAPError::NotExactlyOneOfAttribute("uid".to_owned()));
+            .await;
+        let users = our_handle.check(users)?;
+        let mut uid_by_dn = HashMap::new();
+        for object in users.into_iter() {
+            if let Some(uid) = first_attribute_value(&object, &self.attributes.username) {
+                uid_by_dn.insert(object.dn.to_lowercase(), uid);
             };
+        }
 
-            let firstname = object
-                .attrs
-                .get("givenName")
-                .and_then(|v| v.iter().next())
-                .map(|gn| gn.to_owned());
-            let surname = object
-                .attrs
-                .get("sn")
-                .and_then(|v| v.iter().next())
-                .map(|sn| sn.to_owned());
-
-            // check if this user has write access
-            let permission = self.permission(our_handle.clone(), uid).await?;
-            res.push(Person::<NoId>::new(
-                (),
-                uid.clone(),
-                permission,
-                surname,
-                firstname,
-            ));
+        let mut res = HashMap::new();
+        for group_dn in group_dns.iter() {
+            let search = our_handle
+                .search(
+                    group_dn,
+                    Scope::Base,
+                    "(objectClass=*)",
+                    vec!["member", "memberUid"],
+                )
+                .await
+                .map_err(LDAPError::CannotSearch);
+            let SearchResult(rs, result) = our_handle.check(search)?;
+            if result.rc == NO_SUCH_OBJECT {
+                warn!("The LDAP group {group_dn} does not exist.");
+                continue;
+            };
+            let (rs, _res) = SearchResult(rs, result)
+                .success()
+                .map_err(LDAPError::UserError)?;
+            let Some(entry) = rs.into_iter().next() else {
+                warn!("The LDAP group {group_dn} does not exist.");
+                continue;
+            };
+            let object = SearchEntry::construct(entry);
+            res.insert(
+                group_dn.to_owned(),
+                group_member_uids(&object.attrs, &uid_by_dn),
+            );
         }
         Ok(res)
     }
-}
 
-#[async_trait::async_trait]
-impl AuthnBackend for LDAPBackend {
-    type User = User;
-    type Credentials = UserCredentials;
-    type Error = LDAPError;
-    #[tracing::instrument(level=Level::TRACE,skip_all,err)]
-    async fn authenticate(&self, creds: UserCredentials) -> Result<Option<User>, LDAPError> {
-        let (mut handle, user) = self.get_user_no_unbind(&creds.username).await?;
+    /// Check the password of a user by binding as them
+    pub async fn authenticate(&self, creds: &UserCredentials) -> Result<Option<User>, LDAPError> {
+        let user = self.find_user(&creds.username).await?;
         let user = match user {
             Some(x) => x,
             None => {
@@ -293,8 +503,8 @@ impl AuthnBackend for LDAPBackend {
         };
         // we now know that the user exists.
         // try to bind as that user
-        // get a new handle and re-bind
-        // we need to rebind as the search user
+        // this changes who the connection is bound as, so it must not come from the pool
+        let mut handle = self.pool.connect().await?;
         let res = handle
             .simple_bind(&user.ldap_dn, &creds.password)
             // on a connection error, return Err(_)
@@ -302,18 +512,20 @@ impl AuthnBackend for LDAPBackend {
             .map_err(|_| LDAPError::CannotBind)?
             .success()
             // if the password is wrong, return Ok(None), else Ok(Some(the-user))
-            .map_or(Ok(None), |_| Ok(Some(user)))?;
+            .map_or(Ok(None), |_| {
+                Ok(Some(User {
+                    username: user.username,
+                }))
+            })?;
         // unbind to cleanly exit the ldap session
         handle.unbind().await.map_err(|_| LDAPError::CannotUnbind)?;
         Ok(res)
     }
 
-    #[tracing::instrument(level=Level::TRACE,skip_all,err)]
-    async fn get_user(&self, id: &UserId<Self>) -> Result<Option<User>, LDAPError> {
-        let (mut handle, res) = self.get_user_no_unbind(id).await?;
-        // unbind to cleanly exit the ldap session
-        handle.unbind().await.map_err(|_| LDAPError::CannotUnbind)?;
-        Ok(res)
+    pub async fn get_user(&self, id: &str) -> Result<Option<User>, LDAPError> {
+        Ok(self.find_user(id).await?.map(|user| User {
+            username: user.username,
+        }))
     }
 }
 #[derive(Debug)]
@@ -359,41 +571,186 @@ impl std::fmt::Display for LDAPError {
 }
 impl std::error::Error for LDAPError {}
 
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn group_members_by_dn_and_uid() {
+        let uid_by_dn = HashMap::from([
+            (
+                "uid=adam,ou=people,dc=example,dc=com".to_owned(),
+                "adam".to_owned(),
+            ),
+            (
+                "uid=beth,ou=people,dc=example,dc=com".to_owned(),
+                "beth".to_owned(),
+            ),
+            (
+                "uid=eve,ou=people,dc=example,dc=com".to_owned(),
+                "eve".to_owned(),
+            ),
+        ]);
+        let group = HashMap::from([
+            (
+                "member".to_owned(),
+                vec![
+                    "uid=Adam,ou=People,dc=example,dc=com".to_owned(),
+                    "uid=mallory,ou=people,dc=example,dc=com".to_owned(),
+                ],
+            ),
+            (
+                "memberUid".to_owned(),
+                vec!["eve".to_owned(), "adam".to_owned(), "nobody".to_owned()],
+            ),
+        ]);
+        assert_eq!(
+            group_member_uids(&group, &uid_by_dn),
+            vec!["adam".to_owned(), "eve".to_owned()]
+        );
+        assert!(group_member_uids(&HashMap::new(), &uid_by_dn).is_empty());
+    }
+
+    #[test]
+    fn admins_are_taken_from_the_admin_search() {
+        let entry = |uid: &str, sn: Option<&str>| SearchEntry {
+            dn: format!("uid={uid},ou=people,dc=example,dc=com"),
+            attrs: HashMap::from_iter(
+                [("uid".to_owned(), vec![uid.to_owned()])]
+                    .into_iter()
+                    .chain(sn.map(|sn| ("sn".to_owned(), vec![sn.to_owned()]))),
+            ),
+            bin_attrs: HashMap::new(),
+        };
+        let admins = HashSet::from(["beth".to_owned()]);
+        let persons = persons_from_entries(
+            vec![entry("adam", Some("Abramovich")), entry("beth", None)],
+            &admins,
+            &LdapAttributes::default(),
+        )
+        .unwrap();
+        assert_eq!(persons.len(), 2);
+        assert_eq!(persons[0].name, "adam");
+        assert_eq!(persons[0].surname.as_deref(), Some("Abramovich"));
+        assert_eq!(persons[0].global_permission, UserPermission::User);
+        assert_eq!(persons[1].global_permission, UserPermission::Admin);
+
+        let no_uid = SearchEntry {
+            dn: "cn=broken,dc=example,dc=com".to_owned(),
+            attrs: HashMap::new(),
+            bin_attrs: HashMap::new(),
+        };
+        assert!(persons_from_entries(vec![no_uid], &admins, &LdapAttributes::default()).is_err());
+    }
+
+    #[test]
+    fn mapping_defaults_and_overrides() {
+        #[derive(Deserialize)]
+        struct Ldap {
+            #[serde(default)]
+            search_scope: SearchScope,
+            #[serde(default)]
+            attributes: LdapAttributes,
+        }
+        let ldap: Ldap = toml::from_str("").unwrap();
+        assert_eq!(ldap.search_scope, SearchScope::One);
+        assert_eq!(ldap.attributes, LdapAttributes::default());
+
+        let ldap: Ldap =
+            toml::from_str("search_scope = \"sub\"\n[attributes]\nusername = \"sAMAccountName\"")
+                .unwrap();
+        assert_eq!(Scope::from(ldap.search_scope), Scope::Subtree);
+        assert_eq!(ldap.attributes.username, "sAMAccountName");
+        assert_eq!(ldap.attributes.surname, "sn");
+    }
+
+    #[test]
+    fn attribute_names_ignore_case() {
+        let entry = SearchEntry {
+            dn: "cn=Adam,ou=people,dc=example,dc=com".to_owned(),
+            attrs: HashMap::from([
+                ("sAMAccountName".to_owned(), vec!["adam".to_owned()]),
+                ("mail".to_owned(), vec![]),
+            ]),
+            bin_attrs: HashMap::new(),
+        };
+        assert_eq!(
+            first_attribute_value(&entry, "samaccountname"),
+            Some("adam".to_owned())
+        );
+        assert_eq!(first_attribute_value(&entry, "mail"), None);
+        assert_eq!(first_attribute_value(&entry, "displayName"), None);
+    }
+}
+
 /// Note: we assume that testuser is present in the LDAP server here.
 /// The password has to be added as ASTERCONF_TESTUSER_PASSWORD in .env
 ///
 /// If you cannot/do not want this, simply do not run these tests (they are ignored by default)
 #[cfg(test)]
 mod ldap_test {
-    use axum_login::AuthnBackend;
     use dotenv::dotenv;
 
     use super::*;
-    use crate::config::Config;
+    use crate::config::{Config, DEFAULT_CONFIG_PATH};
+
+    /// The backend from the config, which has to use LDAP
+    async fn ldap_backend() -> LDAPBackend {
+        Config::create(std::path::Path::new(DEFAULT_CONFIG_PATH))
+            .await
+            .unwrap()
+            .auth_backend
+            .ldap()
+            .expect("Tests against LDAP need the ldap auth backend")
+            .clone()
+    }
 
     /// Ensure that your config.yaml has the correct credentials for your LDAP databse
     #[tokio::test]
     #[ignore]
     async fn ldap_bind() {
-        let backend = Config::create().await.unwrap().ldap_backend;
-        let mut handle = backend.new_bound_connection().await.unwrap();
-        handle.unbind().await.unwrap();
+        let backend = ldap_backend().await;
+        backend.pool.get().await.unwrap();
+    }
+
+    /// Connections are returned to the pool and used again
+    #[tokio::test]
+    #[ignore]
+    async fn ldap_pool_reuses_connections() {
+        let backend = ldap_backend().await.with_pool_size(1);
+        backend.get_user("testuser").await.unwrap();
+        // with a single connection, this would wait forever if it had not been returned
+        backend.get_user("testuser").await.unwrap();
+        backend.get_all_users().await.unwrap();
     }
 
     #[tokio::test]
     #[ignore]
     async fn ldap_get_user() {
-        let backend = Config::create().await.unwrap().ldap_backend;
-        let res = backend.get_user(&"testuser".to_string()).await.unwrap();
+        let backend = ldap_backend().await;
+        let res = backend.get_user("testuser").await.unwrap();
         res.unwrap();
     }
 
+    #[tokio::test]
+    #[ignore]
+    async fn ldap_get_person() {
+        let backend = ldap_backend().await;
+        let person = backend.get_person("testuser").await.unwrap().unwrap();
+        assert_eq!(person.name, "testuser");
+        assert!(backend
+            .get_person("DOES NOT EXIST EVEN REMOTELY")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
     #[tokio::test]
     #[ignore]
     async fn ldap_get_user_does_not_exist() {
-        let backend = Config::create().await.unwrap().ldap_backend;
+        let backend = ldap_backend().await;
         let res = backend
-            .get_user(&"DOES NOT EXIST EVEN REMOTELY".to_string())
+            .get_user("DOES NOT EXIST EVEN REMOTELY")
             .await
             .unwrap();
         assert!(res.is_none());
@@ -412,10 +769,10 @@ mod ldap_test {
     #[tokio::test]
     #[ignore]
     async fn ldap_authenticate_user() {
-        let backend = Config::create().await.unwrap().ldap_backend;
+        let backend = ldap_backend().await;
         dotenv().ok();
         let res = backend
-            .authenticate(UserCredentials {
+            .authenticate(&UserCredentials {
                 username: "testuser".to_string(),
                 password: std::env::var("PROJEKTTAGEBUCH_TESTUSER_PASSWORD").unwrap(),
             })
@@ -427,9 +784,9 @@ mod ldap_test {
     #[tokio::test]
     #[ignore]
     async fn ldap_authenticate_user_password_wrong() {
-        let backend = Config::create().await.unwrap().ldap_backend;
+        let backend = ldap_backend().await;
         let res = backend
-            .authenticate(UserCredentials {
+            .authenticate(&UserCredentials {
                 username: "testuser".to_string(),
                 password: "THIS IS NOT THE PASSWORD".to_string(),
             })
@@ -441,9 +798,9 @@ mod ldap_test {
     #[tokio::test]
     #[ignore]
     async fn ldap_auth_user_twice() {
-        let backend = Config::create().await.unwrap().ldap_backend;
+        let backend = ldap_backend().await;
         let res = backend
-            .authenticate(UserCredentials {
+            .authenticate(&UserCredentials {
                 username: "testuser".to_string(),
                 password: "THIS IS NOT THE PASSWORD".to_string(),
             })
@@ -451,7 +808,7 @@ mod ldap_test {
             .unwrap();
         assert!(res.is_none());
         let res = backend
-            .authenticate(UserCredentials {
+            .authenticate(&UserCredentials {
                 username: "testuser".to_string(),
                 password: "THIS IS NOT THE PASSWORD".to_string(),
             })
@@ -0,0 +1,187 @@
+//! A bounded pool of LDAP connections bound as the search user
+//!
+//! Opening a connection takes a TLS handshake and a bind, and looking up the user runs on every
+//! authenticated request. Searches therefore borrow an already bound connection from this pool.
+//! Binds with the password of a user never use it, because they change who a connection is bound
+//! as.
+//!
+//! Connections that were idle for a while are checked with a `WhoAmI` before they are handed out.
+//! Broken connections are dropped and replaced by new ones.
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use ldap3::{exop::WhoAmI, Ldap, LdapConnAsync};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+use crate::ldap::LDAPError;
+
+/// Number of connections if not configured otherwise
+pub(crate) const DEFAULT_POOL_SIZE: usize = 4;
+
+/// Connections idle for longer than this are checked before they are used again
+const CHECK_IDLE_AFTER: Duration = Duration::from_secs(30);
+
+/// How long the check may take before the connection is considered broken
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+pub(crate) struct LdapPool {
+    /// String defining the ldaps server to bind against
+    bind_string: String,
+    /// dn and password of the search user
+    bind_dn: String,
+    bind_pw: String,
+    /// bound connections not in use, with the time they were returned
+    idle: Arc<Mutex<Vec<(Ldap, Instant)>>>,
+    /// one permit for every connection that may be in use at the same time
+    permits: Arc<Semaphore>,
+    size: usize,
+}
+impl std::fmt::Debug for LdapPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("LdapPool")
+            .field("bind_string", &self.bind_string)
+            .field("bind_dn", &self.bind_dn)
+            .field("bind_pw", &"[redacted]")
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+impl LdapPool {
+    pub(crate) fn new(bind_string: String, bind_dn: String, bind_pw: String, size: usize) -> Self {
+        Self {
+            bind_string,
+            bind_dn,
+            bind_pw,
+            idle: Arc::new(Mutex::new(vec![])),
+            permits: Arc::new(Semaphore::new(size)),
+            size,
+        }
+    }
+
+    /// The same server and search user, with room for `size` connections
+    pub(crate) fn with_size(self, size: usize) -> Self {
+        Self::new(self.bind_string, self.bind_dn, self.bind_pw, size)
+    }
+
+    /// A new connection that is not bound yet and not part of the pool
+    pub(crate) async fn connect(&self) -> Result<Ldap, LDAPError> {
+        let (conn, ldap) = LdapConnAsync::new(&self.bind_string)
+            .await
+            .map_err(LDAPError::CannotConnect)?;
+        // spawn a task that drives the connection until ldap is dropped
+        ldap3::drive!(conn);
+        Ok(ldap)
+    }
+
+    async fn new_bound_connection(&self) -> Result<Ldap, LDAPError> {
+        let mut ldap = self.connect().await?;
+        // LDAP-bind the handle
+        ldap.simple_bind(&self.bind_dn, &self.bind_pw)
+            .await
+            .map_err(|_| LDAPError::CannotBind)?
+            .success()
+            .map_err(LDAPError::UserError)?;
+        Ok(ldap)
+    }
+
+    /// Whether the server still answers on this connection
+    async fn is_healthy(handle: &mut Ldap) -> bool {
+        if handle.is_closed() {
+            return false;
+        };
+        matches!(
+            handle
+                .with_timeout(CHECK_TIMEOUT)
+                .extended(WhoAmI)
+                .await
+                .map(|res| res.success()),
+            Ok(Ok(_))
+        )
+    }
+
+    /// Borrow a bound connection, waiting if all of them are in use.
+    pub(crate) async fn get(&self) -> Result<PooledConnection, LDAPError> {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("The semaphore of the pool is never closed");
+        loop {
+            let candidate = self.idle.lock().expect("Pool mutex poisoned").pop();
+            let Some((mut handle, returned_at)) = candidate else {
+                break;
+            };
+            if !handle.is_closed() && returned_at.elapsed() < CHECK_IDLE_AFTER
+                || Self::is_healthy(&mut handle).await
+            {
+                return Ok(PooledConnection {
+                    handle: Some(handle),
+                    idle: self.idle.clone(),
+                    _permit: permit,
+                });
+            };
+            debug!("Dropping a broken LDAP connection from the pool.");
+        }
+        let handle = self.new_bound_connection().await?;
+        Ok(PooledConnection {
+            handle: Some(handle),
+            idle: self.idle.clone(),
+            _permit: permit,
+        })
+    }
+}
+
+/// A connection borrowed from the [`LdapPool`]; it is returned when dropped.
+pub(crate) struct PooledConnection {
+    /// None once discarded
+    handle: Option<Ldap>,
+    idle: Arc<Mutex<Vec<(Ldap, Instant)>>>,
+    _permit: OwnedSemaphorePermit,
+}
+impl PooledConnection {
+    /// Pass `res` through, but do not return the connection to the pool if the operation failed
+    /// because of the connection.
+    pub(crate) fn check<T>(&mut self, res: Result<T, LDAPError>) -> Result<T, LDAPError> {
+        if let Err(LDAPError::CannotSearch(_)) = res {
+            debug!("Discarding an LDAP connection after a failed search.");
+            self.handle = None;
+        };
+        res
+    }
+}
+impl Deref for PooledConnection {
+    type Target = Ldap;
+
+    fn deref(&self) -> &Self::Target {
+        self.handle
+            .as_ref()
+            .expect("A discarded connection is not used again")
+    }
+}
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.handle
+            .as_mut()
+            .expect("A discarded connection is not used again")
+    }
+}
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let Some(mut handle) = self.handle.take() {
+            if !handle.is_closed() {
+                self.idle
+                    .lock()
+                    .expect("Pool mutex poisoned")
+                    .push((handle, Instant::now()));
+            };
+        };
+    }
+}
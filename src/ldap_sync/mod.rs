@@ -1,26 +1,39 @@
-//! Sync users from LDAP into the DB
+//! Sync users from the auth backend into the DB
+//!
+//! Every backend lists its users the same way, so the sync does not care where they come from.
+//! Projects may be linked to LDAP groups. After the users are synced, the members of these groups
+//! are added to their projects, and members that left all groups are removed again. Only members
+//! that were added by this sync are ever removed; members added by hand stay.
 
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use tracing::{debug, info, warn};
 
 use crate::{
+    auth::AuthError,
     config::Config,
-    db::{update_users, DBError},
+    db::{
+        add_audit_event, add_synced_members, get_person, get_projects, get_synced_member_names,
+        remove_synced_members, update_users_prepare, DBError,
+    },
     ldap::LDAPError,
+    matrix::MatrixClientError,
+    types::{AuditAction, AuditEvent, FullId, Project, ProjectRole},
     InShutdown,
 };
 
 #[derive(Debug)]
-enum SyncError {
+pub(crate) enum SyncError {
     Db(DBError),
-    Ldap(LDAPError),
+    Auth(AuthError),
+    Matrix(MatrixClientError),
 }
 impl core::fmt::Display for SyncError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Db(x) => write!(f, "Error while updating DB: {x}."),
-            Self::Ldap(x) => write!(f, "Error while reading from LDAP: {x}."),
+            Self::Auth(x) => write!(f, "Error while reading users: {x}."),
+            Self::Matrix(x) => write!(f, "Error while removing users from Matrix: {x}."),
         }
     }
 }
@@ -30,37 +43,251 @@ impl From<DBError> for SyncError {
         Self::Db(value)
     }
 }
+impl From<AuthError> for SyncError {
+    fn from(value: AuthError) -> Self {
+        Self::Auth(value)
+    }
+}
 impl From<LDAPError> for SyncError {
     fn from(value: LDAPError) -> Self {
-        Self::Ldap(value)
+        Self::Auth(AuthError::Ldap(value))
+    }
+}
+impl From<MatrixClientError> for SyncError {
+    fn from(value: MatrixClientError) -> Self {
+        Self::Matrix(value)
     }
 }
 
-/// Fetch users from LDAP and update, once.
+/// Fetch users from the auth backend and update, once.
+///
+/// Users that no longer exist in the backend are deleted from the DB first and then kicked from the
+/// rooms of all their projects. A room we cannot kick from does not keep the others or the next
+/// runs from working; the reconciliation with Matrix kicks them later.
 async fn update_users_in_db(config: Arc<Config>) -> Result<(), SyncError> {
-    // get users from ldap
-    let users = config.ldap_backend.get_all_users().await?;
-    update_users(config.pg_pool.clone(), users).await?;
+    let Some(users) = config.auth_backend.get_all_users().await? else {
+        debug!("Not syncing users, because they are added when they log in.");
+        return Ok(());
+    };
+    let (deleted, tx) = update_users_prepare(config.pg_pool.clone(), users).await?;
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+
+    let mut our_client = config.matrix_client.clone();
+    for (person, projects) in deleted.iter() {
+        for project in projects.iter() {
+            match our_client.ensure_user_not_in_room(person, project).await {
+                Ok(()) => info!(
+                    "Removed {} from {}. They no longer exist in the auth backend.",
+                    person.name, project.name
+                ),
+                Err(e) => warn!(
+                    "Failed to remove {} from the room of {}, although they no longer exist in the auth backend: {e}",
+                    person.name, project.name
+                ),
+            };
+        }
+    }
+    Ok(())
+}
+
+/// The difference between the members of a project and the members of its LDAP groups
+#[derive(Debug, PartialEq, Eq)]
+struct GroupDrift<'a> {
+    /// in one of the groups, but not a member of the project
+    to_add: Vec<&'a str>,
+    /// added by the sync, but no longer in any of the groups
+    to_remove: Vec<&'a str>,
+}
+
+fn group_drift<'a>(
+    members: &[&'a str],
+    synced_members: &'a [String],
+    group_members: &'a [String],
+) -> GroupDrift<'a> {
+    GroupDrift {
+        to_add: group_members
+            .iter()
+            .map(String::as_str)
+            .filter(|name| !members.contains(name))
+            .collect(),
+        to_remove: synced_members
+            .iter()
+            .map(String::as_str)
+            .filter(|name| !group_members.iter().any(|m| m == name))
+            .collect(),
+    }
+}
+
+/// Bring the members of `project` in line with its LDAP groups, once.
+///
+/// `group_members` contains the uids of the members of every group that exists.
+async fn sync_project_groups(
+    config: Arc<Config>,
+    project: &Project<FullId>,
+    group_members: &HashMap<String, Vec<String>>,
+) -> Result<(), SyncError> {
+    let mut wanted = vec![];
+    for group_dn in project.ldap_groups.iter() {
+        match group_members.get(group_dn) {
+            Some(uids) => wanted.extend(uids.iter().cloned()),
+            None => {
+                // better to keep stale members than to remove everyone because of a typo
+                warn!(
+                    "Not syncing the members of {} because its LDAP group {group_dn} does not exist.",
+                    project.name
+                );
+                return Ok(());
+            }
+        };
+    }
+    wanted.sort();
+    wanted.dedup();
+
+    let mut tx = config
+        .pg_pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    let synced = get_synced_member_names(&mut tx, project.db_id()).await?;
+    let members = project
+        .members
+        .iter()
+        .map(|(person, _)| person.name.as_str())
+        .collect::<Vec<_>>();
+    let drift = group_drift(&members, &synced, &wanted);
+    if drift.to_add.is_empty() && drift.to_remove.is_empty() {
+        return Ok(());
+    };
+
+    let mut to_add = vec![];
+    for name in drift.to_add {
+        match get_person(config.pg_pool.clone(), name).await? {
+            Some(person) => to_add.push(person),
+            None => debug!(
+                "{name} is in an LDAP group of {} but not in the DB.",
+                project.name
+            ),
+        };
+    }
+    let to_remove = project
+        .members
+        .iter()
+        .map(|(person, _)| person)
+        .filter(|person| drift.to_remove.contains(&person.name.as_str()))
+        .collect::<Vec<_>>();
+
+    add_synced_members(&mut tx, project.db_id(), &to_add.iter().collect::<Vec<_>>()).await?;
+    remove_synced_members(&mut tx, project.db_id(), &to_remove).await?;
+    for person in to_add.iter() {
+        let event = AuditEvent::by_ldap_sync(AuditAction::AddMember)
+            .with_project(project)
+            .with_target(person)
+            .with_values(None, Some(ProjectRole::Member.to_string()));
+        add_audit_event(&mut tx, &event).await?;
+    }
+    for person in to_remove.iter() {
+        let event = AuditEvent::by_ldap_sync(AuditAction::RemoveMember)
+            .with_project(project)
+            .with_target(person)
+            .with_values(
+                project.local_role_for_user(person).map(|x| x.to_string()),
+                None,
+            );
+        add_audit_event(&mut tx, &event).await?;
+    }
+
+    let mut our_client = config.matrix_client.clone();
+    for person in to_add.iter() {
+        our_client.ensure_user_in_room(person, project).await?;
+    }
+    for person in to_remove.iter() {
+        our_client.ensure_user_not_in_room(person, project).await?;
+    }
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+
+    for person in to_add.iter() {
+        info!(
+            "Added {} to {}. They are in one of its LDAP groups.",
+            person.name, project.name
+        );
+    }
+    for person in to_remove.iter() {
+        info!(
+            "Removed {} from {}. They are no longer in any of its LDAP groups.",
+            person.name, project.name
+        );
+    }
+    Ok(())
+}
+
+/// Sync the members of LDAP groups into their projects, once.
+///
+/// Projects without groups are visited as well, to remove members of groups that were unlinked.
+/// Archived projects are left as they are. Nothing happens if users do not come from LDAP.
+async fn update_group_members(config: Arc<Config>) -> Result<(), SyncError> {
+    let Some(ldap_backend) = config.auth_backend.ldap() else {
+        debug!("Not syncing LDAP groups, because users do not come from LDAP.");
+        return Ok(());
+    };
+    let projects = get_projects(config.pg_pool.clone(), false).await?;
+    let mut group_dns = projects
+        .iter()
+        .flat_map(|p| p.ldap_groups.iter().cloned())
+        .collect::<Vec<_>>();
+    group_dns.sort();
+    group_dns.dedup();
+    let group_members = if group_dns.is_empty() {
+        HashMap::new()
+    } else {
+        ldap_backend.get_group_members(&group_dns).await?
+    };
+
+    for project in projects.iter() {
+        // one failing project should not keep us from syncing the others
+        if let Err(e) = sync_project_groups(config.clone(), project, &group_members).await {
+            warn!(
+                "Failed to sync the LDAP groups of project {} ({}): {e}",
+                project.name,
+                project.db_id()
+            );
+        };
+    }
     Ok(())
 }
 
+/// Sync users and LDAP groups once, e.g. when asked to on the command line
+pub(crate) async fn sync_once(config: Arc<Config>) -> Result<(), SyncError> {
+    update_users_in_db(config.clone()).await?;
+    update_group_members(config).await
+}
+
 pub async fn continuous_sync(
     config: Arc<Config>,
     mut watcher: tokio::sync::watch::Receiver<InShutdown>,
 ) {
-    info!("Starting LDAP -> DB Sync task.");
+    info!("Starting users -> DB Sync task.");
     let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
         config.user_resync_interval as u64 * 60,
     ));
     interval.tick().await;
     loop {
-        debug!("LDAP->DB Sync starting new run.");
+        debug!("Users->DB Sync starting new run.");
         // get new data
         let sync_res = update_users_in_db(config.clone()).await;
         match sync_res {
             Ok(()) => debug!("Successfully updated db."),
             Err(e) => {
-                warn!("Failed to update db from LDAP. Error encountered: {e}");
+                warn!("Failed to update db from the auth backend. Error encountered: {e}");
+            }
+        };
+        match update_group_members(config.clone()).await {
+            Ok(()) => debug!("Successfully synced LDAP groups into projects."),
+            Err(e) => {
+                warn!("Failed to sync LDAP groups into projects. Error encountered: {e}");
             }
         };
 
@@ -74,3 +301,31 @@ pub async fn continuous_sync(
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn group_drift_keeps_manual_members() {
+        let synced = vec!["carl".to_owned(), "dora".to_owned()];
+        let group = vec!["adam".to_owned(), "carl".to_owned(), "eve".to_owned()];
+        // beth was added by hand and is in no group
+        let drift = group_drift(&["adam", "beth", "carl", "dora"], &synced, &group);
+        assert_eq!(
+            drift,
+            GroupDrift {
+                to_add: vec!["eve"],
+                to_remove: vec!["dora"],
+            }
+        );
+    }
+
+    #[test]
+    fn unlinked_groups_remove_synced_members() {
+        let synced = vec!["carl".to_owned()];
+        let drift = group_drift(&["beth", "carl"], &synced, &[]);
+        assert!(drift.to_add.is_empty());
+        assert_eq!(drift.to_remove, vec!["carl"]);
+    }
+}
@@ -1,15 +1,27 @@
 use std::{str::FromStr, sync::Arc};
 
-use config::Config;
+use cli::Command;
+use config::{Config, DbConfig};
 use tracing::{debug, error, info};
-use tracing_subscriber::{filter, fmt::format::FmtSpan, prelude::*, EnvFilter};
+use tracing_subscriber::{
+    filter,
+    fmt::{format::FmtSpan, writer::BoxMakeWriter},
+    prelude::*,
+    EnvFilter,
+};
 
 mod actions;
+mod auth;
+mod cli;
 mod config;
 mod db;
 mod ldap;
+mod ldap_pool;
 mod ldap_sync;
 mod matrix;
+mod matrix_sync;
+mod permissions;
+mod render;
 mod types;
 mod web_server;
 
@@ -82,33 +94,8 @@ async fn signal_handler(
     Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("installing crypto provider");
-    rustls::crypto::ring::default_provider()
-        .install_default()
-        .expect("Failed to install rustls crypto provider");
-
-    let config = Arc::new(Config::create().await?);
-    println!("got config");
-
-    let my_crate_filter = EnvFilter::new("projekttagebuch");
-
-    let level_filter = filter::LevelFilter::from_str(&config.log_level)?;
-
-    let subscriber = tracing_subscriber::registry().with(my_crate_filter).with(
-        tracing_subscriber::fmt::layer()
-            .compact()
-            .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
-            .with_line_number(true)
-            .with_filter(level_filter),
-    );
-    if let Err(e) = tracing::subscriber::set_global_default(subscriber) {
-        eprintln!("Error setting global tracing subscriber: {e}");
-        Err(e)?;
-    };
-    debug!("Successfully instantiated tracing.");
-
+/// Run the web server, the user sync and the room reconciliation until we are asked to stop
+async fn serve(config: Arc<Config>) -> Result<(), Box<dyn std::error::Error>> {
     sqlx::migrate!().run(&config.pg_pool).await?;
     debug!("Successfully migrated db.");
 
@@ -116,12 +103,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let (tx, rx) = tokio::sync::watch::channel(InShutdown::No);
 
     let sync_handle = tokio::spawn(ldap_sync::continuous_sync(config.clone(), rx));
+    let reconcile_handle = tokio::spawn(matrix_sync::continuous_reconciliation(
+        config.clone(),
+        tx.subscribe(),
+    ));
 
     // start the Signal handler
     let signal_handle = tokio::spawn(signal_handler(tx.subscribe(), tx.clone()));
 
     // start the web server
-    let webserver = web_server::Webserver::new().await?;
+    let webserver = web_server::Webserver::new(&config).await?;
     let web_watcher = tx.subscribe();
     let web_handle = tokio::spawn(async move {
         if let Err(e) = webserver.run_web_server(config, web_watcher).await {
@@ -130,10 +121,97 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         };
     });
 
-    let (signal_res, sync_res, web_res) = tokio::join!(signal_handle, sync_handle, web_handle);
+    let (signal_res, sync_res, reconcile_res, web_res) =
+        tokio::join!(signal_handle, sync_handle, reconcile_handle, web_handle);
     signal_res??;
     sync_res?;
+    reconcile_res?;
     web_res?;
 
     Ok(())
 }
+
+/// Run only the user sync until we are asked to stop
+async fn sync_users(config: Arc<Config>) -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = tokio::sync::watch::channel(InShutdown::No);
+    let sync_handle = tokio::spawn(ldap_sync::continuous_sync(config, rx));
+    let signal_handle = tokio::spawn(signal_handler(tx.subscribe(), tx.clone()));
+    let (signal_res, sync_res) = tokio::join!(signal_handle, sync_handle);
+    signal_res??;
+    sync_res?;
+    Ok(())
+}
+
+/// Log at `log_level` to stdout, or to stderr to keep stdout free for the output of commands, so
+/// that scripts can read it
+fn init_tracing(log_level: &str, to_stdout: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let my_crate_filter = EnvFilter::new("projekttagebuch");
+
+    let level_filter = filter::LevelFilter::from_str(log_level)?;
+
+    let writer = if to_stdout {
+        BoxMakeWriter::new(std::io::stdout)
+    } else {
+        BoxMakeWriter::new(std::io::stderr)
+    };
+    let subscriber = tracing_subscriber::registry().with(my_crate_filter).with(
+        tracing_subscriber::fmt::layer()
+            .compact()
+            .with_writer(writer)
+            .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
+            .with_line_number(true)
+            .with_filter(level_filter),
+    );
+    if let Err(e) = tracing::subscriber::set_global_default(subscriber) {
+        eprintln!("Error setting global tracing subscriber: {e}");
+        Err(e)?;
+    };
+    debug!("Successfully instantiated tracing.");
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = std::env::args().collect::<Vec<_>>();
+    let cli = match cli::parse(&args, std::env::var(config::CONFIG_PATH_ENV).ok()) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("{e}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if cli.command == Command::Help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    };
+
+    eprintln!("installing crypto provider");
+    rustls::crypto::ring::default_provider()
+        .install_default()
+        .expect("Failed to install rustls crypto provider");
+
+    // migrating only needs the DB, so it works before Matrix or the auth backend are reachable
+    if cli.command == Command::Migrate {
+        let config = DbConfig::create(&cli.config_path).await?;
+        init_tracing(&config.log_level, false)?;
+        sqlx::migrate!().run(&config.pg_pool).await?;
+        println!("The DB schema is up to date.");
+        return Ok(());
+    };
+
+    let config = Arc::new(Config::create(&cli.config_path).await?);
+    eprintln!("got config");
+    init_tracing(&config.log_level, cli.command == Command::Serve)?;
+
+    match cli.command {
+        Command::Serve => serve(config).await,
+        Command::SyncUsers { once: false } => sync_users(config).await,
+        command => {
+            if let Err(e) = cli::run(config, cli.acting_as.as_deref(), command).await {
+                eprintln!("{e}");
+                std::process::exit(1);
+            };
+            Ok(())
+        }
+    }
+}
@@ -1,11 +1,14 @@
 //! Code for communicating with matrix
 
-use matrix_sdk::ruma::{OwnedRoomId, RoomId, UserId};
-use matrix_sdk::{config::SyncSettings, Client};
+use matrix_sdk::ruma::events::room::{
+    power_levels::RoomPowerLevelsEventContent, tombstone::RoomTombstoneEventContent,
+};
+use matrix_sdk::ruma::{int, Int, OwnedRoomId, RoomId, UserId};
+use matrix_sdk::{config::SyncSettings, Client, RoomMemberships};
 use tracing::warn;
 
 use crate::types::Project;
-use crate::types::{DbNoMatrix, FullId, MatrixNoDb, NoId, Person};
+use crate::types::{DbNoMatrix, FullId, MatrixNoDb, NoId, Person, ProjectRole};
 
 #[derive(Debug)]
 pub enum MatrixClientError {
@@ -16,8 +19,14 @@ pub enum MatrixClientError {
     RoomDoesNotExist(OwnedRoomId),
     CannotParseUserId(matrix_sdk::IdParseError),
     CannotAddUser(matrix_sdk::Error),
+    CannotKickUser(matrix_sdk::Error),
     CannotCheckMembershipStatus(matrix_sdk::Error),
     CannotSetRoomName(matrix_sdk::Error),
+    CannotSetRoomTopic(matrix_sdk::Error),
+    CannotSetPowerLevels(matrix_sdk::Error),
+    CannotGetMembers(matrix_sdk::Error),
+    CannotTombstoneRoom(matrix_sdk::Error),
+    CannotLeaveRoom(matrix_sdk::Error),
     UserIsBanned,
     StateUnknown,
 }
@@ -45,12 +54,30 @@ impl core::fmt::Display for MatrixClientError {
             Self::CannotAddUser(e) => {
                 write!(f, "Unable to add a user to a room: {e}")
             }
+            Self::CannotKickUser(e) => {
+                write!(f, "Unable to kick a user from a room: {e}")
+            }
             Self::CannotCheckMembershipStatus(e) => {
                 write!(f, "Unable to check membership status for a user: {e}")
             }
             Self::CannotSetRoomName(e) => {
                 write!(f, "Unable to set a rooms name: {e}")
             }
+            Self::CannotSetRoomTopic(e) => {
+                write!(f, "Unable to set a rooms topic: {e}")
+            }
+            Self::CannotSetPowerLevels(e) => {
+                write!(f, "Unable to set a rooms power levels: {e}")
+            }
+            Self::CannotGetMembers(e) => {
+                write!(f, "Unable to get the members of a room: {e}")
+            }
+            Self::CannotTombstoneRoom(e) => {
+                write!(f, "Unable to tombstone a room: {e}")
+            }
+            Self::CannotLeaveRoom(e) => {
+                write!(f, "Unable to leave a room: {e}")
+            }
             Self::UserIsBanned => {
                 write!(
                     f,
@@ -68,6 +95,15 @@ impl core::fmt::Display for MatrixClientError {
 }
 impl std::error::Error for MatrixClientError {}
 
+/// The state of a projects room as far as we care about it
+#[derive(Debug)]
+pub(crate) struct RoomState {
+    /// The rooms name, if it has one
+    pub(crate) name: Option<String>,
+    /// Names (localparts) of the users on our server that are joined or invited, excluding us
+    pub(crate) members: Vec<String>,
+}
+
 /// The Matrix client used in this application to make requests to Matrix.
 ///
 /// Note: [`Client`] is just a wrapper for Arc, so it is fine to clone this [`MatrixClient`]
@@ -217,6 +253,17 @@ impl MatrixClient {
         &mut self,
         person: &Person<DbNoMatrix>,
         project: &Project<FullId>,
+    ) -> Result<(), MatrixClientError> {
+        self.ensure_name_not_in_room(&person.name, project).await
+    }
+
+    /// Ensure that the user called `name` on our server is not in the room for `project` in matrix
+    ///
+    /// Unlike [`Self::ensure_user_not_in_room`] this works for users we do not know in the DB.
+    pub async fn ensure_name_not_in_room(
+        &mut self,
+        name: &str,
+        project: &Project<FullId>,
     ) -> Result<(), MatrixClientError> {
         self.do_sync().await?;
 
@@ -227,7 +274,7 @@ impl MatrixClient {
             .get_room(&room_id)
             .ok_or_else(|| MatrixClientError::RoomDoesNotExist(room_id.clone()))?;
         // remove the old member
-        let user_id = UserId::parse(format!("@{}:{}", person.name, self.servername))
+        let user_id = UserId::parse(format!("@{}:{}", name, self.servername))
             .map_err(MatrixClientError::CannotParseUserId)?;
         // check that we only remove users that are actually in the room
         let already_in_room = room
@@ -268,7 +315,7 @@ impl MatrixClient {
                 );
             }
             Err(e) => {
-                return Err(MatrixClientError::CannotAddUser(e));
+                return Err(MatrixClientError::CannotKickUser(e));
             }
         };
 
@@ -290,4 +337,248 @@ impl MatrixClient {
             .ok_or_else(|| MatrixClientError::RoomDoesNotExist(room_id.clone()))?;
         room.set_name(new_name).await.map(|_| ()).map_err(MatrixClientError::CannotSetRoomName)
     }
+
+    pub async fn set_project_topic(
+        &mut self,
+        project: &Project<FullId>,
+        new_topic: &str,
+    ) -> Result<(), MatrixClientError> {
+        self.do_sync().await?;
+        let room_id =
+            RoomId::parse(project.matrix_id()).map_err(MatrixClientError::CannotParseRoomId)?;
+        let room = self
+            .client
+            .get_room(&room_id)
+            .ok_or_else(|| MatrixClientError::RoomDoesNotExist(room_id.clone()))?;
+        room.set_room_topic(new_topic)
+            .await
+            .map(|_| ())
+            .map_err(MatrixClientError::CannotSetRoomTopic)
+    }
+
+    /// Get the name and the members of the room for this project
+    pub async fn room_state(
+        &mut self,
+        project: &Project<FullId>,
+    ) -> Result<RoomState, MatrixClientError> {
+        self.do_sync().await?;
+        let room_id =
+            RoomId::parse(project.matrix_id()).map_err(MatrixClientError::CannotParseRoomId)?;
+        let room = self
+            .client
+            .get_room(&room_id)
+            .ok_or_else(|| MatrixClientError::RoomDoesNotExist(room_id.clone()))?;
+
+        let members = room
+            .members(RoomMemberships::JOIN | RoomMemberships::INVITE)
+            .await
+            .map_err(MatrixClientError::CannotGetMembers)?
+            .into_iter()
+            .filter(|m| m.user_id() != room.own_user_id())
+            .filter(|m| m.user_id().server_name().as_str() == self.servername)
+            .map(|m| m.user_id().localpart().to_owned())
+            .collect();
+        Ok(RoomState {
+            name: room.name(),
+            members,
+        })
+    }
+
+    /// Set the power level of `person` in the room for `project` to the one of `role`
+    pub async fn set_power_level(
+        &mut self,
+        person: &Person<DbNoMatrix>,
+        project: &Project<FullId>,
+        role: ProjectRole,
+    ) -> Result<(), MatrixClientError> {
+        self.do_sync().await?;
+        let room_id =
+            RoomId::parse(project.matrix_id()).map_err(MatrixClientError::CannotParseRoomId)?;
+        let room = self
+            .client
+            .get_room(&room_id)
+            .ok_or_else(|| MatrixClientError::RoomDoesNotExist(room_id.clone()))?;
+        let user_id = UserId::parse(format!("@{}:{}", person.name, self.servername))
+            .map_err(MatrixClientError::CannotParseUserId)?;
+
+        room.update_power_levels(vec![(&user_id, Int::new_saturating(role.power_level()))])
+            .await
+            .map_err(MatrixClientError::CannotSetPowerLevels)?;
+        tracing::info!(
+            "Set the power level of {} in room {} ({}) to {}.",
+            user_id,
+            project.name,
+            room_id,
+            role.power_level()
+        );
+        Ok(())
+    }
+
+    /// Reset the power level of `person` in the room for `project` to the default of the room
+    ///
+    /// Only sends an update if `person` has a power level of their own.
+    pub async fn reset_power_level(
+        &mut self,
+        person: &Person<DbNoMatrix>,
+        project: &Project<FullId>,
+    ) -> Result<(), MatrixClientError> {
+        self.do_sync().await?;
+        let room_id =
+            RoomId::parse(project.matrix_id()).map_err(MatrixClientError::CannotParseRoomId)?;
+        let room = self
+            .client
+            .get_room(&room_id)
+            .ok_or_else(|| MatrixClientError::RoomDoesNotExist(room_id.clone()))?;
+        let user_id = UserId::parse(format!("@{}:{}", person.name, self.servername))
+            .map_err(MatrixClientError::CannotParseUserId)?;
+
+        let power_levels = room
+            .power_levels()
+            .await
+            .map_err(|e| MatrixClientError::CannotSetPowerLevels(e.into()))?;
+        if !power_levels.users.contains_key(&user_id) {
+            return Ok(());
+        };
+        // the default removes the user from the list of power levels
+        room.update_power_levels(vec![(&user_id, power_levels.users_default)])
+            .await
+            .map_err(MatrixClientError::CannotSetPowerLevels)?;
+        tracing::info!(
+            "Reset the power level of {} in room {} ({}).",
+            user_id,
+            project.name,
+            room_id
+        );
+        Ok(())
+    }
+
+    /// Make sure that every member of `project` has the power level of their role in its room
+    ///
+    /// Only sends an update if a level differs. Returns the names of the members whose level was
+    /// changed.
+    pub async fn ensure_power_levels(
+        &mut self,
+        project: &Project<FullId>,
+    ) -> Result<Vec<String>, MatrixClientError> {
+        self.do_sync().await?;
+        let room_id =
+            RoomId::parse(project.matrix_id()).map_err(MatrixClientError::CannotParseRoomId)?;
+        let room = self
+            .client
+            .get_room(&room_id)
+            .ok_or_else(|| MatrixClientError::RoomDoesNotExist(room_id.clone()))?;
+
+        let power_levels = room
+            .power_levels()
+            .await
+            .map_err(|e| MatrixClientError::CannotSetPowerLevels(e.into()))?;
+        let members = project
+            .members
+            .iter()
+            .map(|(person, role)| {
+                UserId::parse(format!("@{}:{}", person.name, self.servername))
+                    .map(|user_id| (person, Int::new_saturating(role.power_level()), user_id))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(MatrixClientError::CannotParseUserId)?;
+        let mut updates = vec![];
+        let mut changed = vec![];
+        for (person, level, user_id) in members.iter() {
+            if power_levels.for_user(user_id) != *level {
+                updates.push((user_id.as_ref(), *level));
+                changed.push(person.name.clone());
+            };
+        }
+        if !updates.is_empty() {
+            room.update_power_levels(updates)
+                .await
+                .map_err(MatrixClientError::CannotSetPowerLevels)?;
+        };
+        Ok(changed)
+    }
+
+    /// Make the matrix room for this project read-only
+    ///
+    /// Only users with power level 100 (i.e. us) may still send events or invite users afterwards.
+    pub async fn set_room_read_only(
+        &mut self,
+        project: &Project<FullId>,
+    ) -> Result<(), MatrixClientError> {
+        self.do_sync().await?;
+        let room_id =
+            RoomId::parse(project.matrix_id()).map_err(MatrixClientError::CannotParseRoomId)?;
+        let room = self
+            .client
+            .get_room(&room_id)
+            .ok_or_else(|| MatrixClientError::RoomDoesNotExist(room_id.clone()))?;
+
+        let mut power_levels = room
+            .power_levels()
+            .await
+            .map_err(|e| MatrixClientError::CannotSetPowerLevels(e.into()))?;
+        power_levels.events_default = int!(100);
+        power_levels.state_default = int!(100);
+        power_levels.invite = int!(100);
+        // explicit overrides (e.g. for m.room.message) would still let users write
+        power_levels.events.clear();
+        room.send_state_event(RoomPowerLevelsEventContent::from(power_levels))
+            .await
+            .map_err(MatrixClientError::CannotSetPowerLevels)?;
+        tracing::info!("Made Matrix-Room {} ({}) read-only.", project.name, room_id);
+        Ok(())
+    }
+
+    /// Close the matrix room for this project for good
+    ///
+    /// Kicks everyone that is joined or invited, tombstones the room and leaves it.
+    pub async fn tombstone_room(
+        &mut self,
+        project: &Project<FullId>,
+    ) -> Result<(), MatrixClientError> {
+        self.do_sync().await?;
+        let room_id =
+            RoomId::parse(project.matrix_id()).map_err(MatrixClientError::CannotParseRoomId)?;
+        let room = self
+            .client
+            .get_room(&room_id)
+            .ok_or_else(|| MatrixClientError::RoomDoesNotExist(room_id.clone()))?;
+
+        let members = room
+            .members(RoomMemberships::JOIN | RoomMemberships::INVITE)
+            .await
+            .map_err(MatrixClientError::CannotGetMembers)?;
+        for member in members {
+            if member.user_id() == room.own_user_id() {
+                continue;
+            };
+            room.kick_user(member.user_id(), Some("projekttagebuch Automatisierung"))
+                .await
+                .map_err(MatrixClientError::CannotKickUser)?;
+            tracing::info!(
+                "Kicked {} from Matrix-Room {} ({})",
+                member.user_id(),
+                project.name,
+                room_id
+            );
+        }
+
+        // The spec requires a replacement room, but there is none.
+        // Pointing to the room itself makes clients show the tombstone without offering a
+        // successor.
+        room.send_state_event(RoomTombstoneEventContent::new(
+            "Dieses Projekt wurde gelöscht.".to_owned(),
+            room_id.clone(),
+        ))
+        .await
+        .map_err(MatrixClientError::CannotTombstoneRoom)?;
+        room.leave()
+            .await
+            .map_err(MatrixClientError::CannotLeaveRoom)?;
+        tracing::info!(
+            "Tombstoned and left Matrix-Room {} ({}).",
+            project.name,
+            room_id
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,197 @@
+//! Reconcile project memberships, roles and room names between the DB and Matrix
+//!
+//! The DB is the source of truth. Adding or removing members touches both systems, and a crash
+//! between the two leaves them out of sync. Users may also leave a room by hand. This task
+//! periodically compares every active project with its room and repairs the difference. The
+//! power level of every member is set to the one of their [`crate::types::ProjectRole`].
+
+use std::sync::Arc;
+
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::Config,
+    db::{get_projects, DBError},
+    matrix::{MatrixClientError, RoomState},
+    types::{FullId, Project},
+    InShutdown,
+};
+
+#[derive(Debug)]
+enum ReconcileError {
+    Db(DBError),
+    Matrix(MatrixClientError),
+}
+impl core::fmt::Display for ReconcileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Db(x) => write!(f, "Error while reading from DB: {x}."),
+            Self::Matrix(x) => write!(f, "Error while communicating with Matrix: {x}."),
+        }
+    }
+}
+impl std::error::Error for ReconcileError {}
+impl From<DBError> for ReconcileError {
+    fn from(value: DBError) -> Self {
+        Self::Db(value)
+    }
+}
+impl From<MatrixClientError> for ReconcileError {
+    fn from(value: MatrixClientError) -> Self {
+        Self::Matrix(value)
+    }
+}
+
+/// The difference between the members of a project in the DB and in its room
+#[derive(Debug, PartialEq, Eq)]
+struct MembershipDrift<'a> {
+    /// in the DB, but not in the room
+    missing_in_room: Vec<&'a str>,
+    /// in the room, but not in the DB
+    missing_in_db: Vec<&'a str>,
+}
+
+fn membership_drift<'a>(db_members: &[&'a str], room_members: &'a [String]) -> MembershipDrift<'a> {
+    MembershipDrift {
+        missing_in_room: db_members
+            .iter()
+            .filter(|name| !room_members.iter().any(|m| m == *name))
+            .copied()
+            .collect(),
+        missing_in_db: room_members
+            .iter()
+            .map(String::as_str)
+            .filter(|name| !db_members.contains(name))
+            .collect(),
+    }
+}
+
+/// Bring the room of `project` in line with the DB, once.
+async fn reconcile_project(
+    config: Arc<Config>,
+    project: &Project<FullId>,
+) -> Result<(), ReconcileError> {
+    let mut our_client = config.matrix_client.clone();
+    let RoomState { name, members } = our_client.room_state(project).await?;
+
+    if name.as_deref() != Some(project.name.as_str()) {
+        our_client
+            .set_project_name(project, project.name.clone())
+            .await?;
+        info!(
+            "Reconciliation: renamed room {} from {:?} to {}.",
+            project.matrix_id(),
+            name,
+            project.name
+        );
+    };
+
+    let db_members = project
+        .members
+        .iter()
+        .map(|(person, _)| person.name.as_str())
+        .collect::<Vec<_>>();
+    let drift = membership_drift(&db_members, &members);
+    for name in drift.missing_in_room {
+        // the person is a member in the DB, so we know them
+        let person = project
+            .members
+            .iter()
+            .find(|(person, _)| person.name == name)
+            .map(|(person, _)| person)
+            .expect("name was taken from the project members");
+        our_client.ensure_user_in_room(person, project).await?;
+        info!(
+            "Reconciliation: {} is a member of {} but was not in its room. Invited them again.",
+            name, project.name
+        );
+    }
+    for name in drift.missing_in_db {
+        our_client.ensure_name_not_in_room(name, project).await?;
+        info!(
+            "Reconciliation: {} was in the room of {} but is not a member. Kicked them.",
+            name, project.name
+        );
+    }
+    for name in our_client.ensure_power_levels(project).await? {
+        info!(
+            "Reconciliation: the power level of {} in {} did not match their role. Set it again.",
+            name, project.name
+        );
+    }
+    Ok(())
+}
+
+/// Reconcile all active projects, once.
+///
+/// Archived rooms are read-only and left as they are.
+async fn reconcile_all_projects(config: Arc<Config>) -> Result<(), ReconcileError> {
+    let projects = get_projects(config.pg_pool.clone(), false).await?;
+    for project in projects.iter() {
+        // one broken room should not keep us from fixing the others
+        if let Err(e) = reconcile_project(config.clone(), project).await {
+            warn!(
+                "Failed to reconcile project {} ({}): {e}",
+                project.name,
+                project.db_id()
+            );
+        };
+    }
+    Ok(())
+}
+
+pub async fn continuous_reconciliation(
+    config: Arc<Config>,
+    mut watcher: tokio::sync::watch::Receiver<InShutdown>,
+) {
+    info!("Starting DB -> Matrix reconciliation task.");
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+        config.room_reconcile_interval as u64 * 60,
+    ));
+    interval.tick().await;
+    loop {
+        // stop on cancellation or continue after the next tick
+        // We wait first: right after startup, the LDAP sync may not have run yet.
+        tokio::select! {
+            _ = watcher.changed() => {
+                debug!("Shutting down reconciliation now.");
+                return;
+            }
+            _ = interval.tick() => {}
+        }
+
+        debug!("DB->Matrix reconciliation starting new run.");
+        match reconcile_all_projects(config.clone()).await {
+            Ok(()) => debug!("Successfully reconciled all projects."),
+            Err(e) => {
+                warn!("Failed to reconcile projects. Error encountered: {e}");
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn drift_in_both_directions() {
+        let room = vec!["adam".to_owned(), "eve".to_owned()];
+        let drift = membership_drift(&["adam", "beth"], &room);
+        assert_eq!(
+            drift,
+            MembershipDrift {
+                missing_in_room: vec!["beth"],
+                missing_in_db: vec!["eve"],
+            }
+        );
+    }
+
+    #[test]
+    fn no_drift() {
+        let room = vec!["beth".to_owned(), "adam".to_owned()];
+        let drift = membership_drift(&["adam", "beth"], &room);
+        assert!(drift.missing_in_room.is_empty());
+        assert!(drift.missing_in_db.is_empty());
+    }
+}
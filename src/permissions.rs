@@ -0,0 +1,256 @@
+//! Who may do what with a project
+//!
+//! Routes and actions ask [`project_access`] instead of looking at memberships themselves.
+
+use crate::types::{
+    DbNoMatrix, Entry, FullId, Person, Project, ProjectRole, ProjectVisibility, UserPermission,
+};
+
+/// What a person may do with a project, from least to most
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ProjectAccess {
+    /// The project does not exist as far as this person is concerned
+    Hidden,
+    /// Only the name is shown
+    NameOnly,
+    /// Members, metadata and diary entries can be read
+    Read,
+    /// May also write the diary
+    Member,
+    /// May also change the project and its members
+    Maintainer,
+    /// May also delete the project and name other owners
+    Owner,
+}
+impl ProjectAccess {
+    pub(crate) fn may_see(&self) -> bool {
+        *self >= Self::NameOnly
+    }
+
+    pub(crate) fn may_read(&self) -> bool {
+        *self >= Self::Read
+    }
+
+    pub(crate) fn may_write(&self) -> bool {
+        *self >= Self::Member
+    }
+
+    pub(crate) fn may_administer(&self) -> bool {
+        *self >= Self::Maintainer
+    }
+
+    /// The permission the project templates are rendered with
+    pub(crate) fn view_permission(&self) -> UserPermission {
+        UserPermission::new_from_is_admin(self.may_administer())
+    }
+
+    /// The permission the diary templates are rendered with
+    pub(crate) fn entry_permission(&self) -> UserPermission {
+        UserPermission::new_from_is_admin(self.may_write())
+    }
+}
+
+/// Creating projects and the global history are reserved for global admins
+pub(crate) fn may_administer_instance(person: &Person<DbNoMatrix>) -> bool {
+    person.is_global_admin()
+}
+
+/// Whether someone with `access` may change a members role from `old` to `new`
+///
+/// `None` means not being a member. Only owners may name or remove other owners.
+pub(crate) fn may_change_role(
+    access: ProjectAccess,
+    old: Option<ProjectRole>,
+    new: Option<ProjectRole>,
+) -> bool {
+    if !access.may_administer() {
+        return false;
+    };
+    let touches_owner = old == Some(ProjectRole::Owner) || new == Some(ProjectRole::Owner);
+    !touches_owner || access >= ProjectAccess::Owner
+}
+
+/// Whether `person`, who has `access` to the project of `entry`, may edit or delete it
+///
+/// Members may only change their own entries, maintainers all of them.
+pub(crate) fn may_change_entry(
+    person: &Person<DbNoMatrix>,
+    access: ProjectAccess,
+    entry: &Entry<DbNoMatrix>,
+) -> bool {
+    if access.may_administer() {
+        return true;
+    };
+    access.may_write()
+        && entry
+            .author
+            .as_ref()
+            .is_some_and(|author| author.db_id() == person.db_id())
+}
+
+/// Why [`require_access`] failed
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum AccessDenied {
+    /// The person may not know that the project exists; report it as missing
+    Hidden,
+    /// The person knows the project, but may not do this
+    Insufficient,
+}
+
+/// Make sure `person` has at least `required` access to `project`
+pub(crate) fn require_access(
+    person: &Person<DbNoMatrix>,
+    project: &Project<FullId>,
+    required: ProjectAccess,
+) -> Result<ProjectAccess, AccessDenied> {
+    let access = project_access(person, project);
+    if access >= required {
+        Ok(access)
+    } else if !access.may_see() {
+        Err(AccessDenied::Hidden)
+    } else {
+        Err(AccessDenied::Insufficient)
+    }
+}
+
+/// The access `person` has to `project`
+pub(crate) fn project_access(
+    person: &Person<DbNoMatrix>,
+    project: &Project<FullId>,
+) -> ProjectAccess {
+    if person.is_global_admin() {
+        return ProjectAccess::Owner;
+    };
+    match project.local_role_for_user(person) {
+        Some(ProjectRole::Owner) => ProjectAccess::Owner,
+        Some(ProjectRole::Maintainer) => ProjectAccess::Maintainer,
+        Some(ProjectRole::Member) => ProjectAccess::Member,
+        Some(ProjectRole::Viewer) => ProjectAccess::Read,
+        None => match project.visibility {
+            ProjectVisibility::Public => ProjectAccess::Read,
+            ProjectVisibility::Internal => ProjectAccess::NameOnly,
+            ProjectVisibility::Secret => ProjectAccess::Hidden,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::NoId;
+
+    fn person(id: i32, global_permission: UserPermission) -> Person<DbNoMatrix> {
+        Person::new(id, format!("person{id}"), global_permission, None, None)
+    }
+
+    #[test]
+    fn access_by_membership_and_visibility() {
+        let admin = person(1, UserPermission::User);
+        let member = person(2, UserPermission::User);
+        let outsider = person(3, UserPermission::User);
+        let global_admin = person(4, UserPermission::Admin);
+        let viewer = person(5, UserPermission::User);
+        let mut project = Project::<FullId>::new(("room".to_owned(), 1), "Basil".to_owned());
+        project.add_member(admin.clone(), ProjectRole::Maintainer);
+        project.add_member(member.clone(), ProjectRole::Member);
+        project.add_member(viewer.clone(), ProjectRole::Viewer);
+
+        for (visibility, outsider_access) in [
+            (ProjectVisibility::Public, ProjectAccess::Read),
+            (ProjectVisibility::Internal, ProjectAccess::NameOnly),
+            (ProjectVisibility::Secret, ProjectAccess::Hidden),
+        ] {
+            project.visibility = visibility;
+            assert_eq!(project_access(&admin, &project), ProjectAccess::Maintainer);
+            assert_eq!(project_access(&member, &project), ProjectAccess::Member);
+            assert_eq!(project_access(&viewer, &project), ProjectAccess::Read);
+            assert_eq!(project_access(&outsider, &project), outsider_access);
+            assert_eq!(
+                project_access(&global_admin, &project),
+                ProjectAccess::Owner
+            );
+        }
+        assert!(!ProjectAccess::Hidden.may_see());
+        assert!(ProjectAccess::NameOnly.may_see());
+        assert!(!ProjectAccess::NameOnly.may_read());
+        assert!(ProjectAccess::Member.may_read());
+        assert!(!ProjectAccess::Read.may_write());
+        assert!(ProjectAccess::Member.may_write());
+        assert!(!ProjectAccess::Member.may_administer());
+        assert!(ProjectAccess::Maintainer.may_administer());
+    }
+
+    #[test]
+    fn only_owners_touch_owners() {
+        use ProjectRole::*;
+        assert!(may_change_role(
+            ProjectAccess::Maintainer,
+            Some(Member),
+            Some(Maintainer)
+        ));
+        assert!(may_change_role(
+            ProjectAccess::Maintainer,
+            Some(Viewer),
+            None
+        ));
+        assert!(!may_change_role(
+            ProjectAccess::Maintainer,
+            Some(Maintainer),
+            Some(Owner)
+        ));
+        assert!(!may_change_role(
+            ProjectAccess::Maintainer,
+            Some(Owner),
+            None
+        ));
+        assert!(!may_change_role(
+            ProjectAccess::Member,
+            Some(Viewer),
+            Some(Member)
+        ));
+        assert!(may_change_role(
+            ProjectAccess::Owner,
+            Some(Owner),
+            Some(Viewer)
+        ));
+    }
+
+    #[test]
+    fn members_only_change_their_own_entries() {
+        let author = person(1, UserPermission::User);
+        let other = person(2, UserPermission::User);
+        let project = Project::<FullId>::new(("room".to_owned(), 1), "Basil".to_owned());
+        let entry = Entry::<NoId>::new(&project, author.clone(), "t".to_owned(), "b".to_owned())
+            .set_db_id(1);
+        assert!(may_change_entry(&author, ProjectAccess::Member, &entry));
+        assert!(!may_change_entry(&other, ProjectAccess::Member, &entry));
+        assert!(may_change_entry(&other, ProjectAccess::Maintainer, &entry));
+        // authors that lost write access cannot change their entries anymore
+        assert!(!may_change_entry(&author, ProjectAccess::Read, &entry));
+
+        let mut orphaned = entry.clone();
+        orphaned.author = None;
+        assert!(!may_change_entry(&author, ProjectAccess::Member, &orphaned));
+        assert!(may_change_entry(&other, ProjectAccess::Owner, &orphaned));
+    }
+
+    #[test]
+    fn hidden_projects_are_denied_as_hidden() {
+        let outsider = person(3, UserPermission::User);
+        let mut project = Project::<FullId>::new(("room".to_owned(), 1), "Basil".to_owned());
+        project.visibility = ProjectVisibility::Secret;
+        assert_eq!(
+            require_access(&outsider, &project, ProjectAccess::Read),
+            Err(AccessDenied::Hidden)
+        );
+        project.visibility = ProjectVisibility::Internal;
+        assert_eq!(
+            require_access(&outsider, &project, ProjectAccess::Read),
+            Err(AccessDenied::Insufficient)
+        );
+        assert_eq!(
+            require_access(&outsider, &project, ProjectAccess::NameOnly),
+            Ok(ProjectAccess::NameOnly)
+        );
+    }
+}
@@ -0,0 +1,427 @@
+//! Output layer: render the Protokoll of a project into an archivable PDF/A-3 document.
+//!
+//! The human-readable entries are typeset onto A4 pages. The same data is embedded into the PDF
+//! as `protokoll.json`, so the document stays machine-readable after it has been archived.
+
+use std::sync::Arc;
+
+use krilla::{
+    configure::{Configuration, Validator},
+    embed::{AssociationKind, EmbeddedFile},
+    geom::Point,
+    metadata::{DateTime, Metadata},
+    page::PageSettings,
+    paint::Fill,
+    text::{Font, TextDirection},
+    Document, SerializeSettings,
+};
+use serde::Serialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::types::{DbNoMatrix, Entry, FullId, Project};
+
+static FONT_REGULAR: &[u8] = include_bytes!("../templates/static/fonts/DejaVuSansMono.ttf");
+static FONT_BOLD: &[u8] = include_bytes!("../templates/static/fonts/DejaVuSansMono-Bold.ttf");
+
+/// A4 in pt
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+/// Space reserved for the footer at the bottom of each page
+const FOOTER_HEIGHT: f32 = 24.0;
+
+const TEXT_SIZE: f32 = 10.0;
+const HEADING_SIZE: f32 = 16.0;
+const SUBHEADING_SIZE: f32 = 12.0;
+const LINE_SPACING: f32 = 1.4;
+/// Horizontal advance of every glyph in DejaVu Sans Mono, relative to the font size
+const GLYPH_ADVANCE: f32 = 0.602;
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum RenderError {
+    CannotLoadFont,
+    CannotSerializeSourceData(serde_json::Error),
+    CannotEmbedSourceData,
+    /// krilla errors do not implement Display, so we keep their debug representation
+    CannotCreatePdf(String),
+}
+impl core::fmt::Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CannotLoadFont => write!(f, "Cannot load the embedded font."),
+            Self::CannotSerializeSourceData(e) => {
+                write!(f, "Cannot serialize the source data of the Protokoll: {e}")
+            }
+            Self::CannotEmbedSourceData => {
+                write!(f, "Cannot embed the source data into the PDF.")
+            }
+            Self::CannotCreatePdf(e) => write!(f, "Cannot create the PDF document: {e}"),
+        }
+    }
+}
+impl std::error::Error for RenderError {}
+
+/// The data embedded into the PDF as `protokoll.json`
+#[derive(Serialize)]
+struct SourceData<'a> {
+    rendered_at: String,
+    project_id: i32,
+    project_name: &'a str,
+    members: Vec<SourceMember<'a>>,
+    entries: Vec<SourceEntry<'a>>,
+}
+
+#[derive(Serialize)]
+struct SourceMember<'a> {
+    name: &'a str,
+    firstname: Option<&'a str>,
+    surname: Option<&'a str>,
+    permission: String,
+}
+
+#[derive(Serialize)]
+struct SourceEntry<'a> {
+    entry_id: i32,
+    timestamp: String,
+    /// None if the author has since been removed
+    author: Option<&'a str>,
+    title: &'a str,
+    body: &'a str,
+}
+
+impl<'a> SourceData<'a> {
+    fn new(
+        project: &'a Project<FullId>,
+        entries: &'a [Entry<DbNoMatrix>],
+        rendered_at: OffsetDateTime,
+    ) -> Self {
+        Self {
+            rendered_at: format_rfc3339(rendered_at),
+            project_id: project.db_id(),
+            project_name: &project.name,
+            members: project
+                .members
+                .iter()
+                .map(|(person, permission)| SourceMember {
+                    name: &person.name,
+                    firstname: person.firstname.as_deref(),
+                    surname: person.surname.as_deref(),
+                    permission: permission.to_string(),
+                })
+                .collect(),
+            entries: entries
+                .iter()
+                .map(|entry| SourceEntry {
+                    entry_id: entry.db_id(),
+                    timestamp: format_rfc3339(entry.timestamp),
+                    author: entry.author.as_ref().map(|a| a.name.as_str()),
+                    title: &entry.title,
+                    body: &entry.body,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn format_rfc3339(timestamp: OffsetDateTime) -> String {
+    timestamp
+        .format(&Rfc3339)
+        .expect("all OffsetDateTimes in the DB have a four digit year")
+}
+
+/// A single line of text, already placed on a page
+struct Line {
+    text: String,
+    bold: bool,
+    size: f32,
+    /// baseline of this line, measured from the top of the page
+    y: f32,
+}
+
+/// Lays out lines top to bottom and starts new pages when necessary
+struct Layout {
+    pages: Vec<Vec<Line>>,
+    /// the current baseline
+    y: f32,
+}
+impl Layout {
+    fn new() -> Self {
+        Self {
+            pages: vec![vec![]],
+            y: MARGIN,
+        }
+    }
+
+    /// Add some vertical space
+    fn skip(&mut self, amount: f32) {
+        self.y += amount;
+    }
+
+    /// Add a paragraph, wrapping it to the width of the page
+    fn paragraph(&mut self, text: &str, size: f32, bold: bool) {
+        let chars_per_line = ((PAGE_WIDTH - 2.0 * MARGIN) / (size * GLYPH_ADVANCE)) as usize;
+        for source_line in text.lines() {
+            for line in wrap(&sanitize(source_line), chars_per_line) {
+                self.line(line, size, bold);
+            }
+        }
+    }
+
+    fn line(&mut self, text: String, size: f32, bold: bool) {
+        let height = size * LINE_SPACING;
+        if self.y + height > PAGE_HEIGHT - MARGIN - FOOTER_HEIGHT {
+            self.pages.push(vec![]);
+            self.y = MARGIN;
+        }
+        self.y += height;
+        self.pages
+            .last_mut()
+            .expect("there is always at least one page")
+            .push(Line {
+                text,
+                bold,
+                size,
+                y: self.y,
+            });
+    }
+}
+
+/// Replace characters that DejaVu Sans Mono cannot show.
+///
+/// PDF/A forbids the .notdef glyph, so anything outside the (generously) supported ranges is
+/// shown as `?`. The exact text is still contained in the embedded source data.
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\t' => ' ',
+            // ASCII, Latin-1, Latin Extended-A
+            ' '..='~' | '\u{a0}'..='\u{17f}' => c,
+            // Greek and Cyrillic
+            '\u{391}'..='\u{3a1}' | '\u{3a3}'..='\u{3c9}' | '\u{400}'..='\u{45f}' => c,
+            // dashes, quotes, ellipsis, per mille, euro
+            '\u{2010}'..='\u{2022}' | '\u{2026}' | '\u{2030}'..='\u{2037}' => c,
+            '\u{2039}' | '\u{203a}' | '\u{20ac}' => c,
+            _ => '?',
+        })
+        .collect()
+}
+
+/// Break `text` into lines of at most `width` chars, preferably at spaces.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut current = String::new();
+    let mut current_len = 0;
+    for word in text.split(' ') {
+        let mut word: Vec<char> = word.chars().collect();
+        // words that do not fit on a line at all are hard-broken
+        while word.len() > width {
+            if current_len > 0 {
+                lines.push(std::mem::take(&mut current));
+                current_len = 0;
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        if current_len > 0 && current_len + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        if current_len > 0 {
+            current.push(' ');
+            current_len += 1;
+        }
+        current.extend(word.iter());
+        current_len += word.len();
+    }
+    lines.push(current);
+    lines
+}
+
+fn load_font(bytes: &'static [u8]) -> Result<Font, RenderError> {
+    let data: Arc<dyn AsRef<[u8]> + Send + Sync> = Arc::new(bytes);
+    Font::new(data.into(), 0).ok_or(RenderError::CannotLoadFont)
+}
+
+fn pdf_date(timestamp: OffsetDateTime) -> DateTime {
+    DateTime::new(timestamp.year() as u16)
+        .month(timestamp.month() as u8)
+        .day(timestamp.day())
+        .hour(timestamp.hour())
+        .minute(timestamp.minute())
+        .second(timestamp.second())
+        .utc_offset_hour(0)
+        .utc_offset_minute(0)
+}
+
+/// Render the Protokoll of `project` as PDF/A-3b.
+///
+/// `entries` are typeset in the order given. Project, members and entries are embedded as JSON.
+pub(crate) fn render_protokoll(
+    project: &Project<FullId>,
+    entries: &[Entry<DbNoMatrix>],
+) -> Result<Vec<u8>, RenderError> {
+    let now = OffsetDateTime::now_utc();
+    let regular = load_font(FONT_REGULAR)?;
+    let bold = load_font(FONT_BOLD)?;
+
+    // layout
+    let mut layout = Layout::new();
+    layout.paragraph("Projekttagebuch – Protokoll", SUBHEADING_SIZE, false);
+    layout.paragraph(&project.name, HEADING_SIZE, true);
+    layout.paragraph(
+        &format!(
+            "Stand: {}",
+            now.format(time::macros::format_description!(
+                "[day].[month].[year] [hour]:[minute] UTC"
+            ))
+            .expect("static format description")
+        ),
+        TEXT_SIZE,
+        false,
+    );
+    layout.skip(TEXT_SIZE);
+    layout.paragraph("Mitglieder", SUBHEADING_SIZE, true);
+    for (person, permission) in &project.members {
+        let full_name = match (&person.firstname, &person.surname) {
+            (Some(f), Some(s)) => format!(" ({f} {s})"),
+            (Some(x), None) | (None, Some(x)) => format!(" ({x})"),
+            (None, None) => String::new(),
+        };
+        layout.paragraph(
+            &format!("- {}{full_name}, {permission}", person.name),
+            TEXT_SIZE,
+            false,
+        );
+    }
+    layout.skip(TEXT_SIZE);
+    layout.paragraph("Einträge", SUBHEADING_SIZE, true);
+    if entries.is_empty() {
+        layout.paragraph("Noch keine Einträge.", TEXT_SIZE, false);
+    }
+    for entry in entries {
+        layout.skip(TEXT_SIZE / 2.0);
+        layout.paragraph(&entry.title, TEXT_SIZE, true);
+        let author = entry
+            .author
+            .as_ref()
+            .map(|a| a.name.as_str())
+            .unwrap_or("gelöschter Nutzer");
+        layout.paragraph(
+            &format!("{} – {author}", entry.timestamp_display()),
+            TEXT_SIZE,
+            false,
+        );
+        layout.paragraph(&entry.body, TEXT_SIZE, false);
+    }
+
+    // typesetting
+    let mut document = Document::new_with(SerializeSettings {
+        configuration: Configuration::new_with_validator(Validator::A3_B),
+        ..Default::default()
+    });
+    // the creation date is also used as the date of the embedded file, so set it first
+    document.set_metadata(
+        Metadata::new()
+            .title(format!("Protokoll {}", project.name))
+            .language("de".to_owned())
+            .creator("projekttagebuch".to_owned())
+            .creation_date(pdf_date(now)),
+    );
+    let page_count = layout.pages.len();
+    for (page_idx, lines) in layout.pages.into_iter().enumerate() {
+        let mut page = document.start_page_with(PageSettings::new(PAGE_WIDTH, PAGE_HEIGHT));
+        let mut surface = page.surface();
+        surface.set_fill(Some(Fill::default()));
+        for line in lines {
+            let font = if line.bold { &bold } else { &regular };
+            surface.draw_text(
+                Point {
+                    x: MARGIN,
+                    y: line.y,
+                },
+                font.clone(),
+                line.size,
+                &line.text,
+                false,
+                TextDirection::Auto,
+            );
+        }
+        let footer = sanitize(&format!(
+            "{} – Seite {}/{page_count}",
+            project.name,
+            page_idx + 1
+        ));
+        surface.draw_text(
+            Point {
+                x: MARGIN,
+                y: PAGE_HEIGHT - MARGIN,
+            },
+            regular.clone(),
+            TEXT_SIZE * 0.8,
+            &footer,
+            false,
+            TextDirection::Auto,
+        );
+        surface.finish();
+        page.finish();
+    }
+
+    // source data
+    let source = serde_json::to_vec_pretty(&SourceData::new(project, entries, now))
+        .map_err(RenderError::CannotSerializeSourceData)?;
+    document
+        .embed_file(EmbeddedFile {
+            path: "protokoll.json".to_owned(),
+            mime_type: Some("application/json".to_owned()),
+            description: Some("Quelldaten dieses Protokolls".to_owned()),
+            association_kind: AssociationKind::Source,
+            data: source.into(),
+            compress: true,
+            location: None,
+        })
+        .ok_or(RenderError::CannotEmbedSourceData)?;
+
+    document
+        .finish()
+        .map_err(|e| RenderError::CannotCreatePdf(format!("{e:?}")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wrap_breaks_at_spaces() {
+        assert_eq!(wrap("aaa bbb ccc", 7), vec!["aaa bbb", "ccc"]);
+        assert_eq!(wrap("aaaaaaaaaa b", 4), vec!["aaaa", "aaaa", "aa b"]);
+        assert_eq!(wrap("", 4), vec![""]);
+    }
+
+    #[test]
+    fn sanitize_replaces_unsupported_chars() {
+        assert_eq!(sanitize("Größe\t– 10 €"), "Größe – 10 €");
+        assert_eq!(sanitize("🦀"), "?");
+    }
+
+    #[test]
+    fn render_protokoll_is_pdf() {
+        let project = Project::new(
+            ("!room:matrix.org".to_owned(), 1),
+            "test-project".to_owned(),
+        );
+        let pdf = render_protokoll(&project, &[]).expect("rendering should work");
+        assert!(pdf.starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn all_allowed_chars_have_glyphs() {
+        // PDF/A validation fails if any of these is missing from the font
+        let all: String = (0x20u32..0x2100)
+            .filter_map(char::from_u32)
+            .filter(|c| sanitize(&c.to_string()) != "?")
+            .collect();
+        let project = Project::new(("!room:matrix.org".to_owned(), 1), "p".to_owned());
+        let entry = Entry::from_db(1, 1, None, OffsetDateTime::now_utc(), all.clone(), all);
+        render_protokoll(&project, &[entry]).unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+//! The [`ApiToken`] type used throughout
+//!
+//! An API token lets scripts act as the person that created it, without an LDAP session.
+//! Only a hash of the secret is ever stored; the secret itself is shown once on creation.
+
+use askama::Template;
+use sha2::{Digest, Sha256};
+use time::{macros::format_description, OffsetDateTime};
+use uuid::Uuid;
+
+use super::{DbNoMatrix, IdState, NoId, Person};
+
+/// Every secret starts with this, so that leaked tokens are easy to recognize
+const SECRET_PREFIX: &str = "ptb_";
+
+/// These are the possible states an API tokens ID can be in
+pub(crate) trait ApiTokenIdState: IdState {}
+impl ApiTokenIdState for NoId {}
+impl ApiTokenIdState for DbNoMatrix {}
+
+#[derive(askama::Template)]
+#[template(path = "token/show.html")]
+struct ApiTokenDisplay<'a> {
+    token: &'a ApiToken<DbNoMatrix>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ApiToken<I: ApiTokenIdState> {
+    api_token_id: I,
+    /// The person this token acts as
+    person_id: i32,
+    pub(crate) label: String,
+    pub(crate) created_at: OffsetDateTime,
+    /// None if the token never expires
+    pub(crate) expires_at: Option<OffsetDateTime>,
+    /// None if the token was never used
+    pub(crate) last_used_at: Option<OffsetDateTime>,
+}
+
+fn format_timestamp(timestamp: &OffsetDateTime) -> String {
+    timestamp
+        .format(format_description!(
+            "[day].[month].[year] [hour]:[minute] UTC"
+        ))
+        .expect("static format description")
+}
+
+impl<I> ApiToken<I>
+where
+    I: ApiTokenIdState,
+{
+    pub fn db_id(&self) -> I::DbId {
+        *self.api_token_id.db_id()
+    }
+
+    pub fn person_id(&self) -> i32 {
+        self.person_id
+    }
+
+    pub fn created_at_display(&self) -> String {
+        format_timestamp(&self.created_at)
+    }
+
+    pub fn expires_at_display(&self) -> String {
+        match &self.expires_at {
+            Some(x) => format_timestamp(x),
+            None => "nie".to_owned(),
+        }
+    }
+
+    pub fn last_used_at_display(&self) -> String {
+        match &self.last_used_at {
+            Some(x) => format_timestamp(x),
+            None => "nie".to_owned(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|x| x <= OffsetDateTime::now_utc())
+    }
+}
+
+impl ApiToken<NoId> {
+    /// Create a new token for `person`.
+    ///
+    /// Returns the token and its secret. The secret cannot be recovered later.
+    pub fn generate(
+        person: &Person<DbNoMatrix>,
+        label: String,
+        expires_at: Option<OffsetDateTime>,
+    ) -> (Self, String) {
+        // two v4 UUIDs give us 244 random bits
+        let secret = format!(
+            "{SECRET_PREFIX}{}{}",
+            Uuid::new_v4().simple(),
+            Uuid::new_v4().simple()
+        );
+        (
+            Self {
+                api_token_id: NoId::default(),
+                person_id: person.db_id(),
+                label,
+                created_at: OffsetDateTime::now_utc(),
+                expires_at,
+                last_used_at: None,
+            },
+            secret,
+        )
+    }
+
+    pub(crate) fn set_db_id<I: Into<DbNoMatrix>>(self, id: I) -> ApiToken<DbNoMatrix> {
+        ApiToken {
+            api_token_id: id.into(),
+            person_id: self.person_id,
+            label: self.label,
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+            last_used_at: self.last_used_at,
+        }
+    }
+}
+
+impl ApiToken<DbNoMatrix> {
+    pub fn from_db<IdInto>(
+        api_token_id: IdInto,
+        person_id: i32,
+        label: String,
+        created_at: OffsetDateTime,
+        expires_at: Option<OffsetDateTime>,
+        last_used_at: Option<OffsetDateTime>,
+    ) -> Self
+    where
+        IdInto: Into<DbNoMatrix>,
+    {
+        Self {
+            api_token_id: api_token_id.into(),
+            person_id,
+            label,
+            created_at,
+            expires_at,
+            last_used_at,
+        }
+    }
+
+    /// template this token
+    pub fn display(&self) -> String {
+        ApiTokenDisplay { token: self }
+            .render()
+            .expect("static template")
+    }
+}
+
+/// The hash of a secret, as stored in the DB
+///
+/// The secrets are long and random, so a plain SHA-256 is enough here.
+pub(crate) fn hash_api_token_secret(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
@@ -0,0 +1,299 @@
+//! The [`AuditEvent`] type used throughout
+//!
+//! Every state-changing action writes one of these in the same transaction as the change itself.
+//! Names are copied into the event, so that the history stays readable after persons or projects
+//! are gone.
+
+use std::net::IpAddr;
+
+use askama::Template;
+use time::{macros::format_description, OffsetDateTime};
+
+use super::{DbNoMatrix, FullId, IdState, NoId, Person, Project};
+
+/// Shown as the actor of changes made by the LDAP group sync
+const LDAP_SYNC_ACTOR: &str = "LDAP-Abgleich";
+
+/// These are the possible states an audit events ID can be in
+pub(crate) trait AuditEventIdState: IdState {}
+impl AuditEventIdState for NoId {}
+impl AuditEventIdState for DbNoMatrix {}
+
+/// The kinds of actions that are recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AuditAction {
+    CreateProject,
+    RenameProject,
+    EditProjectMetadata,
+    SetProjectVisibility,
+    ArchiveProject,
+    DeleteProject,
+    AddMember,
+    RemoveMember,
+    SetMemberPermission,
+    LinkLdapGroup,
+    UnlinkLdapGroup,
+    AddEntry,
+    EditEntry,
+    DeleteEntry,
+    CreateApiToken,
+    RevokeApiToken,
+    /// Someone tried to log in with a wrong password
+    LoginFailed,
+}
+impl AuditAction {
+    /// The representation stored in the DB
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::CreateProject => "create_project",
+            Self::RenameProject => "rename_project",
+            Self::EditProjectMetadata => "edit_project_metadata",
+            Self::SetProjectVisibility => "set_project_visibility",
+            Self::ArchiveProject => "archive_project",
+            Self::DeleteProject => "delete_project",
+            Self::AddMember => "add_member",
+            Self::RemoveMember => "remove_member",
+            Self::SetMemberPermission => "set_member_permission",
+            Self::LinkLdapGroup => "link_ldap_group",
+            Self::UnlinkLdapGroup => "unlink_ldap_group",
+            Self::AddEntry => "add_entry",
+            Self::EditEntry => "edit_entry",
+            Self::DeleteEntry => "delete_entry",
+            Self::CreateApiToken => "create_api_token",
+            Self::RevokeApiToken => "revoke_api_token",
+            Self::LoginFailed => "login_failed",
+        }
+    }
+}
+impl TryFrom<&str> for AuditAction {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "create_project" => Self::CreateProject,
+            "rename_project" => Self::RenameProject,
+            "edit_project_metadata" => Self::EditProjectMetadata,
+            "set_project_visibility" => Self::SetProjectVisibility,
+            "archive_project" => Self::ArchiveProject,
+            "delete_project" => Self::DeleteProject,
+            "add_member" => Self::AddMember,
+            "remove_member" => Self::RemoveMember,
+            "set_member_permission" => Self::SetMemberPermission,
+            "link_ldap_group" => Self::LinkLdapGroup,
+            "unlink_ldap_group" => Self::UnlinkLdapGroup,
+            "add_entry" => Self::AddEntry,
+            "edit_entry" => Self::EditEntry,
+            "delete_entry" => Self::DeleteEntry,
+            "create_api_token" => Self::CreateApiToken,
+            "revoke_api_token" => Self::RevokeApiToken,
+            "login_failed" => Self::LoginFailed,
+            x => return Err(x.to_owned()),
+        })
+    }
+}
+/// The text shown in the UI
+impl core::fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CreateProject => write!(f, "Projekt erstellt"),
+            Self::RenameProject => write!(f, "Projekt umbenannt"),
+            Self::EditProjectMetadata => write!(f, "Projektdaten geändert"),
+            Self::SetProjectVisibility => write!(f, "Sichtbarkeit geändert"),
+            Self::ArchiveProject => write!(f, "Projekt archiviert"),
+            Self::DeleteProject => write!(f, "Projekt gelöscht"),
+            Self::AddMember => write!(f, "Mitglied hinzugefügt"),
+            Self::RemoveMember => write!(f, "Mitglied entfernt"),
+            Self::SetMemberPermission => write!(f, "Berechtigung geändert"),
+            Self::LinkLdapGroup => write!(f, "LDAP-Gruppe verknüpft"),
+            Self::UnlinkLdapGroup => write!(f, "LDAP-Gruppe entfernt"),
+            Self::AddEntry => write!(f, "Eintrag erstellt"),
+            Self::EditEntry => write!(f, "Eintrag bearbeitet"),
+            Self::DeleteEntry => write!(f, "Eintrag gelöscht"),
+            Self::CreateApiToken => write!(f, "API-Token erstellt"),
+            Self::RevokeApiToken => write!(f, "API-Token widerrufen"),
+            Self::LoginFailed => write!(f, "Anmeldung fehlgeschlagen"),
+        }
+    }
+}
+
+#[derive(askama::Template)]
+#[template(path = "audit/show.html")]
+struct AuditEventDisplay<'a> {
+    event: &'a AuditEvent<DbNoMatrix>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AuditEvent<I: AuditEventIdState> {
+    audit_event_id: I,
+    pub(crate) created_at: OffsetDateTime,
+    pub(crate) actor_name: String,
+    pub(crate) action: AuditAction,
+    /// None if the action does not concern a project
+    pub(crate) project_id: Option<i32>,
+    pub(crate) project_name: Option<String>,
+    /// None if the action does not concern another person
+    pub(crate) target_name: Option<String>,
+    pub(crate) old_value: Option<String>,
+    pub(crate) new_value: Option<String>,
+}
+
+impl<I> AuditEvent<I>
+where
+    I: AuditEventIdState,
+{
+    pub fn db_id(&self) -> I::DbId {
+        *self.audit_event_id.db_id()
+    }
+
+    /// What the new value is called in the UI
+    pub fn new_value_label(&self) -> &'static str {
+        match self.action {
+            AuditAction::LoginFailed => "von",
+            _ => "nachher",
+        }
+    }
+
+    pub fn created_at_display(&self) -> String {
+        self.created_at
+            .format(format_description!(
+                "[day].[month].[year] [hour]:[minute] UTC"
+            ))
+            .expect("static format description")
+    }
+}
+
+impl AuditEvent<NoId> {
+    /// A new event for an action `actor` is doing right now
+    pub fn new(actor: &Person<DbNoMatrix>, action: AuditAction) -> Self {
+        Self {
+            audit_event_id: NoId::default(),
+            created_at: OffsetDateTime::now_utc(),
+            actor_name: actor.name.clone(),
+            action,
+            project_id: None,
+            project_name: None,
+            target_name: None,
+            old_value: None,
+            new_value: None,
+        }
+    }
+
+    /// A new event for an action the LDAP sync is doing right now
+    pub fn by_ldap_sync(action: AuditAction) -> Self {
+        Self {
+            audit_event_id: NoId::default(),
+            created_at: OffsetDateTime::now_utc(),
+            actor_name: LDAP_SYNC_ACTOR.to_owned(),
+            action,
+            project_id: None,
+            project_name: None,
+            target_name: None,
+            old_value: None,
+            new_value: None,
+        }
+    }
+
+    /// A failed login as `username` from `address`. Nobody is logged in, so the actor is the
+    /// name that was tried.
+    pub fn failed_login(username: &str, address: IpAddr) -> Self {
+        Self {
+            audit_event_id: NoId::default(),
+            created_at: OffsetDateTime::now_utc(),
+            actor_name: username.to_owned(),
+            action: AuditAction::LoginFailed,
+            project_id: None,
+            project_name: None,
+            target_name: None,
+            old_value: None,
+            new_value: Some(address.to_string()),
+        }
+    }
+
+    /// The project this action concerns
+    pub fn with_project(mut self, project: &Project<FullId>) -> Self {
+        self.project_id = Some(project.db_id());
+        self.project_name = Some(project.name.clone());
+        self
+    }
+
+    /// The person this action concerns
+    pub fn with_target(mut self, target: &Person<DbNoMatrix>) -> Self {
+        self.target_name = Some(target.name.clone());
+        self
+    }
+
+    /// The value before and after the action
+    pub fn with_values(mut self, old_value: Option<String>, new_value: Option<String>) -> Self {
+        self.old_value = old_value;
+        self.new_value = new_value;
+        self
+    }
+}
+
+impl AuditEvent<DbNoMatrix> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_db<IdInto>(
+        audit_event_id: IdInto,
+        created_at: OffsetDateTime,
+        actor_name: String,
+        action: AuditAction,
+        project_id: Option<i32>,
+        project_name: Option<String>,
+        target_name: Option<String>,
+        old_value: Option<String>,
+        new_value: Option<String>,
+    ) -> Self
+    where
+        IdInto: Into<DbNoMatrix>,
+    {
+        Self {
+            audit_event_id: audit_event_id.into(),
+            created_at,
+            actor_name,
+            action,
+            project_id,
+            project_name,
+            target_name,
+            old_value,
+            new_value,
+        }
+    }
+
+    /// template this event
+    pub fn display(&self) -> String {
+        AuditEventDisplay { event: self }
+            .render()
+            .expect("static template")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn action_kinds_roundtrip() {
+        for action in [
+            AuditAction::CreateProject,
+            AuditAction::RenameProject,
+            AuditAction::EditProjectMetadata,
+            AuditAction::SetProjectVisibility,
+            AuditAction::ArchiveProject,
+            AuditAction::DeleteProject,
+            AuditAction::AddMember,
+            AuditAction::RemoveMember,
+            AuditAction::SetMemberPermission,
+            AuditAction::LinkLdapGroup,
+            AuditAction::UnlinkLdapGroup,
+            AuditAction::AddEntry,
+            AuditAction::EditEntry,
+            AuditAction::DeleteEntry,
+            AuditAction::CreateApiToken,
+            AuditAction::RevokeApiToken,
+            AuditAction::LoginFailed,
+        ] {
+            assert_eq!(AuditAction::try_from(action.as_str()), Ok(action));
+        }
+        assert!(AuditAction::try_from("something_else").is_err());
+    }
+}
@@ -0,0 +1,125 @@
+//! The [`Entry`] type used throughout
+//!
+//! An entry is a single item in the diary ("Protokoll") of a project.
+
+use askama::Template;
+use time::{macros::format_description, OffsetDateTime};
+
+use super::{DbNoMatrix, FullId, IdState, NoId, Person, Project, UserPermission};
+
+/// These are the possible states an entries ID can be in
+pub(crate) trait EntryIdState: IdState {}
+impl EntryIdState for NoId {}
+impl EntryIdState for DbNoMatrix {}
+
+#[derive(askama::Template)]
+#[template(path = "protokoll/show.html")]
+struct EntryDisplay<'a> {
+    entry: &'a Entry<DbNoMatrix>,
+    /// Permission of the person requesting the template
+    ///
+    /// This decides whether `edit` and `delete` are shown.
+    view_permission: UserPermission,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Entry<I: EntryIdState> {
+    entry_id: I,
+    /// The project this entry belongs to
+    project_id: i32,
+    /// The person that wrote this entry
+    ///
+    /// None if that person has since been removed from the DB
+    pub(crate) author: Option<Person<DbNoMatrix>>,
+    pub(crate) timestamp: OffsetDateTime,
+    pub(crate) title: String,
+    pub(crate) body: String,
+}
+
+impl<I> Entry<I>
+where
+    I: EntryIdState,
+{
+    pub fn db_id(&self) -> I::DbId {
+        *self.entry_id.db_id()
+    }
+
+    pub fn project_id(&self) -> i32 {
+        self.project_id
+    }
+
+    /// The timestamp formatted for humans
+    pub fn timestamp_display(&self) -> String {
+        self.timestamp
+            .format(format_description!(
+                "[day].[month].[year] [hour]:[minute] UTC"
+            ))
+            .expect("static format description")
+    }
+}
+
+impl Entry<NoId> {
+    /// Create a new entry written by `author` right now.
+    pub fn new(
+        project: &Project<FullId>,
+        author: Person<DbNoMatrix>,
+        title: String,
+        body: String,
+    ) -> Self {
+        Self {
+            entry_id: NoId::default(),
+            project_id: project.db_id(),
+            author: Some(author),
+            timestamp: OffsetDateTime::now_utc(),
+            title,
+            body,
+        }
+    }
+
+    pub(crate) fn set_db_id<I: Into<DbNoMatrix>>(self, id: I) -> Entry<DbNoMatrix> {
+        Entry {
+            entry_id: id.into(),
+            project_id: self.project_id,
+            author: self.author,
+            timestamp: self.timestamp,
+            title: self.title,
+            body: self.body,
+        }
+    }
+}
+
+impl Entry<DbNoMatrix> {
+    pub fn from_db<IdInto>(
+        entry_id: IdInto,
+        project_id: i32,
+        author: Option<Person<DbNoMatrix>>,
+        timestamp: OffsetDateTime,
+        title: String,
+        body: String,
+    ) -> Self
+    where
+        IdInto: Into<DbNoMatrix>,
+    {
+        Self {
+            entry_id: entry_id.into(),
+            project_id,
+            author,
+            timestamp,
+            title,
+            body,
+        }
+    }
+
+    /// template this entry
+    pub fn display<A>(&self, view_permission: A) -> String
+    where
+        A: AsRef<UserPermission>,
+    {
+        EntryDisplay {
+            entry: self,
+            view_permission: view_permission.as_ref().to_owned(),
+        }
+        .render()
+        .expect("static template")
+    }
+}
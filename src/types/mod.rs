@@ -9,6 +9,24 @@ pub(crate) use person::Person;
 mod project;
 pub(crate) use project::Project;
 
+mod project_metadata;
+pub(crate) use project_metadata::{ProjectMetadata, ProjectStatus};
+
+mod project_role;
+pub(crate) use project_role::ProjectRole;
+
+mod project_visibility;
+pub(crate) use project_visibility::ProjectVisibility;
+
+mod entry;
+pub(crate) use entry::Entry;
+
+mod api_token;
+pub(crate) use api_token::{hash_api_token_secret, ApiToken};
+
+mod audit_event;
+pub(crate) use audit_event::{AuditAction, AuditEvent};
+
 pub(crate) trait IdState: core::fmt::Debug {
     type DbId: Copy;
     type MatrixId;
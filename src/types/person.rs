@@ -2,9 +2,8 @@
 
 use askama::Template;
 
-use super::{DbNoMatrix, IdState, NoId, UserPermission};
+use super::{DbNoMatrix, IdState, NoId, ProjectRole, UserPermission};
 
-// todo have "view-perm" and person-status in this project separately
 #[derive(askama::Template)]
 #[template(path = "user/show.html")]
 struct UserTemplate<'a> {
@@ -13,12 +12,10 @@ struct UserTemplate<'a> {
     project_id: i32,
     /// The permission of the users viewing this template
     ///
-    /// This decides wheter `remove user` and `promote/demote user` is shown.
+    /// This decides wheter `remove user` and the role selection is shown.
     view_permission: UserPermission,
-    /// The permission of this user in its group
-    ///
-    /// This determins whether they are shown as `Admin` or `User`
-    local_permission: UserPermission,
+    /// The role of this user in its group
+    role: ProjectRole,
 }
 
 pub(crate) trait PersonIdState: IdState {}
@@ -32,6 +29,9 @@ pub(crate) struct Person<I: PersonIdState> {
     pub(crate) global_permission: UserPermission,
     pub(crate) surname: Option<String>,
     pub(crate) firstname: Option<String>,
+    pub(crate) email: Option<String>,
+    /// Shown instead of first- and surname if set
+    pub(crate) display_name: Option<String>,
 }
 impl<I> Person<I>
 where
@@ -53,9 +53,18 @@ where
             global_permission,
             surname,
             firstname,
+            email: None,
+            display_name: None,
         }
     }
 
+    /// Set the contact details that are not needed to identify the person
+    pub fn with_contact(mut self, email: Option<String>, display_name: Option<String>) -> Self {
+        self.email = email;
+        self.display_name = display_name;
+        self
+    }
+
     pub fn is_global_admin(&self) -> bool {
         match self.global_permission {
             UserPermission::Admin => true,
@@ -70,9 +79,10 @@ where
 
 impl Person<DbNoMatrix> {
     /// template the user-line for this user
-    pub fn display<A>(&self, project_id: i32, view_permission: A, local_permission: A) -> String
+    pub fn display<A, R>(&self, project_id: i32, view_permission: A, role: R) -> String
     where
         A: AsRef<UserPermission>,
+        R: AsRef<ProjectRole>,
     {
         UserTemplate {
             person: self,
@@ -80,7 +90,7 @@ impl Person<DbNoMatrix> {
             // this is a bit of weird magic - askama templates take these permission by-ref
             // (because they are in for-loops which .iter() )
             // But we want to pass it as owned
-            local_permission: local_permission.as_ref().to_owned(),
+            role: role.as_ref().to_owned(),
             view_permission: view_permission.as_ref().to_owned(),
         }
         .render()
@@ -2,7 +2,12 @@
 
 use askama::Template;
 
-use super::{DbNoMatrix, FullId, IdState, MatrixNoDb, NoId, Person, UserPermission};
+use crate::permissions::{may_change_entry, project_access};
+
+use super::{
+    DbNoMatrix, Entry, FullId, IdState, MatrixNoDb, NoId, Person, ProjectMetadata, ProjectRole,
+    ProjectStatus, ProjectVisibility, UserPermission,
+};
 
 /// These are the possible states a projects ID can be in
 pub(crate) trait ProjectIdState: IdState {}
@@ -14,7 +19,13 @@ impl ProjectIdState for FullId {}
 pub(crate) struct Project<I: ProjectIdState> {
     project_id: I,
     pub(crate) name: String,
-    pub(crate) members: Vec<(Person<DbNoMatrix>, UserPermission)>,
+    pub(crate) members: Vec<(Person<DbNoMatrix>, ProjectRole)>,
+    /// Archived projects are read-only and hidden from the overview by default
+    pub(crate) is_archived: bool,
+    pub(crate) metadata: ProjectMetadata,
+    pub(crate) visibility: ProjectVisibility,
+    /// DNs of the LDAP groups whose members are synced into this project
+    pub(crate) ldap_groups: Vec<String>,
 }
 
 impl<I> Project<I>
@@ -29,6 +40,10 @@ where
             project_id: project_id.into(),
             name,
             members: vec![],
+            is_archived: false,
+            metadata: ProjectMetadata::default(),
+            visibility: ProjectVisibility::default(),
+            ldap_groups: vec![],
         }
     }
 
@@ -46,6 +61,8 @@ where
 struct ProjectDisplayHeaderOnly<'a> {
     project: &'a Project<FullId>,
     view_permission: UserPermission,
+    /// whether the user may open the project; otherwise only the name is shown
+    may_read: bool,
     element_server: String,
 }
 
@@ -58,6 +75,19 @@ struct ProjectDisplayWithUsers<'a> {
     element_server: String,
 }
 
+#[derive(askama::Template)]
+#[template(path = "project/with_entries.html", escape = "none")]
+struct ProjectDisplayWithEntries<'a> {
+    project: &'a Project<FullId>,
+    /// The diary entries of this project, newest first, with the permission each is rendered with
+    entries: Vec<(&'a Entry<DbNoMatrix>, UserPermission)>,
+    /// Permission of the person requesting the template
+    view_permission: UserPermission,
+    /// Whether the person requesting the template may write diary entries
+    entry_permission: UserPermission,
+    element_server: String,
+}
+
 #[derive(askama::Template)]
 #[template(path = "project/name_show.html")]
 struct ProjectNameDisplay<'a> {
@@ -66,10 +96,23 @@ struct ProjectNameDisplay<'a> {
     view_permission: UserPermission,
 }
 
+#[derive(askama::Template)]
+#[template(path = "project/metadata_show.html")]
+struct ProjectMetadataDisplay<'a> {
+    project: &'a Project<FullId>,
+    /// Permission of the person requesting the template
+    view_permission: UserPermission,
+}
+
+#[derive(askama::Template)]
+#[template(path = "project/metadata_edit.html")]
+struct ProjectMetadataEdit<'a> {
+    project: &'a Project<FullId>,
+}
 
 impl Project<FullId> {
-    pub(crate) fn add_member(&mut self, person: Person<DbNoMatrix>, permission: UserPermission) {
-        self.members.push((person, permission));
+    pub(crate) fn add_member(&mut self, person: Person<DbNoMatrix>, role: ProjectRole) {
+        self.members.push((person, role));
     }
 
     /// Render self, displaying only the header
@@ -78,11 +121,11 @@ impl Project<FullId> {
         user: &Person<DbNoMatrix>,
         element_server: String,
     ) -> String {
-        let view_permission = UserPermission::new_from_is_admin(
-            user.is_global_admin() || self.local_permission_for_user(&user).is_some_and(|x| x.is_admin()));
+        let access = project_access(user, self);
         ProjectDisplayHeaderOnly {
             project: self,
-            view_permission,
+            view_permission: access.view_permission(),
+            may_read: access.may_read(),
             element_server,
         }
         .render()
@@ -104,6 +147,50 @@ impl Project<FullId> {
         .expect("static template")
     }
 
+    /// Render self, displaying the diary entries
+    ///
+    /// Entries `user` may not change are shown without the buttons to do so.
+    pub(crate) fn display_with_entries(
+        &self,
+        entries: &[Entry<DbNoMatrix>],
+        user: &Person<DbNoMatrix>,
+        element_server: String,
+    ) -> String {
+        let access = project_access(user, self);
+        ProjectDisplayWithEntries {
+            project: self,
+            entries: entries
+                .iter()
+                .map(|entry| {
+                    let may_change = may_change_entry(user, access, entry);
+                    (entry, UserPermission::new_from_is_admin(may_change))
+                })
+                .collect(),
+            view_permission: access.view_permission(),
+            entry_permission: access.entry_permission(),
+            element_server,
+        }
+        .render()
+        .expect("static template")
+    }
+
+    /// Render the metadata with the edit button next to it if the user has permission
+    pub(crate) fn display_metadata(&self, view_permission: &UserPermission) -> String {
+        ProjectMetadataDisplay {
+            project: self,
+            view_permission: *view_permission,
+        }
+        .render()
+        .expect("static template")
+    }
+
+    /// Render the form editing the metadata
+    pub(crate) fn display_metadata_edit(&self) -> String {
+        ProjectMetadataEdit { project: self }
+            .render()
+            .expect("static template")
+    }
+
     /// Render self.name with the edit button next to it if the user has permission
     pub(crate) fn display_name(
         &self,
@@ -118,17 +205,13 @@ impl Project<FullId> {
     }
 
     /// None, when the user is not in the group.
-    /// Some(Admin) when they have admin privileges for this group
-    /// Some(User) when they have normal privileges for this group
+    /// Some(role) with the role they have in this group otherwise
     ///
     /// IGNORES global permissions for the user
-    pub(crate) fn local_permission_for_user(
-        &self,
-        person: &Person<DbNoMatrix>,
-    ) -> Option<UserPermission> {
-        for (user, perm) in self.members.iter() {
+    pub(crate) fn local_role_for_user(&self, person: &Person<DbNoMatrix>) -> Option<ProjectRole> {
+        for (user, role) in self.members.iter() {
             if user.person_id == person.person_id {
-                return Some(*perm);
+                return Some(*role);
             }
         }
         None
@@ -141,6 +224,10 @@ impl Project<NoId> {
             project_id: id.into(),
             name: self.name,
             members: self.members,
+            is_archived: self.is_archived,
+            metadata: self.metadata,
+            visibility: self.visibility,
+            ldap_groups: self.ldap_groups,
         }
     }
 }
@@ -153,6 +240,10 @@ impl Project<MatrixNoDb> {
             },
             name: self.name,
             members: self.members,
+            is_archived: self.is_archived,
+            metadata: self.metadata,
+            visibility: self.visibility,
+            ldap_groups: self.ldap_groups,
         }
     }
 }
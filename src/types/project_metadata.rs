@@ -0,0 +1,207 @@
+//! The [`ProjectMetadata`] describing a project beyond its name and members
+
+use pulldown_cmark::{html, CowStr, Event, Parser, Tag};
+use serde::{Deserialize, Serialize};
+use time::{macros::format_description, Date};
+
+/// The lifecycle of a project
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ProjectStatus {
+    Planned,
+    #[default]
+    Active,
+    Paused,
+    Done,
+}
+impl ProjectStatus {
+    /// All states, in the order they are offered in the UI
+    pub(crate) const ALL: [Self; 4] = [Self::Planned, Self::Active, Self::Paused, Self::Done];
+
+    /// The representation stored in the DB and used in forms
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Planned => "planned",
+            Self::Active => "active",
+            Self::Paused => "paused",
+            Self::Done => "done",
+        }
+    }
+}
+impl TryFrom<&str> for ProjectStatus {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "planned" => Self::Planned,
+            "active" => Self::Active,
+            "paused" => Self::Paused,
+            "done" => Self::Done,
+            x => return Err(x.to_owned()),
+        })
+    }
+}
+/// The text shown in the UI
+impl core::fmt::Display for ProjectStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Planned => write!(f, "geplant"),
+            Self::Active => write!(f, "aktiv"),
+            Self::Paused => write!(f, "pausiert"),
+            Self::Done => write!(f, "abgeschlossen"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct ProjectMetadata {
+    /// Markdown
+    pub(crate) description: String,
+    pub(crate) status: ProjectStatus,
+    pub(crate) start_date: Option<Date>,
+    pub(crate) end_date: Option<Date>,
+    /// Project number or cost centre
+    pub(crate) project_number: Option<String>,
+}
+
+/// Only these links are rendered as links; everything else (`javascript:` etc.) is dropped
+fn is_allowed_url(url: &str) -> bool {
+    let url = url.trim_start().to_lowercase();
+    url.starts_with("https://")
+        || url.starts_with("http://")
+        || url.starts_with("mailto:")
+        || url.starts_with('/')
+        || url.starts_with('#')
+}
+
+fn format_date(date: &Option<Date>, format: &[time::format_description::FormatItem]) -> String {
+    date.map(|d| d.format(format).expect("static format description"))
+        .unwrap_or_default()
+}
+
+impl ProjectMetadata {
+    /// The start date must not be after the end date
+    pub(crate) fn dates_are_ordered(&self) -> bool {
+        match (self.start_date, self.end_date) {
+            (Some(start), Some(end)) => start <= end,
+            _ => true,
+        }
+    }
+
+    /// Render the description to HTML
+    ///
+    /// Raw HTML in the description is shown as text, not interpreted.
+    pub(crate) fn description_html(&self) -> String {
+        let parser = Parser::new(&self.description).map(|event| match event {
+            Event::Html(x) | Event::InlineHtml(x) => Event::Text(x),
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) if !is_allowed_url(&dest_url) => Event::Start(Tag::Link {
+                link_type,
+                dest_url: CowStr::Borrowed(""),
+                title,
+                id,
+            }),
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) if !is_allowed_url(&dest_url) => Event::Start(Tag::Image {
+                link_type,
+                dest_url: CowStr::Borrowed(""),
+                title,
+                id,
+            }),
+            x => x,
+        });
+        let mut result = String::new();
+        html::push_html(&mut result, parser);
+        result
+    }
+
+    /// `DD.MM.YYYY` or empty
+    pub(crate) fn start_date_display(&self) -> String {
+        format_date(
+            &self.start_date,
+            format_description!("[day].[month].[year]"),
+        )
+    }
+
+    /// `DD.MM.YYYY` or empty
+    pub(crate) fn end_date_display(&self) -> String {
+        format_date(&self.end_date, format_description!("[day].[month].[year]"))
+    }
+
+    /// `YYYY-MM-DD` or empty, as expected by date inputs
+    pub(crate) fn start_date_input(&self) -> String {
+        format_date(
+            &self.start_date,
+            format_description!("[year]-[month]-[day]"),
+        )
+    }
+
+    /// `YYYY-MM-DD` or empty, as expected by date inputs
+    pub(crate) fn end_date_input(&self) -> String {
+        format_date(&self.end_date, format_description!("[year]-[month]-[day]"))
+    }
+
+    /// One line describing everything except the description; used in the audit log
+    pub(crate) fn summary(&self) -> String {
+        let mut result = self.status.to_string();
+        if self.start_date.is_some() || self.end_date.is_some() {
+            result.push_str(&format!(
+                ", {} - {}",
+                self.start_date_display(),
+                self.end_date_display()
+            ));
+        };
+        if let Some(number) = &self.project_number {
+            result.push_str(&format!(", Nr. {number}"));
+        };
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn status_roundtrip() {
+        for status in ProjectStatus::ALL {
+            assert_eq!(ProjectStatus::try_from(status.as_str()), Ok(status));
+        }
+        assert!(ProjectStatus::try_from("cancelled").is_err());
+    }
+
+    #[test]
+    fn description_is_rendered_without_raw_html() {
+        let metadata = ProjectMetadata {
+            description: "**Ziel**: <script>alert(1)</script> [Wiki](https://example.com) [x](javascript:alert(1))".to_owned(),
+            ..Default::default()
+        };
+        let html = metadata.description_html();
+        assert!(html.contains("<strong>Ziel</strong>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains(r#"<a href="https://example.com">Wiki</a>"#));
+        assert!(!html.contains("javascript"));
+    }
+
+    #[test]
+    fn dates_and_summary() {
+        let mut metadata = ProjectMetadata {
+            status: ProjectStatus::Paused,
+            start_date: Some(time::macros::date!(2024 - 03 - 01)),
+            project_number: Some("4711".to_owned()),
+            ..Default::default()
+        };
+        assert!(metadata.dates_are_ordered());
+        assert_eq!(metadata.summary(), "pausiert, 01.03.2024 - , Nr. 4711");
+        metadata.end_date = Some(time::macros::date!(2024 - 02 - 01));
+        assert!(!metadata.dates_are_ordered());
+    }
+}
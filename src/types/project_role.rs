@@ -0,0 +1,97 @@
+//! The [`ProjectRole`] a member has in a project
+
+use serde::{Deserialize, Serialize};
+
+/// What a member may do in a project, from least to most
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ProjectRole {
+    /// May read the project and its room, but not write in either
+    Viewer,
+    /// May write in the room and the diary
+    #[default]
+    Member,
+    /// May also manage members and change the project, but not delete it
+    Maintainer,
+    /// May do everything, including deleting the project and naming other owners
+    Owner,
+}
+impl ProjectRole {
+    /// All roles, in the order they are offered in the UI
+    pub(crate) const ALL: [Self; 4] = [Self::Viewer, Self::Member, Self::Maintainer, Self::Owner];
+
+    /// The representation stored in the DB and used in forms
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Member => "member",
+            Self::Maintainer => "maintainer",
+            Self::Owner => "owner",
+        }
+    }
+
+    /// The power level a member with this role has in the project room
+    ///
+    /// Rooms send messages at level 0, so viewers cannot write. We created the room and hold
+    /// level 100 in it; owners stay below that so that we can still demote them.
+    pub(crate) fn power_level(&self) -> i64 {
+        match self {
+            Self::Viewer => -1,
+            Self::Member => 0,
+            Self::Maintainer => 50,
+            Self::Owner => 90,
+        }
+    }
+}
+impl AsRef<ProjectRole> for ProjectRole {
+    fn as_ref(&self) -> &ProjectRole {
+        self
+    }
+}
+impl TryFrom<&str> for ProjectRole {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "viewer" => Self::Viewer,
+            "member" => Self::Member,
+            "maintainer" => Self::Maintainer,
+            "owner" => Self::Owner,
+            x => return Err(x.to_owned()),
+        })
+    }
+}
+/// The text shown in the UI
+impl core::fmt::Display for ProjectRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Viewer => write!(f, "Leser"),
+            Self::Member => write!(f, "Mitglied"),
+            Self::Maintainer => write!(f, "Verwalter"),
+            Self::Owner => write!(f, "Eigentümer"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn role_roundtrip() {
+        for role in ProjectRole::ALL {
+            assert_eq!(ProjectRole::try_from(role.as_str()), Ok(role));
+        }
+        assert!(ProjectRole::try_from("admin").is_err());
+    }
+
+    #[test]
+    fn power_levels_follow_roles() {
+        for pair in ProjectRole::ALL.windows(2) {
+            assert!(pair[0] < pair[1]);
+            assert!(pair[0].power_level() < pair[1].power_level());
+        }
+        assert!(ProjectRole::Viewer.power_level() < 0);
+        assert!(ProjectRole::Owner.power_level() < 100);
+    }
+}
@@ -0,0 +1,66 @@
+//! The [`ProjectVisibility`] deciding what non-members see of a project
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ProjectVisibility {
+    /// Every synced user can read the project
+    Public,
+    /// Every synced user sees the name, only members see the rest
+    #[default]
+    Internal,
+    /// Only members know the project exists
+    Secret,
+}
+impl ProjectVisibility {
+    /// All visibilities, in the order they are offered in the UI
+    pub(crate) const ALL: [Self; 3] = [Self::Public, Self::Internal, Self::Secret];
+
+    /// The representation stored in the DB and used in forms
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Internal => "internal",
+            Self::Secret => "secret",
+        }
+    }
+}
+impl TryFrom<&str> for ProjectVisibility {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "public" => Self::Public,
+            "internal" => Self::Internal,
+            "secret" => Self::Secret,
+            x => return Err(x.to_owned()),
+        })
+    }
+}
+/// The text shown in the UI
+impl core::fmt::Display for ProjectVisibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Public => write!(f, "öffentlich"),
+            Self::Internal => write!(f, "intern"),
+            Self::Secret => write!(f, "geheim"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn visibility_roundtrip() {
+        for visibility in ProjectVisibility::ALL {
+            assert_eq!(
+                ProjectVisibility::try_from(visibility.as_str()),
+                Ok(visibility)
+            );
+        }
+        assert!(ProjectVisibility::try_from("private").is_err());
+    }
+}
@@ -1,6 +1,8 @@
 //! Permissions that an individual user can have.
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) enum UserPermission {
     User,
     Admin,
@@ -13,13 +15,6 @@ impl UserPermission {
             UserPermission::User
         }
     }
-
-    pub fn is_admin(&self) -> bool {
-        match self {
-            Self::User => false,
-            Self::Admin => true,
-        }
-    }
 }
 impl AsRef<UserPermission> for UserPermission {
     fn as_ref(&self) -> &UserPermission {
@@ -0,0 +1,755 @@
+//! The versioned JSON API
+//!
+//! All routes live below `/api/v1`. They call the same [`crate::actions`] as the web frontend and
+//! answer with JSON. Requests are authenticated either with an API token or the login session,
+//! see [`Requester`]. Errors are returned as [`ApiError`], which carries a fitting status code and
+//! a JSON body of the form `{"error": "<machine readable>", "message": "<human readable>"}`.
+
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path, Query},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Extension, Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use time::{macros::format_description, Date};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    actions::{
+        add_member_to_project, archive_project, create_project, delete_project, link_ldap_group,
+        remove_member_from_project, rename_project, set_member_permission, set_project_visibility,
+        unlink_ldap_group, update_project_metadata, AddMemberError, ArchiveProjectError,
+        CreateProjectError, DeleteProjectError, LdapGroupError, RemoveMemberError,
+        RenameProjectError, SetPermissionError, SetVisibilityError, UpdateProjectMetadataError,
+    },
+    config::Config,
+    db::{
+        get_person, get_person_by_api_token, get_persons_with_similar_name, get_projects, DBError,
+    },
+    permissions::project_access,
+    types::{
+        hash_api_token_secret, DbNoMatrix, FullId, Person, Project, ProjectMetadata, ProjectRole,
+        ProjectStatus, ProjectVisibility, UserPermission,
+    },
+};
+
+use super::login::AuthSession;
+
+pub(crate) fn create_api_router() -> Router {
+    Router::new()
+        .route(
+            "/api/v1/projects",
+            get(self::list_projects).post(self::project_new),
+        )
+        .route(
+            "/api/v1/projects/:project_id",
+            axum::routing::delete(self::project_delete),
+        )
+        .route(
+            "/api/v1/projects/:project_id/archive",
+            post(self::project_archive),
+        )
+        .route(
+            "/api/v1/projects/:project_id/name",
+            put(self::project_rename),
+        )
+        .route(
+            "/api/v1/projects/:project_id/metadata",
+            put(self::project_update_metadata),
+        )
+        .route(
+            "/api/v1/projects/:project_id/visibility",
+            put(self::project_set_visibility),
+        )
+        .route(
+            "/api/v1/projects/:project_id/ldap_groups",
+            post(self::project_link_ldap_group).delete(self::project_unlink_ldap_group),
+        )
+        .route(
+            "/api/v1/projects/:project_id/members",
+            post(self::project_new_member),
+        )
+        .route(
+            "/api/v1/projects/:project_id/members/:username",
+            axum::routing::delete(self::project_remove_member),
+        )
+        .route(
+            "/api/v1/projects/:project_id/members/:username/permission",
+            put(self::project_set_member_permission),
+        )
+        .route("/api/v1/persons", get(self::search_persons))
+}
+
+/// An error as returned by the API
+#[derive(Debug)]
+pub(crate) struct ApiError {
+    status: StatusCode,
+    /// machine readable description of the error
+    error: &'static str,
+    /// human readable description of the error
+    message: String,
+}
+impl ApiError {
+    fn new(status: StatusCode, error: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            error,
+            message: message.into(),
+        }
+    }
+
+    /// Log the real reason and only hand out an error code to the client
+    fn internal(reason: impl core::fmt::Display) -> Self {
+        let error_uuid = Uuid::new_v4();
+        warn!("Sending internal server error via the API: {reason}. {error_uuid}");
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_server_error",
+            format!("Internal server error. Error code: {error_uuid}"),
+        )
+    }
+
+    /// The matrix server did not do what we asked it to
+    fn matrix(reason: impl core::fmt::Display) -> Self {
+        let error_uuid = Uuid::new_v4();
+        warn!("Sending bad gateway via the API because communication with Matrix failed: {reason}. {error_uuid}");
+        Self::new(
+            StatusCode::BAD_GATEWAY,
+            "matrix_unavailable",
+            format!("Could not communicate with the matrix server. Error code: {error_uuid}"),
+        )
+    }
+
+    fn project_does_not_exist() -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            "project_does_not_exist",
+            "The project does not exist.",
+        )
+    }
+
+    fn person_does_not_exist() -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            "person_does_not_exist",
+            "The person does not exist.",
+        )
+    }
+
+    fn project_is_archived() -> Self {
+        Self::new(
+            StatusCode::CONFLICT,
+            "project_is_archived",
+            "The project is archived and cannot be changed anymore.",
+        )
+    }
+
+    fn requester_has_no_permission() -> Self {
+        Self::new(
+            StatusCode::FORBIDDEN,
+            "requester_has_no_permission",
+            "The requester does not have the necessary permissions.",
+        )
+    }
+}
+impl core::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}): {}", self.status, self.error, self.message)
+    }
+}
+impl std::error::Error for ApiError {}
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        #[derive(Serialize)]
+        struct Body {
+            error: &'static str,
+            message: String,
+        }
+        (
+            self.status,
+            Json(Body {
+                error: self.error,
+                message: self.message,
+            }),
+        )
+            .into_response()
+    }
+}
+impl From<DBError> for ApiError {
+    fn from(value: DBError) -> Self {
+        Self::internal(value)
+    }
+}
+impl From<AddMemberError> for ApiError {
+    fn from(value: AddMemberError) -> Self {
+        match value {
+            AddMemberError::ProjectDoesNotExist => Self::project_does_not_exist(),
+            AddMemberError::ArchivedProject(_) => Self::project_is_archived(),
+            AddMemberError::PersonDoesNotExist => Self::person_does_not_exist(),
+            AddMemberError::RequesterHasNoPermission(_) => Self::requester_has_no_permission(),
+            AddMemberError::DB(e) => Self::internal(e),
+            AddMemberError::Matrix(e) => Self::matrix(e),
+        }
+    }
+}
+impl From<RemoveMemberError> for ApiError {
+    fn from(value: RemoveMemberError) -> Self {
+        match value {
+            RemoveMemberError::ProjectDoesNotExist => Self::project_does_not_exist(),
+            RemoveMemberError::ArchivedProject(_) => Self::project_is_archived(),
+            RemoveMemberError::PersonDoesNotExist => Self::person_does_not_exist(),
+            RemoveMemberError::RequesterHasNoPermission(_) => Self::requester_has_no_permission(),
+            RemoveMemberError::DB(e) => Self::internal(e),
+            RemoveMemberError::Matrix(e) => Self::matrix(e),
+        }
+    }
+}
+impl From<SetPermissionError> for ApiError {
+    fn from(value: SetPermissionError) -> Self {
+        match value {
+            SetPermissionError::ProjectDoesNotExist => Self::project_does_not_exist(),
+            SetPermissionError::ArchivedProject(_) => Self::project_is_archived(),
+            SetPermissionError::PersonDoesNotExist => Self::person_does_not_exist(),
+            SetPermissionError::RequesterHasNoPermission(_) => Self::requester_has_no_permission(),
+            SetPermissionError::DB(e) => Self::internal(e),
+            SetPermissionError::Matrix(e) => Self::matrix(e),
+        }
+    }
+}
+impl From<CreateProjectError> for ApiError {
+    fn from(value: CreateProjectError) -> Self {
+        match value {
+            CreateProjectError::RequesterHasNoPermission => Self::requester_has_no_permission(),
+            CreateProjectError::DB(e) => Self::internal(e),
+            CreateProjectError::Matrix(e) => Self::matrix(e),
+        }
+    }
+}
+impl From<RenameProjectError> for ApiError {
+    fn from(value: RenameProjectError) -> Self {
+        match value {
+            RenameProjectError::ProjectDoesNotExist => Self::project_does_not_exist(),
+            RenameProjectError::ArchivedProject(_) => Self::project_is_archived(),
+            RenameProjectError::RequesterHasNoPermission(_) => Self::requester_has_no_permission(),
+            RenameProjectError::DB(e) => Self::internal(e),
+            RenameProjectError::Matrix(e) => Self::matrix(e),
+        }
+    }
+}
+impl From<UpdateProjectMetadataError> for ApiError {
+    fn from(value: UpdateProjectMetadataError) -> Self {
+        match value {
+            UpdateProjectMetadataError::ProjectDoesNotExist => Self::project_does_not_exist(),
+            UpdateProjectMetadataError::ArchivedProject(_) => Self::project_is_archived(),
+            UpdateProjectMetadataError::RequesterHasNoPermission(_) => {
+                Self::requester_has_no_permission()
+            }
+            UpdateProjectMetadataError::DatesOutOfOrder => Self::new(
+                StatusCode::BAD_REQUEST,
+                "dates_out_of_order",
+                "The start date is after the end date.",
+            ),
+            UpdateProjectMetadataError::DB(e) => Self::internal(e),
+            UpdateProjectMetadataError::Matrix(e) => Self::matrix(e),
+        }
+    }
+}
+impl From<SetVisibilityError> for ApiError {
+    fn from(value: SetVisibilityError) -> Self {
+        match value {
+            SetVisibilityError::ProjectDoesNotExist => Self::project_does_not_exist(),
+            SetVisibilityError::ArchivedProject(_) => Self::project_is_archived(),
+            SetVisibilityError::RequesterHasNoPermission(_) => Self::requester_has_no_permission(),
+            SetVisibilityError::DB(e) => Self::internal(e),
+        }
+    }
+}
+impl From<LdapGroupError> for ApiError {
+    fn from(value: LdapGroupError) -> Self {
+        match value {
+            LdapGroupError::ProjectDoesNotExist => Self::project_does_not_exist(),
+            LdapGroupError::ArchivedProject(_) => Self::project_is_archived(),
+            LdapGroupError::RequesterHasNoPermission(_) => Self::requester_has_no_permission(),
+            LdapGroupError::EmptyGroupDn => Self::new(
+                StatusCode::BAD_REQUEST,
+                "empty_group_dn",
+                "The DN of the LDAP group must not be empty.",
+            ),
+            LdapGroupError::DB(e) => Self::internal(e),
+        }
+    }
+}
+impl From<ArchiveProjectError> for ApiError {
+    fn from(value: ArchiveProjectError) -> Self {
+        match value {
+            ArchiveProjectError::ProjectDoesNotExist => Self::project_does_not_exist(),
+            ArchiveProjectError::ArchivedProject(_) => Self::project_is_archived(),
+            ArchiveProjectError::RequesterHasNoPermission(_) => Self::requester_has_no_permission(),
+            ArchiveProjectError::DB(e) => Self::internal(e),
+            ArchiveProjectError::Matrix(e) => Self::matrix(e),
+        }
+    }
+}
+impl From<DeleteProjectError> for ApiError {
+    fn from(value: DeleteProjectError) -> Self {
+        match value {
+            DeleteProjectError::ProjectDoesNotExist => Self::project_does_not_exist(),
+            DeleteProjectError::RequesterHasNoPermission(_) => Self::requester_has_no_permission(),
+            DeleteProjectError::DB(e) => Self::internal(e),
+            DeleteProjectError::Matrix(e) => Self::matrix(e),
+        }
+    }
+}
+
+/// A person as returned by the API
+#[derive(Serialize)]
+struct PersonJson {
+    name: String,
+    firstname: Option<String>,
+    surname: Option<String>,
+    email: Option<String>,
+    display_name: Option<String>,
+    global_permission: UserPermission,
+}
+impl From<Person<DbNoMatrix>> for PersonJson {
+    fn from(value: Person<DbNoMatrix>) -> Self {
+        Self {
+            name: value.name,
+            firstname: value.firstname,
+            surname: value.surname,
+            email: value.email,
+            display_name: value.display_name,
+            global_permission: value.global_permission,
+        }
+    }
+}
+
+/// A member of a project as returned by the API
+#[derive(Serialize)]
+struct MemberJson {
+    #[serde(flatten)]
+    person: PersonJson,
+    /// the role of this person in the project
+    role: ProjectRole,
+}
+
+/// The metadata of a project, as returned and accepted by the API
+///
+/// Dates are `YYYY-MM-DD`
+#[derive(Serialize, Deserialize, Debug)]
+struct MetadataJson {
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    status: ProjectStatus,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    project_number: Option<String>,
+}
+impl From<ProjectMetadata> for MetadataJson {
+    fn from(value: ProjectMetadata) -> Self {
+        Self {
+            start_date: Some(value.start_date_input()).filter(|x| !x.is_empty()),
+            end_date: Some(value.end_date_input()).filter(|x| !x.is_empty()),
+            description: value.description,
+            status: value.status,
+            project_number: value.project_number,
+        }
+    }
+}
+impl TryFrom<MetadataJson> for ProjectMetadata {
+    type Error = ApiError;
+
+    fn try_from(value: MetadataJson) -> Result<Self, Self::Error> {
+        let parse_date = |x: Option<String>| {
+            x.map(|x| {
+                Date::parse(&x, format_description!("[year]-[month]-[day]")).map_err(|_| {
+                    ApiError::new(
+                        StatusCode::BAD_REQUEST,
+                        "invalid_date",
+                        format!("{x} is not a date of the form YYYY-MM-DD."),
+                    )
+                })
+            })
+            .transpose()
+        };
+        Ok(Self {
+            description: value.description,
+            status: value.status,
+            start_date: parse_date(value.start_date)?,
+            end_date: parse_date(value.end_date)?,
+            project_number: value.project_number,
+        })
+    }
+}
+
+/// A project as returned by the API
+#[derive(Serialize)]
+struct ProjectJson {
+    id: i32,
+    name: String,
+    matrix_room_id: String,
+    is_archived: bool,
+    visibility: ProjectVisibility,
+    metadata: MetadataJson,
+    members: Vec<MemberJson>,
+    /// DNs of the LDAP groups whose members are synced into this project
+    ldap_groups: Vec<String>,
+}
+impl From<Project<FullId>> for ProjectJson {
+    fn from(value: Project<FullId>) -> Self {
+        Self {
+            id: value.db_id(),
+            matrix_room_id: value.matrix_id().to_owned(),
+            is_archived: value.is_archived,
+            visibility: value.visibility,
+            metadata: value.metadata.into(),
+            ldap_groups: value.ldap_groups,
+            name: value.name,
+            members: value
+                .members
+                .into_iter()
+                .map(|(person, role)| MemberJson {
+                    person: person.into(),
+                    role,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The person making an API request
+///
+/// Scripts authenticate with `Authorization: Bearer <secret>` using an API token. Without that
+/// header, the login session of the browser is used.
+pub(crate) struct Requester(pub(crate) Person<DbNoMatrix>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Requester
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = parts
+            .extensions
+            .get::<Arc<Config>>()
+            .cloned()
+            .ok_or_else(|| ApiError::internal("the config is not available to the API"))?;
+
+        if let Some(header) = parts.headers.get(header::AUTHORIZATION) {
+            let secret = header
+                .to_str()
+                .ok()
+                .and_then(|x| x.strip_prefix("Bearer "))
+                .ok_or_else(|| {
+                    ApiError::new(
+                        StatusCode::UNAUTHORIZED,
+                        "invalid_authorization_header",
+                        "Expected an Authorization header of the form 'Bearer <token>'.",
+                    )
+                })?;
+            return match get_person_by_api_token(
+                config.pg_pool.clone(),
+                &hash_api_token_secret(secret.trim()),
+            )
+            .await?
+            {
+                Some(x) => Ok(Requester(x)),
+                None => {
+                    info!("Rejecting API request with an unknown or expired token.");
+                    Err(ApiError::new(
+                        StatusCode::UNAUTHORIZED,
+                        "invalid_token",
+                        "The API token is unknown or has expired.",
+                    ))
+                }
+            };
+        };
+
+        let auth_session = AuthSession::from_request_parts(parts, state)
+            .await
+            .map_err(|(_status, reason)| ApiError::internal(reason))?;
+        let user = auth_session.user.ok_or_else(|| {
+            ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "not_authenticated",
+                "Log in or send an API token.",
+            )
+        })?;
+        match get_person(config.pg_pool.clone(), &user.username).await? {
+            Some(x) => Ok(Requester(x)),
+            // this should fix itself on the next LDAP->DB sync period
+            None => Err(ApiError::internal("a logged-in user did not exist")),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ListProjectsQuery {
+    #[serde(default)]
+    include_archived: bool,
+}
+
+/// Only the projects the requester may read are listed
+async fn list_projects(
+    Requester(requester): Requester,
+    Extension(config): Extension<Arc<Config>>,
+    Query(query): Query<ListProjectsQuery>,
+) -> Result<Json<Vec<ProjectJson>>, ApiError> {
+    let projects = get_projects(config.pg_pool.clone(), query.include_archived).await?;
+    Ok(Json(
+        projects
+            .into_iter()
+            .filter(|x| project_access(&requester, x).may_read())
+            .map(ProjectJson::from)
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct ProjectNameData {
+    name: String,
+}
+
+async fn project_new(
+    Requester(requester): Requester,
+    Extension(config): Extension<Arc<Config>>,
+    Json(data): Json<ProjectNameData>,
+) -> Result<(StatusCode, Json<ProjectJson>), ApiError> {
+    let project = create_project(config, &requester, data.name).await?;
+    Ok((StatusCode::CREATED, Json(project.into())))
+}
+
+async fn project_rename(
+    Requester(requester): Requester,
+    Extension(config): Extension<Arc<Config>>,
+    Path(project_id): Path<i32>,
+    Json(data): Json<ProjectNameData>,
+) -> Result<Json<ProjectJson>, ApiError> {
+    let mut project = rename_project(config, &requester, project_id, data.name.clone()).await?;
+    // rename_project returns the project as it was before renaming
+    project.name = data.name;
+    Ok(Json(project.into()))
+}
+
+async fn project_update_metadata(
+    Requester(requester): Requester,
+    Extension(config): Extension<Arc<Config>>,
+    Path(project_id): Path<i32>,
+    Json(data): Json<MetadataJson>,
+) -> Result<Json<ProjectJson>, ApiError> {
+    let metadata = data.try_into()?;
+    let project = update_project_metadata(config, &requester, project_id, metadata).await?;
+    Ok(Json(project.into()))
+}
+
+#[derive(Deserialize)]
+struct VisibilityData {
+    visibility: ProjectVisibility,
+}
+
+async fn project_set_visibility(
+    Requester(requester): Requester,
+    Extension(config): Extension<Arc<Config>>,
+    Path(project_id): Path<i32>,
+    Json(data): Json<VisibilityData>,
+) -> Result<Json<ProjectJson>, ApiError> {
+    let project = set_project_visibility(config, &requester, project_id, data.visibility).await?;
+    Ok(Json(project.into()))
+}
+
+#[derive(Deserialize)]
+struct LdapGroupData {
+    group_dn: String,
+}
+
+async fn project_link_ldap_group(
+    Requester(requester): Requester,
+    Extension(config): Extension<Arc<Config>>,
+    Path(project_id): Path<i32>,
+    Json(data): Json<LdapGroupData>,
+) -> Result<Json<ProjectJson>, ApiError> {
+    let project = link_ldap_group(config, &requester, project_id, &data.group_dn).await?;
+    Ok(Json(project.into()))
+}
+
+async fn project_unlink_ldap_group(
+    Requester(requester): Requester,
+    Extension(config): Extension<Arc<Config>>,
+    Path(project_id): Path<i32>,
+    Query(data): Query<LdapGroupData>,
+) -> Result<Json<ProjectJson>, ApiError> {
+    let project = unlink_ldap_group(config, &requester, project_id, &data.group_dn).await?;
+    Ok(Json(project.into()))
+}
+
+async fn project_archive(
+    Requester(requester): Requester,
+    Extension(config): Extension<Arc<Config>>,
+    Path(project_id): Path<i32>,
+) -> Result<Json<ProjectJson>, ApiError> {
+    let project = archive_project(config, &requester, project_id).await?;
+    Ok(Json(project.into()))
+}
+
+async fn project_delete(
+    Requester(requester): Requester,
+    Extension(config): Extension<Arc<Config>>,
+    Path(project_id): Path<i32>,
+) -> Result<StatusCode, ApiError> {
+    delete_project(config, &requester, project_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct NewMemberData {
+    username: String,
+}
+
+async fn project_new_member(
+    Requester(requester): Requester,
+    Extension(config): Extension<Arc<Config>>,
+    Path(project_id): Path<i32>,
+    Json(data): Json<NewMemberData>,
+) -> Result<(StatusCode, Json<ProjectJson>), ApiError> {
+    let (_new_member, project) =
+        add_member_to_project(config, &requester, &data.username, project_id).await?;
+    Ok((StatusCode::CREATED, Json(project.into())))
+}
+
+async fn project_remove_member(
+    Requester(requester): Requester,
+    Extension(config): Extension<Arc<Config>>,
+    Path((project_id, username)): Path<(i32, String)>,
+) -> Result<StatusCode, ApiError> {
+    remove_member_from_project(config, &requester, &username, project_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct PermissionData {
+    role: ProjectRole,
+}
+
+async fn project_set_member_permission(
+    Requester(requester): Requester,
+    Extension(config): Extension<Arc<Config>>,
+    Path((project_id, username)): Path<(i32, String)>,
+    Json(data): Json<PermissionData>,
+) -> Result<Json<MemberJson>, ApiError> {
+    let (changed_member, _project) =
+        set_member_permission(config, &requester, &username, project_id, data.role).await?;
+    Ok(Json(MemberJson {
+        person: changed_member.into(),
+        role: data.role,
+    }))
+}
+
+#[derive(Deserialize)]
+struct SearchPersonsQuery {
+    name: String,
+}
+
+async fn search_persons(
+    _requester: Requester,
+    Extension(config): Extension<Arc<Config>>,
+    Query(query): Query<SearchPersonsQuery>,
+) -> Result<Json<Vec<PersonJson>>, ApiError> {
+    let persons = get_persons_with_similar_name(config.pg_pool.clone(), &query.name).await?;
+    Ok(Json(persons.into_iter().map(PersonJson::from).collect()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn action_errors_map_to_status_codes() {
+        assert_eq!(
+            ApiError::from(AddMemberError::ProjectDoesNotExist).status,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            ApiError::from(RemoveMemberError::PersonDoesNotExist).status,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            ApiError::from(RemoveMemberError::ArchivedProject("p".to_owned())).status,
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            ApiError::from(SetPermissionError::RequesterHasNoPermission("p".to_owned())).status,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            ApiError::from(CreateProjectError::RequesterHasNoPermission).status,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            ApiError::from(ArchiveProjectError::ProjectDoesNotExist).status,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            ApiError::from(ArchiveProjectError::ArchivedProject("p".to_owned())).status,
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            ApiError::from(DeleteProjectError::RequesterHasNoPermission("p".to_owned())).status,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            ApiError::from(RenameProjectError::DB(DBError::CannotStartTransaction(
+                sqlx::Error::PoolTimedOut
+            )))
+            .status,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            ApiError::from(UpdateProjectMetadataError::DatesOutOfOrder).status,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            ApiError::from(SetVisibilityError::ProjectDoesNotExist).status,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            ApiError::from(LdapGroupError::EmptyGroupDn).status,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn metadata_json_roundtrip() {
+        let json: MetadataJson = serde_json::from_str(
+            r#"{"status": "paused", "start_date": "2024-03-01", "end_date": null, "project_number": "4711"}"#,
+        )
+        .unwrap();
+        let metadata = ProjectMetadata::try_from(json).unwrap();
+        assert_eq!(metadata.status, ProjectStatus::Paused);
+        assert_eq!(
+            metadata.start_date,
+            Some(time::macros::date!(2024 - 03 - 01))
+        );
+        assert!(metadata.description.is_empty());
+
+        let json = serde_json::to_value(MetadataJson::from(metadata)).unwrap();
+        assert_eq!(json["status"], "paused");
+        assert_eq!(json["start_date"], "2024-03-01");
+        assert!(json["end_date"].is_null());
+
+        let malformed: MetadataJson =
+            serde_json::from_str(r#"{"start_date": "01.03.2024"}"#).unwrap();
+        assert_eq!(
+            ProjectMetadata::try_from(malformed).unwrap_err().status,
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
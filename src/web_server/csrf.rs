@@ -0,0 +1,201 @@
+//! Protection against cross-site request forgery
+//!
+//! Every session gets a random token. The pages put it into `hx-headers` on their `<body>`, so
+//! htmx sends it with every request as [`CSRF_HEADER`]. Requests that change something must carry
+//! the token of their session and must not come from another origin.
+//!
+//! Requests with an `Authorization` header are left alone: they authenticate with an API token
+//! instead of the session cookie, and browsers do not add that header on their own. The login
+//! form has no token yet, so it is only checked for its origin.
+
+use axum::{
+    extract::{Host, Request},
+    http::{header, HeaderMap, Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tower_sessions::Session;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::InternalServerErrorTemplate;
+
+/// The key of the token in the session
+const CSRF_TOKEN_KEY: &str = "csrf_token";
+
+/// The header htmx sends the token in
+pub(super) const CSRF_HEADER: &str = "x-csrf-token";
+
+/// The token of this session, created on first use
+///
+/// Err contains the response to send if the session cannot be read or written.
+pub(super) async fn csrf_token(session: &Session) -> Result<String, Response> {
+    let existing = match session.get::<String>(CSRF_TOKEN_KEY).await {
+        Ok(x) => x,
+        Err(e) => return Err(session_error(e)),
+    };
+    if let Some(token) = existing {
+        return Ok(token);
+    };
+    let token = Uuid::new_v4().simple().to_string();
+    match session.insert(CSRF_TOKEN_KEY, &token).await {
+        Ok(()) => Ok(token),
+        Err(e) => Err(session_error(e)),
+    }
+}
+
+fn session_error(e: tower_sessions::session::Error) -> Response {
+    let error_uuid = Uuid::new_v4();
+    warn!("Sending internal server error because I cannot access the CSRF token in the session: {e}. {error_uuid}");
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        InternalServerErrorTemplate { error_uuid },
+    )
+        .into_response()
+}
+
+/// Whether a request with this method may change something
+fn is_state_changing(method: &Method) -> bool {
+    !matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    )
+}
+
+/// Whether the request was sent from a page of `host`, judged by the Origin header or, if that is
+/// missing, the Referer.
+///
+/// Requests with neither header pass; they do not come from a current browser.
+fn is_same_origin(headers: &HeaderMap, host: &str) -> bool {
+    let Some(value) = headers
+        .get(header::ORIGIN)
+        .or_else(|| headers.get(header::REFERER))
+    else {
+        return true;
+    };
+    value
+        .to_str()
+        .ok()
+        .and_then(|x| x.parse::<Uri>().ok())
+        .and_then(|uri| uri.authority().map(|x| x.as_str() == host))
+        .unwrap_or(false)
+}
+
+/// Compare two tokens in time independent of where they differ
+fn tokens_match(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Reject state-changing requests from other origins or without the token of the session
+pub(super) async fn verify_csrf(
+    Host(host): Host,
+    session: Session,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !is_state_changing(request.method()) || request.headers().contains_key(header::AUTHORIZATION)
+    {
+        return next.run(request).await;
+    };
+
+    if !is_same_origin(request.headers(), &host) {
+        info!(
+            "Sending 403 because a {} request to {} came from another origin.",
+            request.method(),
+            request.uri().path()
+        );
+        return (
+            StatusCode::FORBIDDEN,
+            "Diese Anfrage kam nicht von dieser Seite.",
+        )
+            .into_response();
+    };
+    if request.uri().path() == "/login" {
+        return next.run(request).await;
+    };
+
+    let expected = match session.get::<String>(CSRF_TOKEN_KEY).await {
+        Ok(x) => x,
+        Err(e) => return session_error(e),
+    };
+    let given = request
+        .headers()
+        .get(CSRF_HEADER)
+        .and_then(|x| x.to_str().ok());
+    match (expected, given) {
+        (Some(expected), Some(given)) if tokens_match(&expected, given) => next.run(request).await,
+        _ => {
+            info!(
+                "Sending 403 because a {} request to {} had no valid CSRF token.",
+                request.method(),
+                request.uri().path()
+            );
+            (
+                StatusCode::FORBIDDEN,
+                "Die Seite ist veraltet. Bitte lade sie neu und versuche es noch einmal.",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn headers(name: header::HeaderName, value: &'static str) -> HeaderMap {
+        let mut res = HeaderMap::new();
+        res.insert(name, HeaderValue::from_static(value));
+        res
+    }
+
+    #[test]
+    fn origin_is_checked() {
+        let host = "tagebuch.example.org:8443";
+        assert!(is_same_origin(
+            &headers(header::ORIGIN, "https://tagebuch.example.org:8443"),
+            host
+        ));
+        assert!(!is_same_origin(
+            &headers(header::ORIGIN, "https://evil.example.org"),
+            host
+        ));
+        assert!(!is_same_origin(&headers(header::ORIGIN, "null"), host));
+        assert!(is_same_origin(
+            &headers(
+                header::REFERER,
+                "https://tagebuch.example.org:8443/web/tokens"
+            ),
+            host
+        ));
+        assert!(!is_same_origin(
+            &headers(header::REFERER, "https://tagebuch.example.org/"),
+            host
+        ));
+        assert!(is_same_origin(&HeaderMap::new(), host));
+    }
+
+    #[test]
+    fn only_state_changing_methods_are_checked() {
+        assert!(!is_state_changing(&Method::GET));
+        assert!(!is_state_changing(&Method::HEAD));
+        assert!(is_state_changing(&Method::POST));
+        assert!(is_state_changing(&Method::PUT));
+        assert!(is_state_changing(&Method::DELETE));
+    }
+
+    #[test]
+    fn tokens_are_compared() {
+        assert!(tokens_match("abc", "abc"));
+        assert!(!tokens_match("abc", "abd"));
+        assert!(!tokens_match("abc", "ab"));
+        assert!(!tokens_match("abc", ""));
+    }
+}
@@ -1,31 +1,48 @@
-use crate::ldap::{LDAPBackend, UserCredentials};
+use std::sync::Arc;
+
+use crate::auth::{AuthBackend, UserCredentials};
 use askama_axum::Template;
 /// All the routes needed to do auth and the backend for that
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Redirect},
     routing::{get, post},
-    Form, Router,
+    Extension, Form, Router,
 };
 
-pub type AuthSession = axum_login::AuthSession<LDAPBackend>;
+use super::throttle::LoginThrottle;
+
+pub type AuthSession = axum_login::AuthSession<AuthBackend>;
 
 #[derive(Template)]
 #[template(path = "login.html")]
-pub struct LoginTemplate {}
+pub struct LoginTemplate {
+    /// Users log in at an IdP instead of with a password
+    oidc: bool,
+}
 
-pub(crate) fn create_login_router() -> Router<()> {
+pub(crate) fn create_login_router(throttle: Arc<LoginThrottle>) -> Router<()> {
     Router::new()
         .route("/login", get(self::get::login))
         .route("/login", post(self::post::login))
+        .route("/login/oidc", get(self::get::oidc_login))
+        .route("/login/oidc/callback", get(self::get::oidc_callback))
         .route("/logout", get(self::get::logout))
+        .layer(Extension(throttle))
 }
 
 mod post {
+    use std::net::SocketAddr;
+
+    use axum::{extract::ConnectInfo, http::header};
     use tracing::{info, warn, Level};
     use uuid::Uuid;
 
-    use crate::web_server::InternalServerErrorTemplate;
+    use crate::{
+        actions::{provision_user, record_failed_login},
+        config::Config,
+        web_server::InternalServerErrorTemplate,
+    };
     use askama_axum::IntoResponse;
 
     use super::*;
@@ -33,21 +50,52 @@ mod post {
     #[tracing::instrument(level=Level::TRACE,skip_all,ret)]
     pub(super) async fn login(
         mut auth_session: super::AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Extension(throttle): Extension<Arc<LoginThrottle>>,
+        ConnectInfo(address): ConnectInfo<SocketAddr>,
         Form(creds): Form<UserCredentials>,
     ) -> impl IntoResponse {
+        let address = address.ip();
+        // do not even ask the auth backend, so that guessing cannot lock accounts there
+        let attempt = match throttle.check(address, &creds.username) {
+            Ok(x) => x,
+            Err(wait) => {
+                let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+                info!(
+                    "Returning 429, because there were too many failed logins from {address} or as {}.",
+                    creds.username
+                );
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, seconds.to_string())],
+                    format!("Zu viele fehlgeschlagene Anmeldungen. Bitte versuche es in {seconds} Sekunden noch einmal."),
+                )
+                    .into_response();
+            }
+        };
+
         let user = match auth_session.authenticate(creds.clone()).await {
             Ok(Some(user)) => {
                 info!("New user logged in: {:?}", user);
+                attempt.success();
                 user
             }
             Ok(None) => {
                 warn!("Returning redirect, because the user {} supplied the wrong password or was not found via the user filter.", creds.username);
+                attempt.failure();
+                if let Err(e) = record_failed_login(config, &creds.username, address).await {
+                    warn!(
+                        "Could not record the failed login of {}: {e}",
+                        creds.username
+                    );
+                };
                 return Redirect::to("/login").into_response();
             }
             Err(e) => {
                 warn!(
-                    "Returning internal server error, because I could not ldap search a user: {e}"
+                    "Returning internal server error, because I could not authenticate a user: {e}"
                 );
+                attempt.error();
                 let error_uuid = Uuid::new_v4();
                 warn!("{error_uuid}");
                 return (
@@ -58,8 +106,16 @@ mod post {
             }
         };
 
+        // the sync would add them eventually, but they want to use the service now
+        if let Err(e) = provision_user(config, &user.username).await {
+            warn!(
+                "Could not provision {} on login, they have to wait for the next sync: {e}",
+                user.username
+            );
+        };
+
         if let Err(e) = auth_session.login(&user).await {
-            warn!("Returning internal server error, because I could not ldap bind a user: {e}");
+            warn!("Returning internal server error, because I could not log in a user: {e}");
             let error_uuid = Uuid::new_v4();
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -72,16 +128,123 @@ mod post {
 }
 
 mod get {
-    use tracing::{warn, Level};
+    use axum::extract::Query;
+    use serde::Deserialize;
+    use tower_sessions::Session;
+    use tracing::{info, warn, Level};
     use uuid::Uuid;
 
-    use crate::web_server::InternalServerErrorTemplate;
+    use crate::{
+        auth::{
+            oidc::{PendingLogin, PENDING_LOGIN_KEY},
+            User,
+        },
+        config::Config,
+        db::provision_person,
+        web_server::InternalServerErrorTemplate,
+    };
 
     use super::*;
 
     #[tracing::instrument(level=Level::TRACE,skip_all)]
-    pub async fn login() -> LoginTemplate {
-        LoginTemplate {}
+    pub async fn login(Extension(config): Extension<Arc<Config>>) -> LoginTemplate {
+        LoginTemplate {
+            oidc: config.auth_backend.oidc().is_some(),
+        }
+    }
+
+    fn internal_server_error() -> axum::response::Response {
+        let error_uuid = Uuid::new_v4();
+        warn!("{error_uuid}");
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            InternalServerErrorTemplate { error_uuid },
+        )
+            .into_response()
+    }
+
+    /// Send the user to the IdP, remembering state, nonce and PKCE verifier in the session
+    #[tracing::instrument(level=Level::TRACE,skip_all)]
+    pub async fn oidc_login(
+        Extension(config): Extension<Arc<Config>>,
+        session: Session,
+    ) -> impl IntoResponse {
+        let Some(oidc) = config.auth_backend.oidc() else {
+            return Redirect::to("/login").into_response();
+        };
+        let (url, pending) = match oidc.start_login() {
+            Ok(x) => x,
+            Err(e) => {
+                warn!(
+                    "Returning internal server error, because I could not start an OIDC login: {e}"
+                );
+                return internal_server_error();
+            }
+        };
+        if let Err(e) = session.insert(PENDING_LOGIN_KEY, pending).await {
+            warn!("Returning internal server error, because I could not store an OIDC login in the session: {e}");
+            return internal_server_error();
+        };
+        Redirect::to(url.as_str()).into_response()
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub(super) struct OidcCallback {
+        code: Option<String>,
+        state: Option<String>,
+        error: Option<String>,
+    }
+
+    /// The IdP sends the user back here. Check the answer, put the user into the DB and log them in.
+    #[tracing::instrument(level=Level::TRACE,skip_all)]
+    pub async fn oidc_callback(
+        mut auth_session: AuthSession,
+        session: Session,
+        Extension(config): Extension<Arc<Config>>,
+        Query(callback): Query<OidcCallback>,
+    ) -> impl IntoResponse {
+        let Some(oidc) = config.auth_backend.oidc() else {
+            return Redirect::to("/login").into_response();
+        };
+        // a login can only be finished once
+        let pending = match session.remove::<PendingLogin>(PENDING_LOGIN_KEY).await {
+            Ok(Some(x)) => x,
+            Ok(None) => {
+                warn!("Returning redirect, because an OIDC callback arrived without a login in progress.");
+                return Redirect::to("/login").into_response();
+            }
+            Err(e) => {
+                warn!("Returning internal server error, because I could not read an OIDC login from the session: {e}");
+                return internal_server_error();
+            }
+        };
+        let (Some(code), Some(state)) = (callback.code, callback.state) else {
+            warn!(
+                "Returning redirect, because the IdP did not log the user in: {:?}",
+                callback.error
+            );
+            return Redirect::to("/login").into_response();
+        };
+        let person = match oidc.finish_login(&pending, &code, &state).await {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Returning redirect, because the OIDC login could not be finished: {e}");
+                return Redirect::to("/login").into_response();
+            }
+        };
+        let user = User {
+            username: person.name.clone(),
+        };
+        if let Err(e) = provision_person(config.pg_pool.clone(), person).await {
+            warn!("Returning internal server error, because I could not provision a user: {e}");
+            return internal_server_error();
+        };
+        if let Err(e) = auth_session.login(&user).await {
+            warn!("Returning internal server error, because I could not log in a user: {e}");
+            return internal_server_error();
+        };
+        info!("New user logged in via OIDC: {:?}", user);
+        Redirect::to("/").into_response()
     }
 
     #[tracing::instrument(level=Level::TRACE,skip_all)]
@@ -5,8 +5,8 @@ use axum_login::{
     AuthManagerLayerBuilder,
 };
 use sqlx::SqlitePool;
-use tower_sessions::{cookie::Key, ExpiredDeletion};
-use tower_sessions_sqlx_store::SqliteStore;
+use tower_sessions::{ExpiredDeletion, SessionStore};
+use tower_sessions_sqlx_store::{PostgresStore, SqliteStore};
 use uuid::Uuid;
 
 use std::{future::Future, str::FromStr, sync::Arc, time::Duration};
@@ -21,9 +21,17 @@ use axum::{
 };
 use tracing::{debug, event, Level};
 
-use crate::{config::Config, ldap::LDAPBackend, InShutdown};
+use crate::{
+    auth::AuthBackend,
+    config::{Config, SessionStoreKind},
+    InShutdown,
+};
+use throttle::LoginThrottle;
+mod api;
+mod csrf;
 pub(crate) mod login;
 mod protected;
+mod throttle;
 
 #[derive(Template)]
 #[template(path = "500.html")]
@@ -31,19 +39,36 @@ struct InternalServerErrorTemplate {
     error_uuid: Uuid,
 }
 
+/// Where the sessions of logged in users are stored
+enum Sessions {
+    Sqlite(SqliteStore),
+    Postgres(PostgresStore),
+}
+
 /// App State that simply holds a user session store
 pub struct Webserver {
-    db: SqlitePool,
+    sessions: Sessions,
 }
 impl Webserver {
-    pub async fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let connect_options = sqlx::sqlite::SqliteConnectOptions::new()
-            .filename(".session_data.db")
-            .create_if_missing(true);
-        let db = SqlitePool::connect_with(connect_options).await?;
-        // sqlx::migrate!().run(&db).await?;
-
-        Ok(Self { db })
+    pub(crate) async fn new(config: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let sessions = match config.web_config.session_store {
+            SessionStoreKind::Sqlite => {
+                let connect_options = sqlx::sqlite::SqliteConnectOptions::new()
+                    .filename(&config.web_config.session_db_path)
+                    .create_if_missing(true);
+                let db = SqlitePool::connect_with(connect_options).await?;
+                let store = SqliteStore::new(db);
+                store.migrate().await?;
+                Sessions::Sqlite(store)
+            }
+            SessionStoreKind::Postgres => {
+                // sessions get their own schema, next to our tables
+                let store = PostgresStore::new(config.pg_pool.clone());
+                store.migrate().await?;
+                Sessions::Postgres(store)
+            }
+        };
+        Ok(Self { sessions })
     }
 
     /// Run the web server
@@ -52,51 +77,10 @@ impl Webserver {
         config: Arc<Config>,
         watcher: tokio::sync::watch::Receiver<InShutdown>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        // Session layer.
-        //
-        // This uses `tower-sessions` to establish a layer that will provide the session
-        // as a request extension.
-        let session_store = SqliteStore::new(self.db.clone());
-        session_store.migrate().await?;
-
-        tokio::task::spawn(
-            session_store
-                .clone()
-                .continuously_delete_expired(tokio::time::Duration::from_secs(60)),
-        );
-        // Generate a cryptographic key to sign the session cookie.
-        let key = Key::generate();
-
-        let session_layer = SessionManagerLayer::new(session_store)
-            .with_secure(false)
-            .with_expiry(Expiry::OnInactivity(time::Duration::hours(12)))
-            .with_signed(key);
-
-        // Auth service.
-        //
-        // This combines the session layer with our backend to establish the auth
-        // service which will provide the auth session as a request extension.
-        let auth_backend = config.ldap_backend.clone();
-        let auth_layer = AuthManagerLayerBuilder::new(auth_backend, session_layer).build();
-
-        let our_config = config.clone();
-        let app = Router::new()
-            .merge(protected::create_protected_router())
-            .route_layer(login_required!(LDAPBackend, login_url = "/login"))
-            .merge(login::create_login_router())
-            .layer(auth_layer)
-            .layer(Extension(our_config))
-            .route("/scripts/htmx@2.0.2.js", get(htmx_script))
-            .route(
-                "/scripts/hyperscript.org@0.9.12.js",
-                get(hyperscript_script),
-            )
-            .route(
-                "/scripts/htmx@2.0.2_response_targets.js",
-                get(htmx_script_response_targets),
-            )
-            .route("/style.css", get(css_style))
-            .fallback(fallback);
+        let app = match &self.sessions {
+            Sessions::Sqlite(store) => create_app(config.clone(), store.clone()),
+            Sessions::Postgres(store) => create_app(config.clone(), store.clone()),
+        };
 
         // run it
         let addr = std::net::SocketAddr::from_str(&format!(
@@ -115,7 +99,7 @@ impl Webserver {
         // serve the main app on HTTPS
         axum_server::bind_rustls(addr, config.web_config.rustls_config.clone())
             .handle(shutdown_handle)
-            .serve(app.into_make_service())
+            .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>())
             .await
             .expect("Should be able to start service");
 
@@ -123,6 +107,56 @@ impl Webserver {
     }
 }
 
+/// Build the router with all routes, keeping sessions in `session_store`
+fn create_app<S>(config: Arc<Config>, session_store: S) -> Router
+where
+    S: SessionStore + ExpiredDeletion + Clone,
+{
+    // Session layer.
+    //
+    // This uses `tower-sessions` to establish a layer that will provide the session
+    // as a request extension.
+    tokio::task::spawn(
+        session_store
+            .clone()
+            .continuously_delete_expired(tokio::time::Duration::from_secs(60)),
+    );
+
+    let session_layer = SessionManagerLayer::new(session_store)
+        .with_secure(config.web_config.secure_cookies)
+        .with_expiry(Expiry::OnInactivity(config.web_config.session_idle_expiry))
+        .with_signed(config.web_config.session_key.clone());
+
+    // Auth service.
+    //
+    // This combines the session layer with our backend to establish the auth
+    // service which will provide the auth session as a request extension.
+    let auth_backend = config.auth_backend.clone();
+    let auth_layer = AuthManagerLayerBuilder::new(auth_backend, session_layer).build();
+
+    Router::new()
+        .merge(protected::create_protected_router())
+        .route_layer(login_required!(AuthBackend, login_url = "/login"))
+        .merge(api::create_api_router())
+        .merge(login::create_login_router(Arc::new(LoginThrottle::new(
+            config.web_config.login_limits,
+        ))))
+        .layer(axum::middleware::from_fn(csrf::verify_csrf))
+        .layer(auth_layer)
+        .layer(Extension(config))
+        .route("/scripts/htmx@2.0.2.js", get(htmx_script))
+        .route(
+            "/scripts/hyperscript.org@0.9.12.js",
+            get(hyperscript_script),
+        )
+        .route(
+            "/scripts/htmx@2.0.2_response_targets.js",
+            get(htmx_script_response_targets),
+        )
+        .route("/style.css", get(css_style))
+        .fallback(fallback)
+}
+
 async fn shutdown_signal(
     handle: axum_server::Handle,
     mut watcher: tokio::sync::watch::Receiver<InShutdown>,
@@ -131,7 +165,6 @@ async fn shutdown_signal(
         _ = watcher.changed() => {
             debug!("Shutting down web server now.");
             handle.graceful_shutdown(Some(Duration::from_secs(5)));
-            return;
         }
     }
 }
@@ -7,13 +7,15 @@ use axum::{
     routing::{delete, get, post},
     Extension, Router,
 };
-use tracing::warn;
+use sqlx::PgConnection;
+use tracing::{info, warn};
 use uuid::Uuid;
 
 use crate::{
     config::Config,
-    db::get_person,
-    types::{DbNoMatrix, Person},
+    db::{get_person, get_project},
+    permissions::{require_access, AccessDenied, ProjectAccess},
+    types::{DbNoMatrix, FullId, Person, Project},
     web_server::InternalServerErrorTemplate,
 };
 
@@ -55,6 +57,63 @@ pub(crate) fn create_protected_router() -> Router {
             get(self::get::project_rename_template).
             post(self::post::project_rename),
         )
+        .route(
+            "/web/project/:project_id/metadata",
+            get(self::get::project_metadata_template),
+        )
+        .route(
+            "/web/project/:project_id/metadata/edit",
+            get(self::get::project_metadata_edit_template).post(self::post::project_metadata_edit),
+        )
+        .route(
+            "/web/project/:project_id/protokoll",
+            get(self::get::project_with_entries).post(self::post::entry_new),
+        )
+        .route(
+            "/web/project/:project_id/protokoll.pdf",
+            get(self::get::project_protokoll_pdf),
+        )
+        .route(
+            "/web/project/:project_id/protokoll/new",
+            get(self::get::entry_new_template),
+        )
+        .route(
+            "/web/project/:project_id/protokoll/:entry_id",
+            get(self::get::entry_show)
+                .post(self::post::entry_edit)
+                .delete(self::delete::entry_delete),
+        )
+        .route(
+            "/web/project/:project_id/protokoll/:entry_id/edit",
+            get(self::get::entry_edit_template),
+        )
+        .route(
+            "/web/tokens",
+            get(self::get::tokens_page).post(self::post::token_new),
+        )
+        .route("/web/tokens/:token_id", delete(self::delete::token_revoke))
+        .route(
+            "/web/project/:project_id",
+            delete(self::delete::project_delete),
+        )
+        .route(
+            "/web/project/:project_id/archive",
+            post(self::post::project_archive),
+        )
+        .route(
+            "/web/project/:project_id/history",
+            get(self::get::project_history),
+        )
+        .route(
+            "/web/project/:project_id/visibility",
+            post(self::post::project_set_visibility),
+        )
+        .route(
+            "/web/project/:project_id/ldap_groups",
+            post(self::post::project_link_ldap_group)
+                .delete(self::delete::project_unlink_ldap_group),
+        )
+        .route("/web/audit", get(self::get::audit_page))
 }
 
 /// Get the user (as present in db) from the auth session, creating relevant Server Error returns
@@ -98,28 +157,200 @@ async fn get_user_from_session(
     }
 }
 
+/// Get a project from the DB and make sure that `user` has at least the `required` access to it,
+/// creating relevant error returns
+///
+/// Projects the user may not see at all are reported as not existing.
+async fn get_project_with_access(
+    con: &mut PgConnection,
+    user: &Person<DbNoMatrix>,
+    project_id: i32,
+    required: ProjectAccess,
+) -> Result<(Project<FullId>, ProjectAccess), axum::response::Response> {
+    let project = match get_project(con, project_id).await {
+        Ok(Some(x)) => x,
+        Ok(None) => {
+            info!("Project {project_id} was requested but does not exist.");
+            return Err((StatusCode::NOT_FOUND).into_response());
+        }
+        Err(e) => {
+            let error_uuid = Uuid::new_v4();
+            warn!("Sending internal server error because I cannot get project {project_id} by id: {e}. {error_uuid}");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                InternalServerErrorTemplate { error_uuid },
+            )
+                .into_response());
+        }
+    };
+    match require_access(user, &project, required) {
+        Ok(access) => Ok((project, access)),
+        Err(AccessDenied::Hidden) => {
+            info!(
+                "Project {project_id} was requested by {}, who may not see it.",
+                user.name
+            );
+            Err((StatusCode::NOT_FOUND).into_response())
+        }
+        Err(AccessDenied::Insufficient) => {
+            warn!(
+                "Sending 401 because user {} is not authorized to access group {} this way.",
+                user.name, project.name
+            );
+            Err(StatusCode::UNAUTHORIZED.into_response())
+        }
+    }
+}
+
+/// The response to a change to an archived project, shown in the error display of the page
+fn archived_project_response(
+    user: &Person<DbNoMatrix>,
+    project_name: &str,
+) -> axum::response::Response {
+    info!(
+        "Sending 409 because user {} tried to change the archived project {project_name}.",
+        user.name
+    );
+    (
+        StatusCode::CONFLICT,
+        format!("Das Projekt {project_name} ist archiviert und kann nicht mehr geändert werden."),
+    )
+        .into_response()
+}
+
 pub(super) mod get {
     use crate::{
-        db::{get_person, get_project, get_projects, DBError},
-        types::{FullId, Project, UserPermission},
-        web_server::{login::AuthSession, InternalServerErrorTemplate},
+        actions::provision_user,
+        db::{
+            get_api_tokens, get_audit_events, get_entries, get_entry, get_person, search_projects,
+            AuditEventFilter, DBError, ProjectFilter, AUDIT_EVENT_LIMIT, PROJECT_PAGE_SIZE,
+        },
+        permissions::{may_administer_instance, may_change_entry},
+        render::render_protokoll,
+        types::{ApiToken, AuditEvent, Entry, FullId, Project, ProjectStatus, UserPermission},
+        web_server::{csrf::csrf_token, login::AuthSession, InternalServerErrorTemplate},
     };
 
     use super::*;
 
     use askama_axum::IntoResponse;
-    use axum::{extract::Path, http::StatusCode};
+    use axum::{
+        extract::{Path, Query},
+        http::{header, StatusCode},
+    };
+    use serde::Deserialize;
+    use time::{macros::format_description, Date};
+    use tower_sessions::Session;
     use tracing::{debug, info, warn};
     use uuid::Uuid;
 
     use crate::config::Config;
 
     #[derive(askama_axum::Template)]
-    #[template(path = "landing/complete.html", escape = "none")]
+    #[template(path = "landing/complete.html")]
     struct LandingAsUser {
         user: Person<DbNoMatrix>,
         projects: Vec<Project<FullId>>,
         element_server: String,
+        /// the search and filters, as entered by the user
+        query: RootQuery,
+        /// number of all projects matching the query
+        total: i64,
+        csrf_token: String,
+        page_count: i64,
+    }
+
+    /// Search and filters of the project list; all fields may be left out
+    #[derive(Deserialize, Debug, Default, Clone)]
+    pub(super) struct RootQuery {
+        #[serde(default)]
+        archived: bool,
+        /// the search term
+        #[serde(default)]
+        q: String,
+        /// only projects the user is a member of
+        #[serde(default)]
+        mine: bool,
+        /// only projects the user is an admin of
+        #[serde(default)]
+        admin: bool,
+        /// empty for any status
+        #[serde(default)]
+        status: String,
+        /// 1-based
+        page: Option<i64>,
+    }
+    impl RootQuery {
+        fn page(&self) -> i64 {
+            self.page.unwrap_or(1).max(1)
+        }
+
+        /// None if the status is unknown
+        fn to_filter(&self, user: &Person<DbNoMatrix>) -> Option<ProjectFilter> {
+            Some(ProjectFilter {
+                include_archived: self.archived,
+                search: match self.q.trim() {
+                    "" => None,
+                    x => Some(x.to_owned()),
+                },
+                member_id: self.mine.then(|| user.db_id()),
+                admin_id: self.admin.then(|| user.db_id()),
+                status: match self.status.as_str() {
+                    "" => None,
+                    x => Some(ProjectStatus::try_from(x).ok()?),
+                },
+                page: self.page() - 1,
+                // global admins see all projects, including secret ones
+                viewer_id: (!may_administer_instance(user)).then(|| user.db_id()),
+            })
+        }
+
+        /// The url showing this query
+        fn url(&self) -> String {
+            let mut params = vec![];
+            if self.archived {
+                params.push("archived=true".to_owned());
+            };
+            if !self.q.is_empty() {
+                params.push(format!("q={}", urlencoding::encode(&self.q)));
+            };
+            if self.mine {
+                params.push("mine=true".to_owned());
+            };
+            if self.admin {
+                params.push("admin=true".to_owned());
+            };
+            if !self.status.is_empty() {
+                params.push(format!("status={}", urlencoding::encode(&self.status)));
+            };
+            if self.page() > 1 {
+                params.push(format!("page={}", self.page()));
+            };
+            if params.is_empty() {
+                "/".to_owned()
+            } else {
+                format!("/?{}", params.join("&"))
+            }
+        }
+
+        /// The url of another page of this query
+        fn page_url(&self, page: i64) -> String {
+            Self {
+                page: Some(page),
+                ..self.clone()
+            }
+            .url()
+        }
+
+        /// The url of this query with archived projects shown or hidden, starting on the first page
+        fn archived_url(&self, archived: bool) -> String {
+            Self {
+                archived,
+                page: None,
+                ..self.clone()
+            }
+            .url()
+        }
     }
 
     #[derive(askama_axum::Template)]
@@ -131,8 +362,14 @@ pub(super) mod get {
 
     pub(super) async fn root(
         auth_session: AuthSession,
+        session: Session,
         Extension(config): Extension<Arc<Config>>,
+        Query(query): Query<RootQuery>,
     ) -> impl IntoResponse {
+        let csrf_token = match csrf_token(&session).await {
+            Ok(x) => x,
+            Err(response) => return response,
+        };
         let user = if let Some(x) = auth_session.user {
             x
         } else {
@@ -145,12 +382,11 @@ pub(super) mod get {
                 .into_response();
         };
 
-        // get projects
-        let projects = match get_projects(config.pg_pool.clone()).await {
+        let user_obj = match get_person(config.pg_pool.clone(), &user.username).await {
             Ok(x) => x,
             Err(e) => {
                 let error_uuid = Uuid::new_v4();
-                warn!("Sending internal server error because I cannot get projects from the DB: {e}. Error Code is {error_uuid}.");
+                warn!("Sending internal server error because I cannot get the logged in user from the DB: {e}. Error Code is {error_uuid}.");
                 return (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     InternalServerErrorTemplate { error_uuid },
@@ -158,11 +394,46 @@ pub(super) mod get {
                     .into_response();
             }
         };
-        let user_obj = match get_person(config.pg_pool.clone(), &user.username).await {
+        let person = match user_obj {
+            Some(x) => x,
+            // user is logged in but does not exist in DB, e.g. because the session is older than
+            // provisioning on login. Try to add them now.
+            None => match provision_user(config.clone(), &user.username).await {
+                Ok(Some(x)) => x,
+                // Tell the user to come back in the right amount of time.
+                Ok(None) => {
+                    warn!("Sending not_yet_synced template, because user {} logged in successfully but is not yet cached in our db.", user.username);
+                    return LandingNotYetSynced {
+                        username: user.username,
+                        retry_after: config.user_resync_interval,
+                    }
+                    .into_response();
+                }
+                Err(e) => {
+                    let error_uuid = Uuid::new_v4();
+                    warn!("Sending internal server error because I cannot provision the logged in user: {e}. Error Code is {error_uuid}.");
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        InternalServerErrorTemplate { error_uuid },
+                    )
+                        .into_response();
+                }
+            },
+        };
+
+        // get projects
+        let filter = match query.to_filter(&person) {
+            Some(x) => x,
+            None => {
+                warn!("Sending 400 because the project filter {query:?} is malformed.");
+                return StatusCode::BAD_REQUEST.into_response();
+            }
+        };
+        let (projects, total) = match search_projects(config.pg_pool.clone(), &filter).await {
             Ok(x) => x,
             Err(e) => {
                 let error_uuid = Uuid::new_v4();
-                warn!("Sending internal server error because I cannot get the logged in user from the DB: {e}. Error Code is {error_uuid}.");
+                warn!("Sending internal server error because I cannot get projects from the DB: {e}. Error Code is {error_uuid}.");
                 return (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     InternalServerErrorTemplate { error_uuid },
@@ -170,24 +441,16 @@ pub(super) mod get {
                     .into_response();
             }
         };
-        match user_obj {
-            Some(person) => LandingAsUser {
-                user: person,
-                projects,
-                element_server: config.matrix_client.element_server().to_owned(),
-            }
-            .into_response(),
-            // user exists in LDAP but does not yet exist in DB.
-            // Tell the user to come back in the right amount of time.
-            None => {
-                warn!("Sending not_yet_synced template, because user {} logged in successfully but is not yet cached in our db.", user.username);
-                LandingNotYetSynced {
-                    username: user.username,
-                    retry_after: config.user_resync_interval,
-                }
-                .into_response()
-            }
+        LandingAsUser {
+            user: person,
+            projects,
+            element_server: config.matrix_client.element_server().to_owned(),
+            query,
+            total,
+            page_count: ((total + PROJECT_PAGE_SIZE - 1) / PROJECT_PAGE_SIZE).max(1),
+            csrf_token,
         }
+        .into_response()
     }
 
     #[derive(askama_axum::Template, Debug)]
@@ -229,22 +492,15 @@ pub(super) mod get {
                     .into_response();
             }
         };
-        let project = match get_project(&mut con, project_id).await {
-            Ok(Some(x)) => x,
-            Ok(None) => {
-                info!("Project {project_id} was requested but does not exist.");
-                return (StatusCode::NOT_FOUND).into_response();
-            }
-            Err(e) => {
-                let error_uuid = Uuid::new_v4();
-                warn!("Sending internal server error because I cannot get project {project_id} by id: {e}. {error_uuid}");
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    InternalServerErrorTemplate { error_uuid },
-                )
-                    .into_response();
-            }
-        };
+        let (project, _) =
+            match get_project_with_access(&mut con, &user, project_id, ProjectAccess::NameOnly)
+                .await
+            {
+                Ok(x) => x,
+                Err(e) => {
+                    return e;
+                }
+            };
         project
             .display_header_only(
                 &user,
@@ -284,34 +540,17 @@ pub(super) mod get {
                     .into_response();
             }
         };
-        let project = match get_project(&mut con, project_id).await {
-            Ok(Some(x)) => x,
-            Ok(None) => {
-                info!("Project {project_id} was requested but does not exist.");
-                return (StatusCode::NOT_FOUND).into_response();
-            }
-            Err(e) => {
-                let error_uuid = Uuid::new_v4();
-                warn!("Sending internal server error because I cannot get project {project_id} by id: {e}. {error_uuid}");
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    InternalServerErrorTemplate { error_uuid },
-                )
-                    .into_response();
-            }
-        };
-        // find out whether the user is an admin of this group
-        let permission = match user.global_permission {
-            UserPermission::Admin => UserPermission::Admin,
-            UserPermission::User => match project.local_permission_for_user(&user) {
-                Some(x) => x,
-                None => UserPermission::User,
-            },
-        };
+        let (project, access) =
+            match get_project_with_access(&mut con, &user, project_id, ProjectAccess::Read).await {
+                Ok(x) => x,
+                Err(e) => {
+                    return e;
+                }
+            };
         // template it with header_only
         project
             .display_with_users(
-                permission,
+                access.view_permission(),
                 config.matrix_client.element_server().to_owned(),
             )
             .into_response()
@@ -339,7 +578,7 @@ pub(super) mod get {
         Extension(config): Extension<Arc<Config>>,
         Path(project_id): Path<i32>
     ) -> impl IntoResponse {
-        let _requester = match get_user_from_session(auth_session, config.clone()).await {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
             Ok(x) => x,
             Err(e) => {
                 return e.into_response();
@@ -364,20 +603,17 @@ pub(super) mod get {
                     .into_response();
             }
         };
-        let project = match get_project(&mut con, project_id).await {
-            Ok(Some(x)) => x,
-            Ok(None) => {
-                info!("Project {project_id} was requested but does not exist.");
-                return (StatusCode::NOT_FOUND).into_response();
-            }
+        let (project, _) = match get_project_with_access(
+            &mut con,
+            &requester,
+            project_id,
+            ProjectAccess::Maintainer,
+        )
+        .await
+        {
+            Ok(x) => x,
             Err(e) => {
-                let error_uuid = Uuid::new_v4();
-                warn!("Sending internal server error because I cannot get project {project_id} by id: {e}. {error_uuid}");
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    InternalServerErrorTemplate { error_uuid },
-                )
-                    .into_response();
+                return e;
             }
         };
         debug!("Returning new ProjectRenameTemplate");
@@ -414,60 +650,30 @@ pub(super) mod get {
                     .into_response();
             }
         };
-        let project = match get_project(&mut con, project_id).await {
-            Ok(Some(x)) => x,
-            Ok(None) => {
-                info!("Project {project_id} was requested but does not exist.");
-                return (StatusCode::NOT_FOUND).into_response();
-            }
+        let (project, access) = match get_project_with_access(
+            &mut con,
+            &requester,
+            project_id,
+            ProjectAccess::NameOnly,
+        )
+        .await
+        {
+            Ok(x) => x,
             Err(e) => {
-                let error_uuid = Uuid::new_v4();
-                warn!("Sending internal server error because I cannot get project {project_id} by id: {e}. {error_uuid}");
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    InternalServerErrorTemplate { error_uuid },
-                )
-                    .into_response();
+                return e;
             }
         };
-        let view_permission = UserPermission::new_from_is_admin(
-            requester.is_global_admin() || project.local_permission_for_user(&requester).is_some_and(|x| x.is_admin()));
         debug!("Returning new project name");
-        project.display_name(&view_permission).into_response()
-    }
-}
-
-pub(super) mod post {
-    use std::sync::Arc;
-
-    use askama_axum::IntoResponse;
-    use axum::{extract::Path, http::StatusCode, Extension, Form};
-    use serde::Deserialize;
-    use tracing::{warn, Level};
-    use uuid::Uuid;
-
-    use crate::{
-        actions::{
-            add_member_to_project, create_project, set_member_permission, AddMemberError, CreateProjectError, RenameProjectError, SetPermissionError
-        },
-        config::Config,
-        db::get_persons_with_similar_name,
-        types::UserPermission,
-        web_server::{
-            login::AuthSession, protected::get_user_from_session, InternalServerErrorTemplate,
-        },
-    };
-
-    #[derive(Deserialize, Debug)]
-    pub(super) struct NewProjectData {
-        name: String,
+        project
+            .display_name(&access.view_permission())
+            .into_response()
     }
 
-    #[tracing::instrument(level=Level::TRACE, skip(auth_session, config))]
-    pub(super) async fn project_new(
+    /// Get the metadata of a project by ID
+    pub(super) async fn project_metadata_template(
         auth_session: AuthSession,
         Extension(config): Extension<Arc<Config>>,
-        Form(new_form): Form<NewProjectData>,
+        Path(project_id): Path<i32>,
     ) -> impl IntoResponse {
         let requester = match get_user_from_session(auth_session, config.clone()).await {
             Ok(x) => x,
@@ -475,57 +681,46 @@ pub(super) mod post {
                 return e.into_response();
             }
         };
-        match create_project(config.clone(), &requester, new_form.name).await {
-            Ok(x) => {
-                // only global admins can create projects, so we template it with admin privileges
-                x.display_with_users(
-                    UserPermission::Admin,
-                    config.matrix_client.element_server().to_owned(),
-                )
-                .into_response()
-            }
-            Err(CreateProjectError::RequesterHasNoPermission) => {
-                warn!(
-                    "User {} tried to create a project, but is not a global admin.",
-                    requester.name,
-                );
-                return StatusCode::UNAUTHORIZED.into_response();
-            }
-            Err(CreateProjectError::DB(e)) => {
-                let error_uuid = Uuid::new_v4();
-                warn!("Sending internal Server error because I cannot insert a new project: {e}. {error_uuid}");
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    InternalServerErrorTemplate { error_uuid },
-                )
-                    .into_response();
-            }
-            Err(CreateProjectError::Matrix(e)) => {
+
+        let mut con = match config
+            .pg_pool
+            .clone()
+            .acquire()
+            .await
+            .map_err(DBError::CannotStartTransaction)
+        {
+            Ok(x) => x,
+            Err(e) => {
                 let error_uuid = Uuid::new_v4();
-                warn!("Sending internal server error because communication with Matrix failed while adding a new project: {e}. {error_uuid}");
+                warn!("Sending internal server error because I cannot start a transaction: {e}. {error_uuid}");
                 return (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     InternalServerErrorTemplate { error_uuid },
                 )
                     .into_response();
             }
-        }
+        };
+        let (project, access) =
+            match get_project_with_access(&mut con, &requester, project_id, ProjectAccess::Read)
+                .await
+            {
+                Ok(x) => x,
+                Err(e) => {
+                    return e;
+                }
+            };
+        debug!("Returning project metadata");
+        project
+            .display_metadata(&access.view_permission())
+            .into_response()
     }
 
-    #[derive(Deserialize)]
-    pub(super) struct ProjectNewMemberFormData {
-        username: String,
-    }
-    /// Add an existing user as member to an existing project
-    pub(super) async fn project_new_member(
+    /// Get the form editing the metadata of a project
+    pub(super) async fn project_metadata_edit_template(
         auth_session: AuthSession,
         Extension(config): Extension<Arc<Config>>,
         Path(project_id): Path<i32>,
-        Form(form): Form<ProjectNewMemberFormData>,
     ) -> impl IntoResponse {
-        // get the user this name belongs to
-        // get the project from ID
-        // add that uer to the given project
         let requester = match get_user_from_session(auth_session, config.clone()).await {
             Ok(x) => x,
             Err(e) => {
@@ -533,51 +728,1205 @@ pub(super) mod post {
             }
         };
 
-        match add_member_to_project(config.clone(), &requester, &form.username, project_id).await {
-            Ok((new_member, project)) => {
-                let requester_is_now_admin = match project.local_permission_for_user(&requester) {
-                    Some(UserPermission::Admin) => true,
-                    Some(UserPermission::User) => requester.is_global_admin(),
-                    None => requester.is_global_admin(),
-                };
-                new_member
-                    .display(
-                        project.db_id(),
-                        UserPermission::new_from_is_admin(requester_is_now_admin),
-                        new_member.global_permission,
-                    )
-                    .into_response()
-            }
-            Err(AddMemberError::ProjectDoesNotExist) => {
-                warn!("Sending 404 because no project with id {project_id} exists.");
-                StatusCode::NOT_FOUND.into_response()
-            }
-            Err(AddMemberError::PersonDoesNotExist) => {
-                warn!(
-                    "Sending 400 because the person {} does not exist.",
-                    form.username
-                );
-                StatusCode::BAD_REQUEST.into_response()
-            }
-            Err(AddMemberError::RequesterHasNoPermission(project_name)) => {
-                warn!(
-                    "Sending 401 because user {} is not authorized to add member to group {}.",
-                    requester.name, project_name
-                );
-                StatusCode::UNAUTHORIZED.into_response()
-            }
-            Err(AddMemberError::DB(e)) => {
+        let mut con = match config
+            .pg_pool
+            .clone()
+            .acquire()
+            .await
+            .map_err(DBError::CannotStartTransaction)
+        {
+            Ok(x) => x,
+            Err(e) => {
                 let error_uuid = Uuid::new_v4();
-                warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
-                (
+                warn!("Sending internal server error because I cannot start a transaction: {e}. {error_uuid}");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response();
+            }
+        };
+        let (project, _) = match get_project_with_access(
+            &mut con,
+            &requester,
+            project_id,
+            ProjectAccess::Maintainer,
+        )
+        .await
+        {
+            Ok(x) => x,
+            Err(e) => {
+                return e;
+            }
+        };
+        debug!("Returning project metadata edit form");
+        project.display_metadata_edit().into_response()
+    }
+
+    /// Get an individual project by ID, show its diary entries.
+    pub(super) async fn project_with_entries(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path(project_id): Path<i32>,
+    ) -> impl IntoResponse {
+        let user = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(response) => {
+                return response.into_response();
+            }
+        };
+
+        let mut con = match config
+            .pg_pool
+            .clone()
+            .acquire()
+            .await
+            .map_err(DBError::CannotStartTransaction)
+        {
+            Ok(x) => x,
+            Err(e) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because I cannot start a transaction: {e}. {error_uuid}");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response();
+            }
+        };
+        let (project, _) =
+            match get_project_with_access(&mut con, &user, project_id, ProjectAccess::Read).await {
+                Ok(x) => x,
+                Err(e) => {
+                    return e;
+                }
+            };
+        let entries = match get_entries(&mut con, project_id).await {
+            Ok(x) => x,
+            Err(e) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because I cannot get the entries of project {project_id}: {e}. {error_uuid}");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response();
+            }
+        };
+        project
+            .display_with_entries(
+                &entries,
+                &user,
+                config.matrix_client.element_server().to_owned(),
+            )
+            .into_response()
+    }
+
+    /// Get the Protokoll of a project as PDF/A-3 document.
+    pub(super) async fn project_protokoll_pdf(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path(project_id): Path<i32>,
+    ) -> impl IntoResponse {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(response) => {
+                return response.into_response();
+            }
+        };
+
+        let mut con = match config
+            .pg_pool
+            .clone()
+            .acquire()
+            .await
+            .map_err(DBError::CannotStartTransaction)
+        {
+            Ok(x) => x,
+            Err(e) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because I cannot start a transaction: {e}. {error_uuid}");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response();
+            }
+        };
+        let (project, _) =
+            match get_project_with_access(&mut con, &requester, project_id, ProjectAccess::Read)
+                .await
+            {
+                Ok(x) => x,
+                Err(e) => {
+                    return e;
+                }
+            };
+        let entries = match get_entries(&mut con, project_id).await {
+            Ok(x) => x,
+            Err(e) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because I cannot get the entries of project {project_id}: {e}. {error_uuid}");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response();
+            }
+        };
+        let pdf = match render_protokoll(&project, &entries) {
+            Ok(x) => x,
+            Err(e) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because I cannot render the Protokoll of project {project_id}: {e}. {error_uuid}");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response();
+            }
+        };
+        (
+            [
+                (header::CONTENT_TYPE, "application/pdf".to_owned()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"protokoll-{project_id}.pdf\""),
+                ),
+            ],
+            pdf,
+        )
+            .into_response()
+    }
+
+    #[derive(askama_axum::Template)]
+    #[template(path = "protokoll/new.html")]
+    pub(super) struct EntryNewTemplate {
+        project_id: i32,
+    }
+    pub(super) async fn entry_new_template(Path(project_id): Path<i32>) -> impl IntoResponse {
+        EntryNewTemplate { project_id }.into_response()
+    }
+
+    /// Get the project and the entry from the DB, making sure that the entry belongs to the
+    /// project, creating relevant error returns
+    async fn get_project_and_entry(
+        config: Arc<Config>,
+        user: &Person<DbNoMatrix>,
+        project_id: i32,
+        entry_id: i32,
+        required: ProjectAccess,
+    ) -> Result<(Project<FullId>, ProjectAccess, Entry<DbNoMatrix>), axum::response::Response> {
+        let mut con = match config
+            .pg_pool
+            .clone()
+            .acquire()
+            .await
+            .map_err(DBError::CannotStartTransaction)
+        {
+            Ok(x) => x,
+            Err(e) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because I cannot start a transaction: {e}. {error_uuid}");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response());
+            }
+        };
+        let (project, access) =
+            get_project_with_access(&mut con, user, project_id, required).await?;
+        match get_entry(&mut con, entry_id).await {
+            Ok(Some(x)) if x.project_id() == project_id => Ok((project, access, x)),
+            Ok(_) => {
+                info!("Entry {entry_id} in project {project_id} was requested but does not exist.");
+                Err((StatusCode::NOT_FOUND).into_response())
+            }
+            Err(e) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because I cannot get entry {entry_id} by id: {e}. {error_uuid}");
+                Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response())
+            }
+        }
+    }
+
+    pub(super) async fn entry_show(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path((project_id, entry_id)): Path<(i32, i32)>,
+    ) -> impl IntoResponse {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(e) => {
+                return e.into_response();
+            }
+        };
+        let (_, access, entry) = match get_project_and_entry(
+            config,
+            &requester,
+            project_id,
+            entry_id,
+            ProjectAccess::Read,
+        )
+        .await
+        {
+            Ok(x) => x,
+            Err(e) => {
+                return e;
+            }
+        };
+        let may_change = may_change_entry(&requester, access, &entry);
+        entry
+            .display(UserPermission::new_from_is_admin(may_change))
+            .into_response()
+    }
+
+    #[derive(askama_axum::Template)]
+    #[template(path = "protokoll/edit.html")]
+    pub(super) struct EntryEditTemplate {
+        entry: Entry<DbNoMatrix>,
+    }
+    pub(super) async fn entry_edit_template(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path((project_id, entry_id)): Path<(i32, i32)>,
+    ) -> impl IntoResponse {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(e) => {
+                return e.into_response();
+            }
+        };
+        let (project, access, entry) = match get_project_and_entry(
+            config,
+            &requester,
+            project_id,
+            entry_id,
+            ProjectAccess::Member,
+        )
+        .await
+        {
+            Ok(x) => x,
+            Err(e) => {
+                return e;
+            }
+        };
+        if !may_change_entry(&requester, access, &entry) {
+            warn!(
+                "Sending 401 because user {} is not authorized to edit entry {entry_id} in group {}.",
+                requester.name, project.name
+            );
+            return StatusCode::UNAUTHORIZED.into_response();
+        };
+        debug!("Returning new EntryEditTemplate");
+        EntryEditTemplate { entry }.into_response()
+    }
+
+    #[derive(askama_axum::Template)]
+    #[template(path = "token/complete.html", escape = "none")]
+    struct TokensPage {
+        user: Person<DbNoMatrix>,
+        tokens: Vec<ApiToken<DbNoMatrix>>,
+        csrf_token: String,
+    }
+
+    /// Show the API tokens of the logged in user
+    pub(super) async fn tokens_page(
+        auth_session: AuthSession,
+        session: Session,
+        Extension(config): Extension<Arc<Config>>,
+    ) -> impl IntoResponse {
+        let csrf_token = match csrf_token(&session).await {
+            Ok(x) => x,
+            Err(response) => return response,
+        };
+        let user = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(response) => {
+                return response.into_response();
+            }
+        };
+
+        let tokens = match get_api_tokens(config.pg_pool.clone(), user.db_id()).await {
+            Ok(x) => x,
+            Err(e) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because I cannot get the API tokens of {}: {e}. {error_uuid}", user.name);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response();
+            }
+        };
+        TokensPage {
+            user,
+            tokens,
+            csrf_token,
+        }
+        .into_response()
+    }
+
+    #[derive(askama_axum::Template)]
+    #[template(path = "audit/list.html")]
+    struct AuditList {
+        events: Vec<AuditEvent<DbNoMatrix>>,
+    }
+
+    /// Get the history of a project
+    pub(super) async fn project_history(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path(project_id): Path<i32>,
+    ) -> impl IntoResponse {
+        let user = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(response) => {
+                return response.into_response();
+            }
+        };
+
+        let mut con = match config
+            .pg_pool
+            .clone()
+            .acquire()
+            .await
+            .map_err(DBError::CannotStartTransaction)
+        {
+            Ok(x) => x,
+            Err(e) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because I cannot start a transaction: {e}. {error_uuid}");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response();
+            }
+        };
+        let (_, _) =
+            match get_project_with_access(&mut con, &user, project_id, ProjectAccess::Maintainer)
+                .await
+            {
+                Ok(x) => x,
+                Err(e) => {
+                    return e;
+                }
+            };
+
+        let filter = AuditEventFilter {
+            project_id: Some(project_id),
+            ..Default::default()
+        };
+        match get_audit_events(config.pg_pool.clone(), &filter).await {
+            Ok(events) => AuditList { events }.into_response(),
+            Err(e) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because I cannot get the history of project {project_id}: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    #[derive(askama_axum::Template)]
+    #[template(path = "audit/complete.html")]
+    struct AuditPage {
+        user: Person<DbNoMatrix>,
+        events: Vec<AuditEvent<DbNoMatrix>>,
+        limit: i64,
+        // the filter, as entered by the user
+        actor: String,
+        project_id: String,
+        from: String,
+        until: String,
+        csrf_token: String,
+    }
+
+    /// All fields may be empty, meaning "do not filter on this"
+    #[derive(Deserialize, Debug, Default)]
+    pub(super) struct AuditQuery {
+        #[serde(default)]
+        actor: String,
+        #[serde(default)]
+        project_id: String,
+        /// `YYYY-MM-DD`
+        #[serde(default)]
+        from: String,
+        /// `YYYY-MM-DD`, inclusive
+        #[serde(default)]
+        until: String,
+    }
+    impl AuditQuery {
+        /// None if a field cannot be parsed
+        fn to_filter(&self) -> Option<AuditEventFilter> {
+            let parse_date =
+                |x: &str| Date::parse(x, format_description!("[year]-[month]-[day]")).ok();
+            Some(AuditEventFilter {
+                actor_name: match self.actor.trim() {
+                    "" => None,
+                    x => Some(x.to_owned()),
+                },
+                project_id: match self.project_id.trim() {
+                    "" => None,
+                    x => Some(x.parse().ok()?),
+                },
+                from: match self.from.as_str() {
+                    "" => None,
+                    x => Some(parse_date(x)?.midnight().assume_utc()),
+                },
+                // until the end of that day
+                until: match self.until.as_str() {
+                    "" => None,
+                    x => {
+                        let date = parse_date(x)?;
+                        Some(date.next_day().unwrap_or(date).midnight().assume_utc())
+                    }
+                },
+            })
+        }
+    }
+
+    /// Get the history of all projects; only for global admins
+    pub(super) async fn audit_page(
+        auth_session: AuthSession,
+        session: Session,
+        Extension(config): Extension<Arc<Config>>,
+        Query(query): Query<AuditQuery>,
+    ) -> impl IntoResponse {
+        let csrf_token = match csrf_token(&session).await {
+            Ok(x) => x,
+            Err(response) => return response,
+        };
+        let user = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(response) => {
+                return response.into_response();
+            }
+        };
+        if !may_administer_instance(&user) {
+            warn!(
+                "Sending 401 because user {} is not authorized to see the global history.",
+                user.name
+            );
+            return StatusCode::UNAUTHORIZED.into_response();
+        };
+
+        let filter = match query.to_filter() {
+            Some(x) => x,
+            None => {
+                warn!("Sending 400 because the history filter {query:?} is malformed.");
+                return StatusCode::BAD_REQUEST.into_response();
+            }
+        };
+        match get_audit_events(config.pg_pool.clone(), &filter).await {
+            Ok(events) => AuditPage {
+                user,
+                events,
+                limit: AUDIT_EVENT_LIMIT,
+                actor: query.actor,
+                project_id: query.project_id,
+                from: query.from,
+                until: query.until,
+                csrf_token,
+            }
+            .into_response(),
+            Err(e) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because I cannot get the history: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        use crate::types::UserPermission;
+
+        #[test]
+        fn root_query_urls() {
+            assert_eq!(RootQuery::default().url(), "/");
+            let query = RootQuery {
+                q: "Dach & Fach".to_owned(),
+                mine: true,
+                status: "done".to_owned(),
+                page: Some(2),
+                ..Default::default()
+            };
+            assert_eq!(
+                query.url(),
+                "/?q=Dach%20%26%20Fach&mine=true&status=done&page=2"
+            );
+            assert_eq!(
+                query.page_url(1),
+                "/?q=Dach%20%26%20Fach&mine=true&status=done"
+            );
+            // toggling archived projects starts on the first page again
+            assert_eq!(
+                query.archived_url(true),
+                "/?archived=true&q=Dach%20%26%20Fach&mine=true&status=done"
+            );
+
+            let malformed = RootQuery {
+                status: "cancelled".to_owned(),
+                ..Default::default()
+            };
+            let user = Person::new(4, "Beth".to_owned(), UserPermission::User, None, None);
+            assert!(malformed.to_filter(&user).is_none());
+            let filter = query.to_filter(&user).unwrap();
+            assert_eq!(filter.member_id, Some(4));
+            assert_eq!(filter.admin_id, None);
+            assert_eq!(filter.page, 1);
+        }
+
+        #[test]
+        fn audit_query_to_filter() {
+            let filter = AuditQuery::default().to_filter().unwrap();
+            assert!(filter.actor_name.is_none());
+            assert!(filter.project_id.is_none());
+
+            let filter = AuditQuery {
+                actor: " Beth ".to_owned(),
+                project_id: "2".to_owned(),
+                from: "2024-03-01".to_owned(),
+                until: "2024-03-31".to_owned(),
+            }
+            .to_filter()
+            .unwrap();
+            assert_eq!(filter.actor_name.as_deref(), Some("Beth"));
+            assert_eq!(filter.project_id, Some(2));
+            assert_eq!(
+                filter.from,
+                Some(time::macros::datetime!(2024-03-01 00:00 UTC))
+            );
+            assert_eq!(
+                filter.until,
+                Some(time::macros::datetime!(2024-04-01 00:00 UTC))
+            );
+
+            let malformed = AuditQuery {
+                project_id: "zwei".to_owned(),
+                ..Default::default()
+            };
+            assert!(malformed.to_filter().is_none());
+        }
+    }
+}
+
+pub(super) mod post {
+    use std::sync::Arc;
+
+    use askama_axum::IntoResponse;
+    use axum::{extract::Path, http::StatusCode, Extension, Form};
+    use serde::Deserialize;
+    use time::{macros::format_description, Date};
+    use tracing::{warn, Level};
+    use uuid::Uuid;
+
+    use crate::{
+        actions::{
+            add_entry, add_member_to_project, archive_project, create_api_token, create_project,
+            edit_entry, link_ldap_group, set_member_permission, set_project_visibility,
+            update_project_metadata, AddEntryError, AddMemberError, ArchiveProjectError,
+            CreateApiTokenError, CreateProjectError, EditEntryError, LdapGroupError,
+            RenameProjectError, SetPermissionError, SetVisibilityError, UpdateProjectMetadataError,
+        },
+        config::Config,
+        db::get_persons_with_similar_name,
+        permissions::project_access,
+        types::{
+            ApiToken, DbNoMatrix, ProjectMetadata, ProjectRole, ProjectStatus, ProjectVisibility,
+            UserPermission,
+        },
+        web_server::{
+            login::AuthSession, protected::get_user_from_session, InternalServerErrorTemplate,
+        },
+    };
+
+    #[derive(Deserialize, Debug)]
+    pub(super) struct NewProjectData {
+        name: String,
+    }
+
+    #[tracing::instrument(level=Level::TRACE, skip(auth_session, config))]
+    pub(super) async fn project_new(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Form(new_form): Form<NewProjectData>,
+    ) -> impl IntoResponse {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(e) => {
+                return e.into_response();
+            }
+        };
+        match create_project(config.clone(), &requester, new_form.name).await {
+            Ok(x) => {
+                // only global admins can create projects, so we template it with admin privileges
+                x.display_with_users(
+                    UserPermission::Admin,
+                    config.matrix_client.element_server().to_owned(),
+                )
+                .into_response()
+            }
+            Err(CreateProjectError::RequesterHasNoPermission) => {
+                warn!(
+                    "User {} tried to create a project, but is not a global admin.",
+                    requester.name,
+                );
+                return StatusCode::UNAUTHORIZED.into_response();
+            }
+            Err(CreateProjectError::DB(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal Server error because I cannot insert a new project: {e}. {error_uuid}");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response();
+            }
+            Err(CreateProjectError::Matrix(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because communication with Matrix failed while adding a new project: {e}. {error_uuid}");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response();
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct ProjectNewMemberFormData {
+        username: String,
+    }
+    /// Add an existing user as member to an existing project
+    pub(super) async fn project_new_member(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path(project_id): Path<i32>,
+        Form(form): Form<ProjectNewMemberFormData>,
+    ) -> impl IntoResponse {
+        // get the user this name belongs to
+        // get the project from ID
+        // add that uer to the given project
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(e) => {
+                return e.into_response();
+            }
+        };
+
+        match add_member_to_project(config.clone(), &requester, &form.username, project_id).await {
+            Ok((new_member, project)) => new_member
+                .display(
+                    project.db_id(),
+                    project_access(&requester, &project).view_permission(),
+                    ProjectRole::Member,
+                )
+                .into_response(),
+            Err(AddMemberError::ProjectDoesNotExist) => {
+                warn!("Sending 404 because no project with id {project_id} exists.");
+                StatusCode::NOT_FOUND.into_response()
+            }
+            Err(AddMemberError::ArchivedProject(project_name)) => {
+                super::archived_project_response(&requester, &project_name)
+            }
+            Err(AddMemberError::PersonDoesNotExist) => {
+                warn!(
+                    "Sending 400 because the person {} does not exist.",
+                    form.username
+                );
+                StatusCode::BAD_REQUEST.into_response()
+            }
+            Err(AddMemberError::RequesterHasNoPermission(project_name)) => {
+                warn!(
+                    "Sending 401 because user {} is not authorized to add member to group {}.",
+                    requester.name, project_name
+                );
+                StatusCode::UNAUTHORIZED.into_response()
+            }
+            Err(AddMemberError::DB(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+            Err(AddMemberError::Matrix(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because communication with Matrix failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub(super) struct UserSearchFormData {
+        username: String,
+    }
+    #[derive(askama_axum::Template)]
+    #[template(path = "search/user_results.html")]
+    pub(super) struct UserSearchResultsTemplate {
+        results: Vec<(String, String)>,
+    }
+
+    #[tracing::instrument(level=Level::TRACE, skip(auth_session, config))]
+    pub(super) async fn search_user_results(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Form(form): Form<UserSearchFormData>,
+    ) -> impl IntoResponse {
+        // only logged-in users may search other users
+        let _user = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(response) => {
+                return response.into_response();
+            }
+        };
+
+        // get users whith name similar to the form.username
+        let persons = match get_persons_with_similar_name(config.pg_pool.clone(), &form.username)
+            .await
+        {
+            Ok(x) => x,
+            Err(e) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because I cannot get persons with similar name: {e}. {error_uuid}");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response();
+            }
+        };
+        let results = persons
+            .into_iter()
+            .map(|p| {
+                (
+                    format!(
+                        "{} {} ({})",
+                        p.firstname.unwrap_or("".to_owned()),
+                        p.surname.unwrap_or("".to_owned()),
+                        p.name
+                    ),
+                    p.name,
+                )
+            })
+            .collect();
+        UserSearchResultsTemplate { results }.into_response()
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub(crate) struct SetMemberPermissionForm {
+        username: String,
+        role: String,
+    }
+    pub(super) async fn project_set_member_permission(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path(project_id): Path<i32>,
+        Form(form): Form<SetMemberPermissionForm>,
+    ) -> impl IntoResponse {
+        // get the user this name belongs to
+        // get the project from ID
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(e) => {
+                return e.into_response();
+            }
+        };
+
+        let new_role = match ProjectRole::try_from(form.role.as_str()) {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Sending 400 because the project role {e} is unknown.");
+                return StatusCode::BAD_REQUEST.into_response();
+            }
+        };
+        match set_member_permission(config, &requester, &form.username, project_id, new_role).await
+        {
+            Ok((changed_user, project)) => {
+                let requester_current_permission =
+                    project_access(&requester, &project).view_permission();
+                changed_user
+                    .display(project_id, requester_current_permission, new_role)
+                    .into_response()
+            }
+            Err(SetPermissionError::ProjectDoesNotExist) => {
+                warn!("Sending 404 because no project with id {project_id} exists.");
+                StatusCode::NOT_FOUND.into_response()
+            }
+            Err(SetPermissionError::ArchivedProject(project_name)) => {
+                super::archived_project_response(&requester, &project_name)
+            }
+            Err(SetPermissionError::PersonDoesNotExist) => {
+                warn!(
+                    "Sending 400 because the person {} does not exist.",
+                    form.username
+                );
+                StatusCode::BAD_REQUEST.into_response()
+            }
+            Err(SetPermissionError::RequesterHasNoPermission(project_name)) => {
+                warn!(
+                    "Sending 401 because user {} is not authorized to add member to group {}.",
+                    requester.name, project_name
+                );
+                StatusCode::UNAUTHORIZED.into_response()
+            }
+            Err(SetPermissionError::DB(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+            Err(SetPermissionError::Matrix(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because communication with Matrix failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    /// Dates are `YYYY-MM-DD`; empty fields are unset
+    #[derive(Deserialize, Debug)]
+    pub(crate) struct ProjectMetadataForm {
+        description: String,
+        status: String,
+        start_date: String,
+        end_date: String,
+        project_number: String,
+    }
+    impl ProjectMetadataForm {
+        /// None if a field cannot be parsed
+        fn to_metadata(&self) -> Option<ProjectMetadata> {
+            let parse_date = |x: &str| match x {
+                "" => Some(None),
+                x => Date::parse(x, format_description!("[year]-[month]-[day]"))
+                    .ok()
+                    .map(Some),
+            };
+            Some(ProjectMetadata {
+                description: self.description.clone(),
+                status: ProjectStatus::try_from(self.status.as_str()).ok()?,
+                start_date: parse_date(&self.start_date)?,
+                end_date: parse_date(&self.end_date)?,
+                project_number: match self.project_number.trim() {
+                    "" => None,
+                    x => Some(x.to_owned()),
+                },
+            })
+        }
+    }
+    pub(super) async fn project_metadata_edit(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path(project_id): Path<i32>,
+        Form(data): Form<ProjectMetadataForm>,
+    ) -> impl IntoResponse {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(e) => {
+                return e.into_response();
+            }
+        };
+        let metadata = match data.to_metadata() {
+            Some(x) => x,
+            None => {
+                warn!("Sending 400 because the project metadata {data:?} is malformed.");
+                return StatusCode::BAD_REQUEST.into_response();
+            }
+        };
+
+        match update_project_metadata(config.clone(), &requester, project_id, metadata).await {
+            // only admins may change the metadata, so they may also do so again
+            Ok(project) => project
+                .display_metadata(&UserPermission::Admin)
+                .into_response(),
+            Err(UpdateProjectMetadataError::ProjectDoesNotExist) => {
+                warn!("Sending 404 because no project with id {project_id} exists.");
+                StatusCode::NOT_FOUND.into_response()
+            }
+            Err(UpdateProjectMetadataError::ArchivedProject(project_name)) => {
+                super::archived_project_response(&requester, &project_name)
+            }
+            Err(UpdateProjectMetadataError::DatesOutOfOrder) => {
+                warn!("Sending 400 because the start date of project {project_id} would be after its end date.");
+                StatusCode::BAD_REQUEST.into_response()
+            }
+            Err(UpdateProjectMetadataError::RequesterHasNoPermission(project_name)) => {
+                warn!(
+                    "Sending 401 because user {} is not authorized to change the metadata of group {}.",
+                    requester.name, project_name
+                );
+                StatusCode::UNAUTHORIZED.into_response()
+            }
+            Err(UpdateProjectMetadataError::Matrix(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because communication with Matrix failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+            Err(UpdateProjectMetadataError::DB(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub(crate) struct ProjectRenameForm {
+        name: String,
+    }
+    pub(super) async fn project_rename(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path(project_id): Path<i32>,
+        Form(data): Form<ProjectRenameForm>,
+    ) -> impl IntoResponse {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(e) => {
+                return e.into_response();
+            }
+        };
+
+        match crate::actions::rename_project(config.clone(), &requester, project_id, data.name.clone()).await {
+            Ok(mut project) => {
+                let requester_current_permission =
+                    project_access(&requester, &project).view_permission();
+                // set the name to display back to the user
+                project.name = data.name;
+                project
+                    .display_name(&requester_current_permission)
+                    .into_response()
+            }
+            Err(RenameProjectError::ProjectDoesNotExist) => {
+                warn!("Sending 404 because no project with id {project_id} exists.");
+                StatusCode::NOT_FOUND.into_response()
+            }
+            Err(RenameProjectError::ArchivedProject(project_name)) => {
+                super::archived_project_response(&requester, &project_name)
+            }
+            Err(RenameProjectError::RequesterHasNoPermission(project_name)) => {
+                warn!(
+                    "Sending 401 because user {} is not authorized to add member to group {}.",
+                    requester.name, project_name
+                );
+                StatusCode::UNAUTHORIZED.into_response()
+            }
+            Err(RenameProjectError::Matrix(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because communication with Matrix failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+            Err(RenameProjectError::DB(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    pub(super) async fn project_archive(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path(project_id): Path<i32>,
+    ) -> impl IntoResponse {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(e) => {
+                return e.into_response();
+            }
+        };
+
+        match archive_project(config.clone(), &requester, project_id).await {
+            Ok(project) => project
+                .display_header_only(&requester, config.matrix_client.element_server().to_owned())
+                .into_response(),
+            Err(ArchiveProjectError::ProjectDoesNotExist) => {
+                warn!("Sending 404 because no project with id {project_id} exists.");
+                StatusCode::NOT_FOUND.into_response()
+            }
+            Err(ArchiveProjectError::ArchivedProject(project_name)) => {
+                super::archived_project_response(&requester, &project_name)
+            }
+            Err(ArchiveProjectError::RequesterHasNoPermission(project_name)) => {
+                warn!(
+                    "Sending 401 because user {} is not authorized to archive group {}.",
+                    requester.name, project_name
+                );
+                StatusCode::UNAUTHORIZED.into_response()
+            }
+            Err(ArchiveProjectError::Matrix(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because communication with Matrix failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+            Err(ArchiveProjectError::DB(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub(crate) struct ProjectVisibilityForm {
+        visibility: String,
+    }
+    pub(super) async fn project_set_visibility(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path(project_id): Path<i32>,
+        Form(data): Form<ProjectVisibilityForm>,
+    ) -> impl IntoResponse {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(e) => {
+                return e.into_response();
+            }
+        };
+        let visibility = match ProjectVisibility::try_from(data.visibility.as_str()) {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Sending 400 because the project visibility {e} is unknown.");
+                return StatusCode::BAD_REQUEST.into_response();
+            }
+        };
+
+        match set_project_visibility(config.clone(), &requester, project_id, visibility).await {
+            // only admins may change the visibility, so they still see everything
+            Ok(project) => project
+                .display_with_users(
+                    UserPermission::Admin,
+                    config.matrix_client.element_server().to_owned(),
+                )
+                .into_response(),
+            Err(SetVisibilityError::ProjectDoesNotExist) => {
+                warn!("Sending 404 because no project with id {project_id} exists.");
+                StatusCode::NOT_FOUND.into_response()
+            }
+            Err(SetVisibilityError::ArchivedProject(project_name)) => {
+                super::archived_project_response(&requester, &project_name)
+            }
+            Err(SetVisibilityError::RequesterHasNoPermission(project_name)) => {
+                warn!(
+                    "Sending 401 because user {} is not authorized to change the visibility of group {}.",
+                    requester.name, project_name
+                );
+                StatusCode::UNAUTHORIZED.into_response()
+            }
+            Err(SetVisibilityError::DB(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
+                (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     InternalServerErrorTemplate { error_uuid },
                 )
                     .into_response()
             }
-            Err(AddMemberError::Matrix(e)) => {
+        }
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub(crate) struct LdapGroupForm {
+        pub(super) group_dn: String,
+    }
+    pub(super) async fn project_link_ldap_group(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path(project_id): Path<i32>,
+        Form(data): Form<LdapGroupForm>,
+    ) -> impl IntoResponse {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(e) => {
+                return e.into_response();
+            }
+        };
+
+        match link_ldap_group(config.clone(), &requester, project_id, &data.group_dn).await {
+            // only admins may link groups, so they still see everything
+            Ok(project) => project
+                .display_with_users(
+                    UserPermission::Admin,
+                    config.matrix_client.element_server().to_owned(),
+                )
+                .into_response(),
+            Err(LdapGroupError::ProjectDoesNotExist) => {
+                warn!("Sending 404 because no project with id {project_id} exists.");
+                StatusCode::NOT_FOUND.into_response()
+            }
+            Err(LdapGroupError::ArchivedProject(project_name)) => {
+                super::archived_project_response(&requester, &project_name)
+            }
+            Err(LdapGroupError::EmptyGroupDn) => {
+                warn!("Sending 400 because the DN of the LDAP group is empty.");
+                StatusCode::BAD_REQUEST.into_response()
+            }
+            Err(LdapGroupError::RequesterHasNoPermission(project_name)) => {
+                warn!(
+                    "Sending 401 because user {} is not authorized to link LDAP groups to group {}.",
+                    requester.name, project_name
+                );
+                StatusCode::UNAUTHORIZED.into_response()
+            }
+            Err(LdapGroupError::DB(e)) => {
                 let error_uuid = Uuid::new_v4();
-                warn!("Sending internal server error because communication with Matrix failed: {e}. {error_uuid}");
+                warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     InternalServerErrorTemplate { error_uuid },
@@ -588,74 +1937,78 @@ pub(super) mod post {
     }
 
     #[derive(Deserialize, Debug)]
-    pub(super) struct UserSearchFormData {
-        username: String,
+    pub(crate) struct NewTokenForm {
+        label: String,
+        /// `YYYY-MM-DD`; the token expires at the end of that day (UTC).
+        /// Empty if the token should never expire.
+        expires_at: String,
     }
     #[derive(askama_axum::Template)]
-    #[template(path = "search/user_results.html")]
-    pub(super) struct UserSearchResultsTemplate {
-        results: Vec<(String, String)>,
+    #[template(path = "token/created.html", escape = "none")]
+    pub(super) struct TokenCreatedTemplate {
+        token: ApiToken<DbNoMatrix>,
+        secret: String,
     }
-
-    #[tracing::instrument(level=Level::TRACE, skip(auth_session, config))]
-    pub(super) async fn search_user_results(
+    pub(super) async fn token_new(
         auth_session: AuthSession,
         Extension(config): Extension<Arc<Config>>,
-        Form(form): Form<UserSearchFormData>,
+        Form(form): Form<NewTokenForm>,
     ) -> impl IntoResponse {
-        // only logged-in users may search other users
-        let _user = match get_user_from_session(auth_session, config.clone()).await {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
             Ok(x) => x,
-            Err(response) => {
-                return response.into_response();
+            Err(e) => {
+                return e.into_response();
             }
         };
 
-        // get users whith name similar to the form.username
-        let persons = match get_persons_with_similar_name(config.pg_pool.clone(), &form.username)
-            .await
-        {
-            Ok(x) => x,
-            Err(e) => {
+        let expires_at = if form.expires_at.is_empty() {
+            None
+        } else {
+            match Date::parse(
+                &form.expires_at,
+                format_description!("[year]-[month]-[day]"),
+            ) {
+                Ok(x) => Some(x.next_day().unwrap_or(x).midnight().assume_utc()),
+                Err(e) => {
+                    warn!(
+                        "Sending 400 because {} is not a valid date: {e}",
+                        form.expires_at
+                    );
+                    return StatusCode::BAD_REQUEST.into_response();
+                }
+            }
+        };
+
+        match create_api_token(config.clone(), &requester, form.label, expires_at).await {
+            Ok((token, secret)) => TokenCreatedTemplate { token, secret }.into_response(),
+            Err(CreateApiTokenError::AlreadyExpired) => {
+                warn!("Sending 400 because the requested API token would already be expired.");
+                StatusCode::BAD_REQUEST.into_response()
+            }
+            Err(CreateApiTokenError::DB(e)) => {
                 let error_uuid = Uuid::new_v4();
-                warn!("Sending internal server error because I cannot get persons with similar name: {e}. {error_uuid}");
-                return (
+                warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
+                (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     InternalServerErrorTemplate { error_uuid },
                 )
-                    .into_response();
+                    .into_response()
             }
-        };
-        let results = persons
-            .into_iter()
-            .map(|p| {
-                (
-                    format!(
-                        "{} {} ({})",
-                        p.firstname.unwrap_or("".to_owned()),
-                        p.surname.unwrap_or("".to_owned()),
-                        p.name
-                    ),
-                    p.name,
-                )
-            })
-            .collect();
-        UserSearchResultsTemplate { results }.into_response()
+        }
     }
 
-    #[derive(Deserialize, Debug)]
-    pub(crate) struct SetMemberPermissionForm {
-        username: String,
-        is_local_admin: bool,
+    #[derive(Deserialize)]
+    pub(crate) struct EntryForm {
+        title: String,
+        body: String,
     }
-    pub(super) async fn project_set_member_permission(
+    /// Add a new entry to the diary of a project
+    pub(super) async fn entry_new(
         auth_session: AuthSession,
         Extension(config): Extension<Arc<Config>>,
         Path(project_id): Path<i32>,
-        Form(form): Form<SetMemberPermissionForm>,
+        Form(form): Form<EntryForm>,
     ) -> impl IntoResponse {
-        // get the user this name belongs to
-        // get the project from ID
         let requester = match get_user_from_session(auth_session, config.clone()).await {
             Ok(x) => x,
             Err(e) => {
@@ -663,40 +2016,32 @@ pub(super) mod post {
             }
         };
 
-        let new_perm = UserPermission::new_from_is_admin(form.is_local_admin);
-        match set_member_permission(config, &requester, &form.username, project_id, new_perm).await
+        match add_entry(
+            config.clone(),
+            &requester,
+            project_id,
+            form.title,
+            form.body,
+        )
+        .await
         {
-            Ok((changed_user, project)) => {
-                let requester_current_permission = UserPermission::new_from_is_admin(match project
-                    .local_permission_for_user(&requester)
-                {
-                    Some(UserPermission::Admin) => true,
-                    Some(UserPermission::User) => requester.is_global_admin(),
-                    None => requester.is_global_admin(),
-                });
-                changed_user
-                    .display(project_id, requester_current_permission, new_perm)
-                    .into_response()
-            }
-            Err(SetPermissionError::ProjectDoesNotExist) => {
+            // only admins may add entries, so we template it with admin privileges
+            Ok((entry, _project)) => entry.display(UserPermission::Admin).into_response(),
+            Err(AddEntryError::ProjectDoesNotExist) => {
                 warn!("Sending 404 because no project with id {project_id} exists.");
                 StatusCode::NOT_FOUND.into_response()
             }
-            Err(SetPermissionError::PersonDoesNotExist) => {
-                warn!(
-                    "Sending 400 because the person {} does not exist.",
-                    form.username
-                );
-                StatusCode::BAD_REQUEST.into_response()
+            Err(AddEntryError::ArchivedProject(project_name)) => {
+                super::archived_project_response(&requester, &project_name)
             }
-            Err(SetPermissionError::RequesterHasNoPermission(project_name)) => {
+            Err(AddEntryError::RequesterHasNoPermission(project_name)) => {
                 warn!(
-                    "Sending 401 because user {} is not authorized to add member to group {}.",
+                    "Sending 401 because user {} is not authorized to add entries to group {}.",
                     requester.name, project_name
                 );
                 StatusCode::UNAUTHORIZED.into_response()
             }
-            Err(SetPermissionError::DB(e)) => {
+            Err(AddEntryError::DB(e)) => {
                 let error_uuid = Uuid::new_v4();
                 warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
                 (
@@ -708,15 +2053,12 @@ pub(super) mod post {
         }
     }
 
-    #[derive(Deserialize)]
-    pub(crate) struct ProjectRenameForm {
-        name: String,
-    }
-    pub(super) async fn project_rename(
+    /// Change title and body of an existing entry
+    pub(super) async fn entry_edit(
         auth_session: AuthSession,
         Extension(config): Extension<Arc<Config>>,
-        Path(project_id): Path<i32>,
-        Form(data): Form<ProjectRenameForm>,
+        Path((project_id, entry_id)): Path<(i32, i32)>,
+        Form(form): Form<EntryForm>,
     ) -> impl IntoResponse {
         let requester = match get_user_from_session(auth_session, config.clone()).await {
             Ok(x) => x,
@@ -725,42 +2067,37 @@ pub(super) mod post {
             }
         };
 
-        match crate::actions::rename_project(config.clone(), &requester, project_id, data.name.clone()).await {
-            Ok(mut project) => {
-                let requester_current_permission = UserPermission::new_from_is_admin(match project
-                    .local_permission_for_user(&requester)
-                {
-                    Some(UserPermission::Admin) => true,
-                    Some(UserPermission::User) => requester.is_global_admin(),
-                    None => requester.is_global_admin(),
-                });
-                // set the name to display back to the user
-                project.name = data.name;
-                project
-                    .display_name(&requester_current_permission)
-                    .into_response()
-            }
-            Err(RenameProjectError::ProjectDoesNotExist) => {
+        match edit_entry(
+            config.clone(),
+            &requester,
+            project_id,
+            entry_id,
+            form.title,
+            form.body,
+        )
+        .await
+        {
+            // only the author and maintainers may edit entries, so we template it with admin privileges
+            Ok(entry) => entry.display(UserPermission::Admin).into_response(),
+            Err(EditEntryError::ProjectDoesNotExist) => {
                 warn!("Sending 404 because no project with id {project_id} exists.");
                 StatusCode::NOT_FOUND.into_response()
             }
-            Err(RenameProjectError::RequesterHasNoPermission(project_name)) => {
+            Err(EditEntryError::ArchivedProject(project_name)) => {
+                super::archived_project_response(&requester, &project_name)
+            }
+            Err(EditEntryError::EntryDoesNotExist) => {
+                warn!("Sending 404 because no entry with id {entry_id} exists in project {project_id}.");
+                StatusCode::NOT_FOUND.into_response()
+            }
+            Err(EditEntryError::RequesterHasNoPermission(project_name)) => {
                 warn!(
-                    "Sending 401 because user {} is not authorized to add member to group {}.",
+                    "Sending 401 because user {} is not authorized to edit entries in group {}.",
                     requester.name, project_name
                 );
                 StatusCode::UNAUTHORIZED.into_response()
             }
-            Err(RenameProjectError::Matrix(e)) => {
-                let error_uuid = Uuid::new_v4();
-                warn!("Sending internal server error because communication with Matrix failed: {e}. {error_uuid}");
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    InternalServerErrorTemplate { error_uuid },
-                )
-                    .into_response()
-            }
-            Err(RenameProjectError::DB(e)) => {
+            Err(EditEntryError::DB(e)) => {
                 let error_uuid = Uuid::new_v4();
                 warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
                 (
@@ -787,12 +2124,17 @@ pub(super) mod delete {
     use uuid::Uuid;
 
     use crate::{
-        actions::{remove_member_from_project, RemoveMemberError},
+        actions::{
+            delete_entry, delete_project, remove_member_from_project, revoke_api_token,
+            unlink_ldap_group, DeleteEntryError, DeleteProjectError, LdapGroupError,
+            RemoveMemberError, RevokeApiTokenError,
+        },
         config::Config,
+        types::UserPermission,
         web_server::{login::AuthSession, InternalServerErrorTemplate},
     };
 
-    use super::get_user_from_session;
+    use super::{get_user_from_session, post::LdapGroupForm};
 
     #[derive(Deserialize, Debug)]
     pub(crate) struct RemoveMemberForm {
@@ -820,6 +2162,9 @@ pub(super) mod delete {
                 warn!("Sending 404 because no project with id {project_id} exists.");
                 StatusCode::NOT_FOUND.into_response()
             }
+            Err(RemoveMemberError::ArchivedProject(project_name)) => {
+                super::archived_project_response(&requester, &project_name)
+            }
             Err(RemoveMemberError::PersonDoesNotExist) => {
                 warn!(
                     "Sending 400 because the person {} does not exist.",
@@ -854,4 +2199,178 @@ pub(super) mod delete {
             }
         }
     }
+
+    pub(super) async fn project_unlink_ldap_group(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path(project_id): Path<i32>,
+        Query(form): Query<LdapGroupForm>,
+    ) -> impl IntoResponse {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(e) => {
+                return e.into_response();
+            }
+        };
+
+        match unlink_ldap_group(config.clone(), &requester, project_id, &form.group_dn).await {
+            // only admins may unlink groups, so they still see everything
+            Ok(project) => project
+                .display_with_users(
+                    UserPermission::Admin,
+                    config.matrix_client.element_server().to_owned(),
+                )
+                .into_response(),
+            Err(LdapGroupError::ProjectDoesNotExist) => {
+                warn!("Sending 404 because no project with id {project_id} exists.");
+                StatusCode::NOT_FOUND.into_response()
+            }
+            Err(LdapGroupError::ArchivedProject(project_name)) => {
+                super::archived_project_response(&requester, &project_name)
+            }
+            Err(LdapGroupError::EmptyGroupDn) => {
+                warn!("Sending 400 because the DN of the LDAP group is empty.");
+                StatusCode::BAD_REQUEST.into_response()
+            }
+            Err(LdapGroupError::RequesterHasNoPermission(project_name)) => {
+                warn!(
+                    "Sending 401 because user {} is not authorized to unlink LDAP groups from group {}.",
+                    requester.name, project_name
+                );
+                StatusCode::UNAUTHORIZED.into_response()
+            }
+            Err(LdapGroupError::DB(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    pub(super) async fn entry_delete(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path((project_id, entry_id)): Path<(i32, i32)>,
+    ) -> impl IntoResponse {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(e) => {
+                return e.into_response();
+            }
+        };
+
+        match delete_entry(config.clone(), &requester, project_id, entry_id).await {
+            Ok(()) => (StatusCode::OK, "").into_response(),
+            Err(DeleteEntryError::ProjectDoesNotExist) => {
+                warn!("Sending 404 because no project with id {project_id} exists.");
+                StatusCode::NOT_FOUND.into_response()
+            }
+            Err(DeleteEntryError::ArchivedProject(project_name)) => {
+                super::archived_project_response(&requester, &project_name)
+            }
+            Err(DeleteEntryError::EntryDoesNotExist) => {
+                warn!("Sending 404 because no entry with id {entry_id} exists in project {project_id}.");
+                StatusCode::NOT_FOUND.into_response()
+            }
+            Err(DeleteEntryError::RequesterHasNoPermission(project_name)) => {
+                warn!(
+                    "Sending 401 because user {} is not authorized to delete entries in group {}.",
+                    requester.name, project_name
+                );
+                StatusCode::UNAUTHORIZED.into_response()
+            }
+            Err(DeleteEntryError::DB(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    pub(super) async fn project_delete(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path(project_id): Path<i32>,
+    ) -> impl IntoResponse {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(e) => {
+                return e.into_response();
+            }
+        };
+
+        match delete_project(config.clone(), &requester, project_id).await {
+            Ok(_) => (StatusCode::OK, "").into_response(),
+            Err(DeleteProjectError::ProjectDoesNotExist) => {
+                warn!("Sending 404 because no project with id {project_id} exists.");
+                StatusCode::NOT_FOUND.into_response()
+            }
+            Err(DeleteProjectError::RequesterHasNoPermission(project_name)) => {
+                warn!(
+                    "Sending 401 because user {} is not authorized to delete group {}.",
+                    requester.name, project_name
+                );
+                StatusCode::UNAUTHORIZED.into_response()
+            }
+            Err(DeleteProjectError::Matrix(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because communication with Matrix failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+            Err(DeleteProjectError::DB(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    pub(super) async fn token_revoke(
+        auth_session: AuthSession,
+        Extension(config): Extension<Arc<Config>>,
+        Path(token_id): Path<i32>,
+    ) -> impl IntoResponse {
+        let requester = match get_user_from_session(auth_session, config.clone()).await {
+            Ok(x) => x,
+            Err(e) => {
+                return e.into_response();
+            }
+        };
+
+        match revoke_api_token(config.clone(), &requester, token_id).await {
+            Ok(()) => (StatusCode::OK, "").into_response(),
+            Err(RevokeApiTokenError::TokenDoesNotExist) => {
+                warn!(
+                    "Sending 404 because user {} has no API token with id {token_id}.",
+                    requester.name
+                );
+                StatusCode::NOT_FOUND.into_response()
+            }
+            Err(RevokeApiTokenError::DB(e)) => {
+                let error_uuid = Uuid::new_v4();
+                warn!("Sending internal server error because a DB interaction failed: {e}. {error_uuid}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    InternalServerErrorTemplate { error_uuid },
+                )
+                    .into_response()
+            }
+        }
+    }
 }
@@ -0,0 +1,359 @@
+//! Slowing down password guessing
+//!
+//! Failed logins are counted per client address and per username. After a few free attempts,
+//! every further failure doubles the time until the next attempt is accepted, up to a maximum.
+//! Attempts that come too early are rejected without asking the auth backend, so that a client
+//! guessing passwords can neither lock accounts in the directory nor flood the LDAP server.
+//! A successful login only forgets the failures for its username, not those of the client.
+//!
+//! The counts are kept in memory; a restart forgets them.
+
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::Hash,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::config::LoginLimits;
+
+/// Failed logins of one client or username
+#[derive(Debug, Clone, Copy)]
+struct Failures {
+    count: u32,
+    /// Attempts that were let through and are still waiting for the auth backend. They count as
+    /// failures until they are settled, so that parallel attempts cannot slip past the backoff.
+    pending: u32,
+    last: Instant,
+}
+
+/// Count a new attempt in `map`, until it is settled
+fn reserve<K: Hash + Eq>(map: &mut HashMap<K, Failures>, key: K, now: Instant) {
+    let entry = map.entry(key).or_insert(Failures {
+        count: 0,
+        pending: 0,
+        last: now,
+    });
+    entry.pending = entry.pending.saturating_add(1);
+    entry.last = now;
+}
+
+/// Settle an attempt counted by [`reserve`]. Failures are kept; otherwise the attempt is dropped,
+/// and with it all failures if `forget` is set.
+fn settle<K: Hash + Eq + Borrow<Q>, Q: Hash + Eq + ?Sized>(
+    map: &mut HashMap<K, Failures>,
+    key: &Q,
+    failed: bool,
+    forget: bool,
+    now: Instant,
+) {
+    let Some(entry) = map.get_mut(key) else {
+        return;
+    };
+    entry.pending = entry.pending.saturating_sub(1);
+    if failed {
+        entry.count = entry.count.saturating_add(1);
+        entry.last = now;
+    } else if forget {
+        entry.count = 0;
+    };
+    if entry.count == 0 && entry.pending == 0 {
+        map.remove(key);
+    };
+}
+
+/// How an attempt that was let through ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Success,
+    Failure,
+    /// The auth backend could not be asked
+    Error,
+}
+
+#[derive(Debug)]
+pub(crate) struct LoginThrottle {
+    limits: LoginLimits,
+    by_address: Mutex<HashMap<IpAddr, Failures>>,
+    by_username: Mutex<HashMap<String, Failures>>,
+}
+
+impl LoginThrottle {
+    pub(crate) fn new(limits: LoginLimits) -> Self {
+        Self {
+            limits,
+            by_address: Mutex::new(HashMap::new()),
+            by_username: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// How long to wait after `count` failures
+    fn backoff(&self, count: u32) -> Duration {
+        if count < self.limits.free_attempts {
+            return Duration::ZERO;
+        };
+        let doublings = (count - self.limits.free_attempts).min(31);
+        self.limits
+            .backoff
+            .saturating_mul(1 << doublings)
+            .min(self.limits.max_backoff)
+    }
+
+    /// The time left until `failures` allow the next attempt
+    fn wait_time(&self, failures: Option<&Failures>, now: Instant) -> Duration {
+        match failures {
+            Some(x) => (x.last + self.backoff(x.count.saturating_add(x.pending)))
+                .saturating_duration_since(now),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// The attempt if it may be made now, otherwise the time until it may be made.
+    ///
+    /// An attempt that may be made is counted right away, until it is settled.
+    pub(crate) fn check(&self, address: IpAddr, username: &str) -> Result<Attempt<'_>, Duration> {
+        self.check_at(address, username, Instant::now())
+    }
+
+    fn check_at(
+        &self,
+        address: IpAddr,
+        username: &str,
+        now: Instant,
+    ) -> Result<Attempt<'_>, Duration> {
+        // failures long enough ago no longer count
+        let forget_after = self.limits.max_backoff;
+        let keep = |x: &mut Failures| {
+            x.pending > 0 || now.saturating_duration_since(x.last) <= forget_after
+        };
+        let mut by_address = self.by_address.lock().expect("Throttle mutex poisoned");
+        let mut by_username = self.by_username.lock().expect("Throttle mutex poisoned");
+        by_address.retain(|_, x| keep(x));
+        by_username.retain(|_, x| keep(x));
+
+        let username = username.to_lowercase();
+        let wait = self
+            .wait_time(by_address.get(&address), now)
+            .max(self.wait_time(by_username.get(&username), now));
+        if !wait.is_zero() {
+            return Err(wait);
+        };
+        reserve(&mut by_address, address, now);
+        reserve(&mut by_username, username.clone(), now);
+        Ok(Attempt {
+            throttle: self,
+            address,
+            username,
+            settled: false,
+        })
+    }
+
+    /// `username` has to be lowercase already
+    fn settle_at(&self, address: IpAddr, username: &str, outcome: Outcome, now: Instant) {
+        let failed = outcome == Outcome::Failure;
+        let mut by_address = self.by_address.lock().expect("Throttle mutex poisoned");
+        let mut by_username = self.by_username.lock().expect("Throttle mutex poisoned");
+        settle(&mut by_address, &address, failed, false, now);
+        settle(
+            &mut by_username,
+            username,
+            failed,
+            outcome == Outcome::Success,
+            now,
+        );
+    }
+}
+
+/// A login attempt that [`LoginThrottle::check`] let through
+///
+/// If it is dropped before it is settled, e.g. because the client went away while the auth
+/// backend was asked, it is settled as an error.
+#[must_use]
+pub(crate) struct Attempt<'a> {
+    throttle: &'a LoginThrottle,
+    address: IpAddr,
+    /// lowercase
+    username: String,
+    settled: bool,
+}
+impl Attempt<'_> {
+    /// Forget the failures for the username after a successful login.
+    ///
+    /// The failures of the client stay, so that logging in to an own account in between does not
+    /// let it guess the passwords of others for longer.
+    pub(crate) fn success(self) {
+        self.settle_at(Outcome::Success, Instant::now());
+    }
+
+    /// Count a failed login
+    pub(crate) fn failure(self) {
+        self.settle_at(Outcome::Failure, Instant::now());
+    }
+
+    /// Neither count nor forget anything, because the auth backend could not be asked
+    pub(crate) fn error(self) {
+        self.settle_at(Outcome::Error, Instant::now());
+    }
+
+    fn settle_at(mut self, outcome: Outcome, now: Instant) {
+        self.settled = true;
+        self.throttle
+            .settle_at(self.address, &self.username, outcome, now);
+    }
+}
+impl Drop for Attempt<'_> {
+    fn drop(&mut self) {
+        if !self.settled {
+            self.throttle
+                .settle_at(self.address, &self.username, Outcome::Error, Instant::now());
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn throttle() -> LoginThrottle {
+        LoginThrottle::new(LoginLimits {
+            free_attempts: 3,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        })
+    }
+
+    /// The time to wait, without counting an attempt
+    fn wait(throttle: &LoginThrottle, address: IpAddr, username: &str, now: Instant) -> Duration {
+        match throttle.check_at(address, username, now) {
+            Ok(attempt) => {
+                // not asked, so it does not count
+                attempt.settle_at(Outcome::Error, now);
+                Duration::ZERO
+            }
+            Err(wait) => wait,
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let throttle = throttle();
+        assert_eq!(throttle.backoff(0), Duration::ZERO);
+        assert_eq!(throttle.backoff(2), Duration::ZERO);
+        assert_eq!(throttle.backoff(3), Duration::from_secs(1));
+        assert_eq!(throttle.backoff(4), Duration::from_secs(2));
+        assert_eq!(throttle.backoff(6), Duration::from_secs(8));
+        assert_eq!(throttle.backoff(10), Duration::from_secs(60));
+        assert_eq!(throttle.backoff(u32::MAX), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn failures_are_counted_per_address_and_username() {
+        let throttle = throttle();
+        let start = Instant::now();
+        let attacker: IpAddr = "192.0.2.1".parse().unwrap();
+        let other: IpAddr = "192.0.2.2".parse().unwrap();
+
+        for _ in 0..3 {
+            throttle
+                .check_at(attacker, "adam", start)
+                .unwrap()
+                .settle_at(Outcome::Failure, start);
+        }
+        // the fourth attempt has to wait, from the same address and for the same user
+        assert_eq!(
+            wait(&throttle, attacker, "beth", start),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            wait(&throttle, other, "Adam", start),
+            Duration::from_secs(1)
+        );
+        throttle
+            .check_at(other, "beth", start)
+            .unwrap()
+            .settle_at(Outcome::Success, start);
+        let attempt = throttle
+            .check_at(attacker, "adam", start + Duration::from_secs(1))
+            .unwrap();
+
+        // a successful login starts over for the user, but not for the client
+        attempt.settle_at(Outcome::Success, start);
+        throttle
+            .check_at(other, "adam", start)
+            .unwrap()
+            .settle_at(Outcome::Success, start);
+        assert_eq!(
+            wait(&throttle, attacker, "beth", start + Duration::from_secs(1)),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn parallel_attempts_are_counted_before_they_finish() {
+        let throttle = throttle();
+        let start = Instant::now();
+        let address: IpAddr = "192.0.2.1".parse().unwrap();
+        let attempts = (0..3)
+            .map(|_| throttle.check_at(address, "adam", start).unwrap())
+            .collect::<Vec<_>>();
+        // all three are still waiting for the auth backend
+        assert_eq!(
+            wait(&throttle, address, "adam", start),
+            Duration::from_secs(1)
+        );
+
+        // errors of the backend do not count as failures
+        for attempt in attempts {
+            attempt.settle_at(Outcome::Error, start);
+        }
+        assert!(throttle.by_address.lock().unwrap().is_empty());
+        assert!(throttle.by_username.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn dropped_attempts_do_not_count() {
+        let throttle = throttle();
+        let start = Instant::now();
+        let address: IpAddr = "192.0.2.1".parse().unwrap();
+        for _ in 0..3 {
+            throttle
+                .check_at(address, "adam", start)
+                .unwrap()
+                .settle_at(Outcome::Failure, start);
+        }
+        // the client went away while the auth backend was asked
+        let attempt = throttle
+            .check_at(address, "adam", start + Duration::from_secs(1))
+            .unwrap();
+        drop(attempt);
+        assert_eq!(throttle.by_username.lock().unwrap()["adam"].pending, 0);
+
+        // so the failures expire as usual
+        let later = start + Duration::from_secs(62);
+        assert_eq!(wait(&throttle, address, "adam", later), Duration::ZERO);
+        assert!(throttle.by_address.lock().unwrap().is_empty());
+        assert!(throttle.by_username.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn old_failures_are_forgotten() {
+        let throttle = throttle();
+        let start = Instant::now();
+        let address: IpAddr = "192.0.2.1".parse().unwrap();
+        for _ in 0..3 {
+            throttle
+                .check_at(address, "adam", start)
+                .unwrap()
+                .settle_at(Outcome::Failure, start);
+        }
+        let later = start + Duration::from_secs(61);
+        let attempt = throttle
+            .check_at("192.0.2.2".parse().unwrap(), "beth", later)
+            .unwrap();
+        assert!(throttle.by_address.lock().unwrap().get(&address).is_none());
+        assert!(throttle.by_username.lock().unwrap().get("adam").is_none());
+        attempt.settle_at(Outcome::Success, later);
+    }
+}